@@ -0,0 +1,309 @@
+// Battery awareness. On battery, holding off sleep works against the user, so
+// the keep-awake blocker (`--keep-awake`) stands down unless told otherwise;
+// a session that looks longer than the battery will last gets a warning
+// before it starts; and the session log records the power source of each
+// phase.
+//
+//   [battery]
+//   keep_awake = false     keep the system awake on battery too
+//   warn = true            warn before a session the battery won't last
+//
+// Battery state is read from:
+//   macOS   … `pmset -g batt`
+//   Linux   … /sys/class/power_supply (capacity, status, energy/power)
+//   Windows … Win32_Battery through PowerShell
+// A machine without a battery is never on battery, and logs no power source.
+
+use crate::{duration::format_duration, session::PomodoroSession};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[cfg(not(target_os = "linux"))]
+use std::process::Command;
+
+/// Where the power comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+/// The battery at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Battery {
+    pub source: PowerSource,
+    /// Charge left, in percent
+    pub percent: Option<u8>,
+    /// How long the charge is estimated to last (on battery)
+    pub remaining: Option<Duration>,
+}
+
+/// The `[battery]` table of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatterySettings {
+    /// Keep the system awake (`--keep-awake`) on battery too
+    pub keep_awake: bool,
+    /// Warn before a session longer than the battery is estimated to last
+    pub warn: bool,
+}
+
+impl Default for BatterySettings {
+    fn default() -> Self {
+        BatterySettings {
+            keep_awake: false,
+            warn: true,
+        }
+    }
+}
+
+/// The power source now, if it can be told
+pub fn power_source() -> Option<PowerSource> {
+    read().map(|battery| battery.source)
+}
+
+/// Whether the machine is running on battery
+pub fn on_battery() -> bool {
+    power_source() == Some(PowerSource::Battery)
+}
+
+// Warn when the battery is unlikely to last the session
+pub fn check(session: &PomodoroSession, settings: &BatterySettings) {
+    if !settings.warn || session.dry_run {
+        return;
+    }
+    let Some(battery) = read().filter(|battery| battery.source == PowerSource::Battery) else {
+        return;
+    };
+    if let Some(warning) = warning(&battery, session_len(session)) {
+        log::warn!("{}", warning);
+    }
+}
+
+// What to say about a session of `len` seconds on this battery, if anything
+fn warning(battery: &Battery, len: Option<u64>) -> Option<String> {
+    let remaining = battery.remaining?.as_secs();
+    let len = len.filter(|&len| len > remaining)?;
+    let charge = battery
+        .percent
+        .map(|percent| format!("{}%, ", percent))
+        .unwrap_or_default();
+    Some(format!(
+        "On battery ({}about {} left): that may not last this {} session; plug in to be safe.",
+        charge,
+        format_duration(remaining),
+        format_duration(len)
+    ))
+}
+
+// Seconds the rest of the session should take: every phase still to come, or
+// up to the stop time with --repeat-until (unknown for --cycles 0 without one)
+fn session_len(session: &PomodoroSession) -> Option<u64> {
+    if session.cycles == 0 {
+        let stop = session.repeat_until?;
+        return Some((stop - Local::now()).num_seconds().max(0) as u64);
+    }
+    let mut rest = session.clone();
+    let mut len = rest.remaining();
+    while rest.advance().is_some() {
+        len += rest.phase_len();
+    }
+    Some(len)
+}
+
+#[cfg(target_os = "macos")]
+fn read() -> Option<Battery> {
+    let output = Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "linux")]
+fn read() -> Option<Battery> {
+    use std::fs;
+    let read = |path: &std::path::Path| {
+        fs::read_to_string(path)
+            .ok()
+            .map(|text| text.trim().to_string())
+    };
+    let entries = fs::read_dir("/sys/class/power_supply").ok()?;
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if read(&dir.join("type")).as_deref() != Some("Battery") {
+            continue;
+        }
+        return parse_sysfs(
+            &read(&dir.join("status"))?,
+            read(&dir.join("capacity")).as_deref(),
+            read(&dir.join("energy_now")).or_else(|| read(&dir.join("charge_now"))),
+            read(&dir.join("power_now")).or_else(|| read(&dir.join("current_now"))),
+        );
+    }
+    None
+}
+
+// Other Unix systems: not known
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "linux"))))]
+fn read() -> Option<Battery> {
+    None
+}
+
+#[cfg(windows)]
+fn read() -> Option<Battery> {
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "Get-CimInstance Win32_Battery | Select-Object -First 1 | \
+             ForEach-Object { \"$($_.EstimatedChargeRemaining) $($_.BatteryStatus) $($_.EstimatedRunTime)\" }",
+        ])
+        .output()
+        .ok()?;
+    parse_win32(&String::from_utf8_lossy(&output.stdout))
+}
+
+// `pmset -g batt`, e.g.
+//   Now drawing from 'Battery Power'
+//    -InternalBattery-0 (id=4653155)	85%; discharging; 3:12 remaining present: true
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_pmset(text: &str) -> Option<Battery> {
+    let line = text.lines().find(|line| line.contains("InternalBattery"))?;
+    let source = if text.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    };
+    let percent = line
+        .split_whitespace()
+        .find_map(|word| word.strip_suffix("%;")?.parse().ok());
+    let remaining = line
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .windows(2)
+        .find(|words| words[1] == "remaining")
+        .and_then(|words| {
+            let (hours, minutes) = words[0].split_once(':')?;
+            let minutes = hours.parse::<u64>().ok()? * 60 + minutes.parse::<u64>().ok()?;
+            Some(Duration::from_secs(minutes * 60))
+        });
+    Some(Battery {
+        source,
+        percent,
+        remaining,
+    })
+}
+
+// A battery in /sys/class/power_supply: its status ("Discharging", ...),
+// capacity (percent) and the energy left and power drawn (µWh and µW, or µAh
+// and µA)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_sysfs(
+    status: &str,
+    capacity: Option<&str>,
+    energy: Option<String>,
+    power: Option<String>,
+) -> Option<Battery> {
+    let source = match status {
+        "Discharging" => PowerSource::Battery,
+        _ => PowerSource::Ac,
+    };
+    let number = |value: Option<String>| value?.parse::<u64>().ok().filter(|&n| n > 0);
+    let remaining = match (source, number(energy), number(power)) {
+        (PowerSource::Battery, Some(energy), Some(power)) => {
+            Some(Duration::from_secs(energy * 3600 / power))
+        }
+        _ => None,
+    };
+    Some(Battery {
+        source,
+        percent: capacity.and_then(|capacity| capacity.parse().ok()),
+        remaining,
+    })
+}
+
+// "<percent> <BatteryStatus> <EstimatedRunTime in minutes>" from Win32_Battery;
+// status 1 is discharging, and nothing at all means there is no battery
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_win32(text: &str) -> Option<Battery> {
+    let mut fields = text
+        .split_whitespace()
+        .map(|field| field.parse::<u64>().ok());
+    let percent = fields.next()?;
+    let status = fields.next()??;
+    let minutes = fields.next().flatten();
+    let source = if status == 1 {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    };
+    Some(Battery {
+        source,
+        percent: percent.and_then(|percent| u8::try_from(percent).ok()),
+        remaining: minutes
+            .filter(|_| source == PowerSource::Battery)
+            .map(|minutes| Duration::from_secs(minutes * 60)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_battery_state_on_each_system() {
+        let pmset = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t85%; discharging; 3:12 remaining present: true\n";
+        assert_eq!(
+            parse_pmset(pmset),
+            Some(Battery {
+                source: PowerSource::Battery,
+                percent: Some(85),
+                remaining: Some(Duration::from_secs(192 * 60)),
+            })
+        );
+        let plugged = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(parse_pmset(plugged).unwrap().source, PowerSource::Ac);
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+
+        let sysfs = parse_sysfs(
+            "Discharging",
+            Some("42"),
+            Some("20000000".to_string()),
+            Some("10000000".to_string()),
+        )
+        .unwrap();
+        assert_eq!(sysfs.source, PowerSource::Battery);
+        assert_eq!(sysfs.percent, Some(42));
+        assert_eq!(sysfs.remaining, Some(Duration::from_secs(2 * 3600)));
+        assert_eq!(
+            parse_sysfs("Charging", Some("42"), None, None)
+                .unwrap()
+                .source,
+            PowerSource::Ac
+        );
+
+        let win32 = parse_win32("64 1 95\r\n").unwrap();
+        assert_eq!(win32.source, PowerSource::Battery);
+        assert_eq!(win32.remaining, Some(Duration::from_secs(95 * 60)));
+        assert_eq!(parse_win32("100 2 71582788").unwrap().remaining, None);
+        assert_eq!(parse_win32(""), None);
+    }
+
+    #[test]
+    fn warns_when_the_session_outlasts_the_battery() {
+        let battery = Battery {
+            source: PowerSource::Battery,
+            percent: Some(20),
+            remaining: Some(Duration::from_secs(3600)),
+        };
+        let session = PomodoroSession::new(1500, 300, 4);
+        assert_eq!(session_len(&session), Some(4 * 1800));
+        assert_eq!(
+            warning(&battery, session_len(&session)).unwrap(),
+            "On battery (20%, about 1h left): that may not last this 2h session; plug in to be safe."
+        );
+        assert_eq!(warning(&battery, Some(1800)), None);
+        assert_eq!(session_len(&PomodoroSession::new(1500, 300, 0)), None);
+    }
+}
@@ -0,0 +1,242 @@
+// App blocker: distracting apps are quit (or hidden) when focus starts.
+//
+//   --apps Slack,Mail,Discord   which apps (this list by default)
+//   --app-action quit|hide      quit them (default) or only hide them
+//   --relaunch-apps             start quit apps again for breaks and at the end
+//
+// or in the config file:
+//
+//   [apps]
+//   names = ["Slack", "Discord"]
+//   action = "hide"
+//   relaunch = true
+//
+// macOS asks apps to quit, or hides them, via AppleScript. Elsewhere a quit
+// app's process is killed by name with `pkill`; as there is no portable way
+// to hide a window, "hide" suspends the process (SIGSTOP) until release. On
+// Windows `taskkill` asks <app>.exe to close; "hide" isn't supported there.
+// Only apps that were actually running are relaunched or shown again.
+
+use super::DistractionBlocker;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+/// Apps quit when no list is configured
+pub const DEFAULT_APPS: &[&str] = &["Slack", "Mail", "Discord"];
+
+/// What happens to the apps during focus
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AppAction {
+    #[default]
+    Quit,
+    Hide,
+}
+
+/// The `[apps]` table of the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppSettings {
+    pub names: Vec<String>,
+    pub action: Option<AppAction>,
+    pub relaunch: bool,
+}
+
+pub struct AppBlocker {
+    apps: Vec<String>,
+    action: AppAction,
+    relaunch: bool,
+    /// Apps quit or hidden by the last engage, to bring back on release
+    affected: Mutex<Vec<String>>,
+}
+
+impl AppBlocker {
+    pub fn new(apps: &[String], action: AppAction, relaunch: bool) -> Self {
+        let apps = if apps.is_empty() {
+            DEFAULT_APPS.iter().map(|s| s.to_string()).collect()
+        } else {
+            apps.to_vec()
+        };
+        AppBlocker {
+            apps,
+            action,
+            relaunch,
+            affected: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl DistractionBlocker for AppBlocker {
+    fn name(&self) -> &'static str {
+        "apps"
+    }
+
+    // Each session quits (and relaunches) the apps it found running
+    fn shared(&self) -> bool {
+        false
+    }
+
+    fn engage(&self) -> io::Result<()> {
+        let mut affected = self.affected.lock().unwrap();
+        for app in &self.apps {
+            if affected.contains(app) {
+                continue;
+            }
+            let was_running = match self.action {
+                AppAction::Quit => quit_app(app)?,
+                AppAction::Hide => hide_app(app, true)?,
+            };
+            if was_running {
+                crate::say!(
+                    "{} {}",
+                    match self.action {
+                        AppAction::Quit => "Quit",
+                        AppAction::Hide => "Hid",
+                    },
+                    app
+                );
+                affected.push(app.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn release(&self) -> io::Result<()> {
+        let affected = std::mem::take(&mut *self.affected.lock().unwrap());
+        for app in &affected {
+            match self.action {
+                AppAction::Quit if self.relaunch => {
+                    log::debug!("relaunching {}", app);
+                    launch_app(app)?;
+                }
+                AppAction::Quit => {}
+                AppAction::Hide => {
+                    hide_app(app, false)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Ask the app to quit; returns whether it was running
+#[cfg(target_os = "macos")]
+fn quit_app(app: &str) -> io::Result<bool> {
+    // `quit app` on its own would launch an app that isn't running
+    osascript(&format!(
+        "if application \"{0}\" is running then\n\
+         quit application \"{0}\"\n\
+         return \"yes\"\n\
+         end if",
+        app
+    ))
+}
+
+// Kill the process by name; returns whether one was running
+#[cfg(all(unix, not(target_os = "macos")))]
+fn quit_app(app: &str) -> io::Result<bool> {
+    // Linux process names are usually lowercase (slack, discord, ...)
+    pkill(&["-i", "-x", app])
+}
+
+// Hide (or show again) the app's windows; returns whether it was running
+#[cfg(target_os = "macos")]
+fn hide_app(app: &str, hide: bool) -> io::Result<bool> {
+    osascript(&format!(
+        "tell application \"System Events\"\n\
+         if exists process \"{0}\" then\n\
+         set visible of process \"{0}\" to {1}\n\
+         return \"yes\"\n\
+         end if\n\
+         end tell",
+        app,
+        if hide { "false" } else { "true" }
+    ))
+}
+
+// Suspend (or continue) the process; returns whether one was running
+#[cfg(all(unix, not(target_os = "macos")))]
+fn hide_app(app: &str, hide: bool) -> io::Result<bool> {
+    let signal = if hide { "-STOP" } else { "-CONT" };
+    pkill(&[signal, "-i", "-x", app])
+}
+
+// Ask <app>.exe to close (without /F, like closing its window); returns
+// whether it was running
+#[cfg(windows)]
+fn quit_app(app: &str) -> io::Result<bool> {
+    let status = Command::new("taskkill")
+        .args(["/IM", &format!("{}.exe", app)])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    // 128: no such process
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(128) => Ok(false),
+        _ => Err(io::Error::other(format!("taskkill failed ({})", status))),
+    }
+}
+
+#[cfg(windows)]
+fn hide_app(_app: &str, _hide: bool) -> io::Result<bool> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--app-action hide isn't supported on Windows; use quit",
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn launch_app(app: &str) -> io::Result<()> {
+    // -g: in the background, so it doesn't take focus
+    Command::new("open").args(["-g", "-a", app]).status()?;
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn launch_app(app: &str) -> io::Result<()> {
+    Command::new(app.to_lowercase())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+// `start` finds apps registered with Windows (Slack, Discord, ...) by name
+#[cfg(windows)]
+fn launch_app(app: &str) -> io::Result<()> {
+    Command::new("cmd")
+        .args(["/C", "start", "", app])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+// Run AppleScript; true if it returned "yes"
+#[cfg(target_os = "macos")]
+fn osascript(script: &str) -> io::Result<bool> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .stderr(Stdio::null())
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "yes")
+}
+
+// pkill exits 0 if it matched a process and 1 if none matched
+#[cfg(all(unix, not(target_os = "macos")))]
+fn pkill(args: &[&str]) -> io::Result<bool> {
+    let status = Command::new("pkill").args(args).status()?;
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(io::Error::other(format!("pkill failed ({})", status))),
+    }
+}
@@ -0,0 +1,180 @@
+// Keep-awake "blocker": holds off system sleep during focus (`--keep-awake`).
+//
+//   macOS   … `caffeinate -i -w <pid>`
+//   Linux   … `systemd-inhibit --what=idle:sleep tail --pid=<pid> -f /dev/null`
+//   Windows … SetThreadExecutionState
+//
+// The helper processes watch our pid, so the inhibitor goes away even if
+// pomo is killed without a chance to release it. On battery it does nothing
+// unless `[battery] keep_awake = true`.
+
+use super::DistractionBlocker;
+use crate::battery;
+use std::{
+    io,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+#[cfg(not(windows))]
+use std::process::{Child, Command, Stdio};
+
+#[derive(Default)]
+pub struct AwakeBlocker {
+    /// The running inhibitor, while engaged
+    #[cfg(not(windows))]
+    inhibitor: Mutex<Option<Child>>,
+    #[cfg(windows)]
+    inhibitor: Mutex<bool>,
+    /// Keep the system awake on battery too
+    on_battery: bool,
+    /// Said once that it stands down on battery
+    noted: AtomicBool,
+}
+
+impl AwakeBlocker {
+    pub fn new(on_battery: bool) -> Self {
+        AwakeBlocker {
+            on_battery,
+            ..Default::default()
+        }
+    }
+
+    // Running on battery and not asked to keep awake anyway
+    fn stand_down(&self) -> bool {
+        if self.on_battery || !battery::on_battery() {
+            return false;
+        }
+        if !self.noted.swap(true, Ordering::SeqCst) {
+            crate::say!("On battery: not keeping the system awake (see [battery] keep_awake).");
+        }
+        true
+    }
+}
+
+impl DistractionBlocker for AwakeBlocker {
+    fn name(&self) -> &'static str {
+        "keep-awake"
+    }
+
+    // Each session holds its own inhibitor
+    fn shared(&self) -> bool {
+        false
+    }
+
+    // caffeinate comes with macOS, and Windows has nothing to install
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn check(&self) -> io::Result<()> {
+        if !crate::sound::command_exists("systemd-inhibit") {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "--keep-awake needs `systemd-inhibit`",
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn engage(&self) -> io::Result<()> {
+        let mut inhibitor = self.inhibitor.lock().unwrap();
+        // Already engaged (e.g. re-engaged after waking up)
+        if inhibitor.is_some() || self.stand_down() {
+            return Ok(());
+        }
+        log::debug!("keeping the system awake");
+        let child = inhibit_command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(
+                    e.kind(),
+                    format!(
+                        "--keep-awake needs `{}`",
+                        inhibit_command().get_program().display()
+                    ),
+                ),
+                _ => e,
+            })?;
+        *inhibitor = Some(child);
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn release(&self) -> io::Result<()> {
+        if let Some(mut child) = self.inhibitor.lock().unwrap().take() {
+            log::debug!("allowing the system to sleep");
+            child.kill()?;
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn engage(&self) -> io::Result<()> {
+        let mut engaged = self.inhibitor.lock().unwrap();
+        if !*engaged && !self.stand_down() {
+            log::debug!("keeping the system awake");
+            set_execution_state(ES_CONTINUOUS | ES_SYSTEM_REQUIRED)?;
+            *engaged = true;
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn release(&self) -> io::Result<()> {
+        let mut engaged = self.inhibitor.lock().unwrap();
+        if *engaged {
+            log::debug!("allowing the system to sleep");
+            set_execution_state(ES_CONTINUOUS)?;
+            *engaged = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn inhibit_command() -> Command {
+    let mut command = Command::new("caffeinate");
+    command.args(["-i", "-w", &std::process::id().to_string()]);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn inhibit_command() -> Command {
+    let mut command = Command::new("systemd-inhibit");
+    command.args([
+        "--what=idle:sleep",
+        "--who=pomo",
+        "--why=Focus session",
+        "tail",
+        &format!("--pid={}", std::process::id()),
+        "-f",
+        "/dev/null",
+    ]);
+    command
+}
+
+#[cfg(windows)]
+const ES_CONTINUOUS: u32 = 0x8000_0000;
+#[cfg(windows)]
+const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn SetThreadExecutionState(flags: u32) -> u32;
+}
+
+// The state applies to the calling thread, which is the one running the session
+#[cfg(windows)]
+fn set_execution_state(flags: u32) -> io::Result<()> {
+    // Safety: takes a plain flag word; returns 0 on failure
+    if unsafe { SetThreadExecutionState(flags) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
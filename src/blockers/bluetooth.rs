@@ -0,0 +1,213 @@
+// Bluetooth blocker: the radio is off during focus, to silence AirDrop,
+// handoff and phone notifications.
+//   macOS … `blueutil --power 0|1` (brew install blueutil)
+//   Linux … `rfkill block|unblock bluetooth`, or `bluetoothctl power off|on`
+// (not supported on Windows, which has no command for it)
+//
+// Like Wi-Fi it comes back on for breaks, pauses and exit, but only if it was
+// on when focus started: a radio the user had off stays off.
+
+use super::DistractionBlocker;
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use std::process::Command;
+
+#[derive(Default)]
+pub struct BluetoothBlocker {
+    /// Bluetooth was on and was turned off here, so release turns it back on
+    turned_off: AtomicBool,
+}
+
+impl DistractionBlocker for BluetoothBlocker {
+    fn name(&self) -> &'static str {
+        "bluetooth"
+    }
+
+    fn check(&self) -> io::Result<()> {
+        usable()
+    }
+
+    fn engage(&self) -> io::Result<()> {
+        // Assume it's on if the state can't be read
+        if self.turned_off.load(Ordering::SeqCst) || is_powered().unwrap_or(true) {
+            set_power(false)?;
+            self.turned_off.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    fn release(&self) -> io::Result<()> {
+        if self.turned_off.load(Ordering::SeqCst) {
+            set_power(true)?;
+            self.turned_off.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+// Turn Bluetooth on/off
+fn set_power(on: bool) -> io::Result<()> {
+    log::debug!("setting Bluetooth {}", if on { "on" } else { "off" });
+    run_power_command(on)
+}
+
+#[cfg(target_os = "macos")]
+fn run_power_command(on: bool) -> io::Result<()> {
+    let status = Command::new("blueutil")
+        .args(["--power", if on { "1" } else { "0" }])
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                "--block bluetooth needs `blueutil` (brew install blueutil)",
+            ),
+            _ => e,
+        })?;
+    check(status, "blueutil")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn run_power_command(on: bool) -> io::Result<()> {
+    let rfkill = Command::new("rfkill")
+        .args([if on { "unblock" } else { "block" }, "bluetooth"])
+        .status();
+    match rfkill {
+        Ok(status) => check(status, "rfkill"),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let status = Command::new("bluetoothctl")
+                .args(["power", if on { "on" } else { "off" }])
+                .status()
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => io::Error::new(
+                        e.kind(),
+                        "--block bluetooth needs `rfkill` or `bluetoothctl`",
+                    ),
+                    _ => e,
+                })?;
+            check(status, "bluetoothctl")
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn run_power_command(_on: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--block bluetooth isn't supported on Windows",
+    ))
+}
+
+// Bluetooth can be switched if its state can be read, by the same programs
+#[cfg(target_os = "macos")]
+fn usable() -> io::Result<()> {
+    is_powered().map(drop).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "--block bluetooth needs `blueutil` (brew install blueutil)",
+        )
+    })
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn usable() -> io::Result<()> {
+    is_powered().map(drop).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "--block bluetooth needs `rfkill` or `bluetoothctl`, and a Bluetooth adapter",
+        )
+    })
+}
+
+// Not supported: the same error switching it would give
+#[cfg(windows)]
+fn usable() -> io::Result<()> {
+    run_power_command(false)
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn check(status: std::process::ExitStatus, program: &str) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed ({})", program, status)))
+    }
+}
+
+// Whether Bluetooth is on, or `None` if that can't be told
+#[cfg(target_os = "macos")]
+fn is_powered() -> Option<bool> {
+    let output = Command::new("blueutil").arg("--power").output().ok()?;
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn is_powered() -> Option<bool> {
+    if let Ok(output) = Command::new("rfkill").args(["list", "bluetooth"]).output()
+        && output.status.success()
+    {
+        return parse_rfkill(&String::from_utf8_lossy(&output.stdout));
+    }
+    let output = Command::new("bluetoothctl").arg("show").output().ok()?;
+    parse_bluetoothctl_show(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(windows)]
+fn is_powered() -> Option<bool> {
+    None
+}
+
+// `rfkill list bluetooth`, e.g.
+//   0: hci0: Bluetooth
+//           Soft blocked: no
+//           Hard blocked: no
+// On unless every device is blocked
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn parse_rfkill(text: &str) -> Option<bool> {
+    let blocked: Vec<bool> = text
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Soft blocked: "))
+        .map(|value| value.trim() == "yes")
+        .collect();
+    (!blocked.is_empty()).then(|| blocked.iter().any(|&b| !b))
+}
+
+// The "Powered: yes|no" line of `bluetoothctl show`
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn parse_bluetoothctl_show(text: &str) -> Option<bool> {
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix("Powered: "))
+        .map(|value| value.trim() == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rfkill_soft_block() {
+        let on = "0: hci0: Bluetooth\n\tSoft blocked: no\n\tHard blocked: no\n";
+        let off = "0: hci0: Bluetooth\n\tSoft blocked: yes\n\tHard blocked: no\n";
+        assert_eq!(parse_rfkill(on), Some(true));
+        assert_eq!(parse_rfkill(off), Some(false));
+        assert_eq!(parse_rfkill(""), None);
+    }
+
+    #[test]
+    fn reads_bluetoothctl_power() {
+        let text = "Controller 00:11:22:33:44:55 (public)\n\tName: laptop\n\tPowered: no\n";
+        assert_eq!(parse_bluetoothctl_show(text), Some(false));
+        assert_eq!(
+            parse_bluetoothctl_show("No default controller available\n"),
+            None
+        );
+    }
+}
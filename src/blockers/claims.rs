@@ -0,0 +1,89 @@
+// Blockers shared between sessions. Wi-Fi, Bluetooth, the hosts file and the
+// rest exist once per machine, but several named sessions can run at once, so
+// each session engaging a blocker leaves a claim in the state directory
+// (claims/wifi/emails). Only the first claim really engages the blocker and
+// only the last one releases it: a break in one session doesn't turn Wi-Fi
+// back on under another session's focus. The blocker keeps the settings of the
+// session that engaged it (e.g. its list of sites).
+//
+// Claiming and giving up go one session at a time (claims/.lock): two
+// sessions starting together would otherwise each see the other's claim and
+// both leave the blocker alone.
+//
+// Claims of sessions that are no longer running (nothing answers on their
+// control socket) don't count and are cleared away.
+
+use crate::{daemon, state};
+use std::{
+    fs::{self, File},
+    io,
+    path::PathBuf,
+};
+
+fn claims_dir(blocker: &str) -> PathBuf {
+    state::state_dir().join("claims").join(blocker)
+}
+
+// Hold the lock on the claims until the returned file is dropped
+fn lock() -> io::Result<File> {
+    let dir = state::state_dir().join("claims");
+    fs::create_dir_all(&dir)?;
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(".lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+// This session's name, as used for its claim file
+fn own_name() -> String {
+    state::name().unwrap_or_else(|| state::DEFAULT_NAME.to_string())
+}
+
+// Claim a blocker for this session. Returns whether it is up to this session
+// to engage it (no other running session has it).
+pub fn claim(blocker: &str) -> io::Result<bool> {
+    let _lock = lock()?;
+    let dir = claims_dir(blocker);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(own_name()), std::process::id().to_string())?;
+    Ok(others(blocker).is_empty())
+}
+
+// Give up this session's claim. Returns whether the blocker should be
+// released (no other running session still has it).
+pub fn release(blocker: &str) -> io::Result<bool> {
+    let _lock = lock()?;
+    match fs::remove_file(claims_dir(blocker).join(own_name())) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    Ok(others(blocker).is_empty())
+}
+
+// Running sessions other than this one that have the blocker claimed
+pub fn others(blocker: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(claims_dir(blocker)) else {
+        return Vec::new();
+    };
+    let own = own_name();
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name == own {
+            continue;
+        }
+        if daemon::is_running_named(daemon::session_name(&name)) {
+            names.push(name);
+        } else {
+            // Left behind by a session that died
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    names.sort();
+    names
+}
@@ -0,0 +1,96 @@
+// macOS Do Not Disturb / Focus mode control.
+//
+// Recent macOS versions have no direct command for Focus modes, so this runs
+// two user-created Shortcuts (via the `shortcuts` CLI):
+//   "pomo-dnd-on"  … e.g. a single "Set Do Not Disturb: On" action
+//   "pomo-dnd-off" … e.g. a single "Set Do Not Disturb: Off" action
+// If the `shortcuts` command isn't available (macOS 11 and older), the legacy
+// `doNotDisturb` preference of Notification Center is toggled instead.
+
+use super::DistractionBlocker;
+use std::{io, process::Command};
+
+pub const SHORTCUT_ON: &str = "pomo-dnd-on";
+pub const SHORTCUT_OFF: &str = "pomo-dnd-off";
+
+pub struct DndBlocker;
+
+impl DistractionBlocker for DndBlocker {
+    fn name(&self) -> &'static str {
+        "dnd"
+    }
+
+    // Both Shortcuts must exist (`shortcuts list`), or the legacy preference
+    // be there to toggle
+    fn check(&self) -> io::Result<()> {
+        let output = match Command::new("shortcuts").arg("list").output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Command::new("defaults")
+                    .arg("help")
+                    .output()
+                    .map(drop)
+                    .map_err(|e| match e.kind() {
+                        io::ErrorKind::NotFound => io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            "--block dnd only works on macOS",
+                        ),
+                        _ => e,
+                    });
+            }
+            Err(e) => return Err(e),
+        };
+        let list = String::from_utf8_lossy(&output.stdout);
+        let missing: Vec<&str> = [SHORTCUT_ON, SHORTCUT_OFF]
+            .into_iter()
+            .filter(|name| !list.lines().any(|line| line.trim() == *name))
+            .collect();
+        if !missing.is_empty() {
+            return Err(io::Error::other(format!(
+                "no Shortcut named {} (create it in the Shortcuts app)",
+                missing.join(" or ")
+            )));
+        }
+        Ok(())
+    }
+
+    fn engage(&self) -> io::Result<()> {
+        set_dnd(true)
+    }
+
+    fn release(&self) -> io::Result<()> {
+        set_dnd(false)
+    }
+}
+
+// Turn Do Not Disturb on/off on macOS
+fn set_dnd(on: bool) -> io::Result<()> {
+    log::debug!("setting Do Not Disturb {}", if on { "on" } else { "off" });
+
+    let shortcut = if on { SHORTCUT_ON } else { SHORTCUT_OFF };
+    match Command::new("shortcuts").args(["run", shortcut]).status() {
+        Ok(status) if !status.success() => Err(io::Error::other(format!(
+            "shortcuts run {} failed ({})",
+            shortcut, status
+        ))),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => set_dnd_legacy(on),
+        Err(e) => Err(e),
+    }
+}
+
+// Pre-Monterey fallback: flip the preference and restart Notification Center
+fn set_dnd_legacy(on: bool) -> io::Result<()> {
+    Command::new("defaults")
+        .args([
+            "-currentHost",
+            "write",
+            "com.apple.notificationcenterui",
+            "doNotDisturb",
+            "-boolean",
+            if on { "true" } else { "false" },
+        ])
+        .status()?;
+    Command::new("killall").arg("NotificationCenter").status()?;
+    Ok(())
+}
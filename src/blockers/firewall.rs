@@ -0,0 +1,516 @@
+// Firewall blocker: rather than turning Wi-Fi off (which drops a corporate VPN
+// and SSH sessions), outgoing traffic is refused during focus except for
+//   - VPN tunnel interfaces (utun, tun, wg, ppp and ipsec ones by default)
+//   - the hosts and networks in the allowlist, resolved when focus starts
+//   - DNS, loopback and the TCP ports kept open (22 for SSH by default)
+//   - on Linux, connections already open when focus starts
+//
+//   --block firewall --allow vpn.example.com,10.0.0.0/8
+//
+//   [firewall]
+//   allow = ["vpn.example.com", "git.example.com", "10.0.0.0/8"]
+//   ports = [22]
+//   interfaces = ["utun", "wg"]
+//
+//   Linux … a `rust-pomo` chain jumped to from OUTPUT (iptables, and
+//           ip6tables if installed)
+//   macOS … rules in the pf anchor `com.apple/rust-pomo`; pf is enabled with
+//           a reference (`pfctl -E`) that release gives back
+// Both need root (e.g. run with sudo); Windows isn't supported. On macOS the
+// VPN server itself should be in the allowlist, as pf doesn't know the
+// connections made before focus started.
+//
+// Crash safety: as with the hosts file, rules left behind by a session that
+// died are removed before a new session with this blocker starts.
+
+use super::DistractionBlocker;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    net::{IpAddr, ToSocketAddrs},
+};
+
+#[cfg(unix)]
+use std::process::{Command, Output};
+
+#[cfg(target_os = "macos")]
+use std::{io::Write, process::Stdio, sync::Mutex};
+
+/// `[firewall]` in the config file (and `--allow`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FirewallSettings {
+    /// Hosts, addresses and networks (CIDR) kept reachable
+    pub allow: Vec<String>,
+    /// Outgoing TCP ports kept open
+    pub ports: Vec<u16>,
+    /// Interfaces left alone, by name prefix (VPN tunnels)
+    pub interfaces: Vec<String>,
+}
+
+impl Default for FirewallSettings {
+    fn default() -> Self {
+        FirewallSettings {
+            allow: Vec::new(),
+            ports: vec![22],
+            interfaces: ["utun", "tun", "wg", "ppp", "ipsec"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+pub struct FirewallBlocker {
+    #[cfg_attr(windows, allow(dead_code))]
+    settings: FirewallSettings,
+    /// pf reference taken by engage, given back by release
+    #[cfg(target_os = "macos")]
+    token: Mutex<Option<String>>,
+}
+
+impl FirewallBlocker {
+    pub fn new(settings: &FirewallSettings) -> io::Result<Self> {
+        if cfg!(windows) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "--block firewall isn't supported on Windows",
+            ));
+        }
+        // A mistyped network fails now rather than when focus starts
+        for entry in &settings.allow {
+            network(entry)?;
+        }
+        Ok(FirewallBlocker {
+            settings: settings.clone(),
+            #[cfg(target_os = "macos")]
+            token: Mutex::new(None),
+        })
+    }
+}
+
+// The allowlist as addresses and networks, hosts resolved now
+#[cfg_attr(windows, allow(dead_code))]
+fn resolve(allow: &[String]) -> io::Result<Vec<String>> {
+    let mut addresses = Vec::new();
+    for entry in allow {
+        let found: Vec<String> = match network(entry)? {
+            Some(network) => vec![network],
+            None => match (entry.trim(), 0).to_socket_addrs() {
+                Ok(resolved) => resolved.map(|addr| addr.ip().to_string()).collect(),
+                Err(e) => {
+                    log::warn!("Can't resolve {} for the firewall allowlist: {}", entry, e);
+                    Vec::new()
+                }
+            },
+        };
+        for address in found {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+    }
+    Ok(addresses)
+}
+
+// An allowlist entry that is an address or network (CIDR), rebuilt from its
+// parts so nothing else reaches iptables or the pf anchor; None for a host
+// name. Anything else is an error.
+fn network(entry: &str) -> io::Result<Option<String>> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "'{}' in the firewall allowlist is not a host, address or network (e.g. 10.0.0.0/8)",
+                entry
+            ),
+        )
+    };
+    let entry = entry.trim();
+    let (host, prefix) = match entry.split_once('/') {
+        Some((host, prefix)) => (host, Some(prefix)),
+        None => (entry, None),
+    };
+    let Ok(ip) = host.parse::<IpAddr>() else {
+        // Only addresses have a prefix
+        return match prefix {
+            Some(_) => Err(invalid()),
+            None => Ok(None),
+        };
+    };
+    match prefix {
+        None => Ok(Some(ip.to_string())),
+        Some(prefix) => {
+            let bits = if ip.is_ipv4() { 32 } else { 128 };
+            match prefix.parse::<u8>() {
+                Ok(prefix) if prefix <= bits => Ok(Some(format!("{}/{}", ip, prefix))),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+// Whether an address or network from `resolve` is IPv6
+#[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+fn is_v6(address: &str) -> bool {
+    address.contains(':')
+}
+
+// Rules appended to the chain, as iptables arguments
+#[cfg_attr(not(all(unix, not(target_os = "macos"))), allow(dead_code))]
+fn chain_rules(settings: &FirewallSettings, addresses: &[&str]) -> Vec<Vec<String>> {
+    let rule = |args: &[&str]| -> Vec<String> {
+        args.iter()
+            .chain(&["-j", "ACCEPT"])
+            .map(|arg| arg.to_string())
+            .collect()
+    };
+    let mut rules = vec![
+        rule(&["-o", "lo"]),
+        rule(&["-m", "conntrack", "--ctstate", "ESTABLISHED,RELATED"]),
+    ];
+    for interface in &settings.interfaces {
+        rules.push(rule(&["-o", &format!("{}+", interface)]));
+    }
+    rules.push(rule(&["-p", "udp", "--dport", "53"]));
+    rules.push(rule(&["-p", "tcp", "--dport", "53"]));
+    for port in &settings.ports {
+        rules.push(rule(&["-p", "tcp", "--dport", &port.to_string()]));
+    }
+    for address in addresses {
+        rules.push(rule(&["-d", address]));
+    }
+    rules.push(vec!["-j".to_string(), "REJECT".to_string()]);
+    rules
+}
+
+// The pf anchor's rules; `interfaces` are the present ones matching the
+// settings
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn pf_rules(settings: &FirewallSettings, addresses: &[String], interfaces: &[String]) -> String {
+    let mut rules = String::from("pass out quick on lo0 all\n");
+    for interface in interfaces {
+        rules.push_str(&format!("pass out quick on {} all\n", interface));
+    }
+    rules.push_str("pass out quick proto { tcp udp } to any port 53\n");
+    if !settings.ports.is_empty() {
+        let ports: Vec<String> = settings.ports.iter().map(u16::to_string).collect();
+        rules.push_str(&format!(
+            "pass out quick proto tcp to any port {{ {} }}\n",
+            ports.join(" ")
+        ));
+    }
+    if !addresses.is_empty() {
+        rules.push_str(&format!(
+            "pass out quick to {{ {} }}\n",
+            addresses.join(" ")
+        ));
+    }
+    rules.push_str("block return out all\n");
+    rules
+}
+
+// Interfaces whose names start with one of `prefixes`
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn matching_interfaces(names: &str, prefixes: &[String]) -> Vec<String> {
+    names
+        .split_whitespace()
+        .filter(|name| {
+            prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+// Run a firewall command, telling a missing program and missing root rights
+// apart from other failures
+#[cfg(unix)]
+fn run(program: &str, args: &[&str]) -> io::Result<Output> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(e.kind(), format!("--block firewall needs `{}`", program))
+            }
+            _ => e,
+        })?;
+    check(program, output)
+}
+
+#[cfg(unix)]
+fn check(program: &str, output: Output) -> io::Result<Output> {
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let kind = if stderr.contains("Permission denied") || stderr.contains("must be root") {
+        io::ErrorKind::PermissionDenied
+    } else {
+        io::ErrorKind::Other
+    };
+    Err(io::Error::new(
+        kind,
+        format!("{} failed ({}): {}", program, output.status, stderr),
+    ))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod backend {
+    use super::*;
+
+    /// Chain the rules go in
+    const NAME: &str = "rust-pomo";
+
+    const PROGRAMS: [&str; 2] = ["iptables", "ip6tables"];
+
+    pub fn engage(settings: &FirewallSettings) -> io::Result<()> {
+        let addresses = resolve(&settings.allow)?;
+        for program in PROGRAMS {
+            if program == "ip6tables" && !installed(program) {
+                log::warn!("ip6tables isn't installed; IPv6 traffic isn't blocked");
+                continue;
+            }
+            let family: Vec<&str> = addresses
+                .iter()
+                .map(String::as_str)
+                .filter(|address| is_v6(address) == (program == "ip6tables"))
+                .collect();
+            // The chain may be there already (from a pause, or a crash)
+            if !has_chain(program) {
+                run(program, &["-w", "-N", NAME])?;
+            }
+            run(program, &["-w", "-F", NAME])?;
+            for rule in chain_rules(settings, &family) {
+                let mut args = vec!["-w", "-A", NAME];
+                args.extend(rule.iter().map(String::as_str));
+                run(program, &args)?;
+            }
+            if run(program, &["-w", "-C", "OUTPUT", "-j", NAME]).is_err() {
+                run(program, &["-w", "-I", "OUTPUT", "-j", NAME])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn release() -> io::Result<()> {
+        for program in PROGRAMS {
+            if !installed(program) || !has_chain(program) {
+                continue;
+            }
+            while run(program, &["-w", "-C", "OUTPUT", "-j", NAME]).is_ok() {
+                run(program, &["-w", "-D", "OUTPUT", "-j", NAME])?;
+            }
+            run(program, &["-w", "-F", NAME])?;
+            run(program, &["-w", "-X", NAME])?;
+        }
+        Ok(())
+    }
+
+    fn has_chain(program: &str) -> bool {
+        run(program, &["-w", "-n", "-L", NAME]).is_ok()
+    }
+
+    fn installed(program: &str) -> bool {
+        Command::new(program)
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod backend {
+    use super::*;
+
+    /// Anchors under com.apple/ are evaluated by the stock /etc/pf.conf
+    const ANCHOR: &str = "com.apple/rust-pomo";
+
+    // Load the rules and enable pf; returns the reference to give back
+    pub fn engage(settings: &FirewallSettings) -> io::Result<String> {
+        let addresses = resolve(&settings.allow)?;
+        let names = run("ifconfig", &["-l"])?;
+        let interfaces = matching_interfaces(
+            &String::from_utf8_lossy(&names.stdout),
+            &settings.interfaces,
+        );
+        let rules = pf_rules(settings, &addresses, &interfaces);
+
+        let mut pfctl = Command::new("pfctl")
+            .args(["-a", ANCHOR, "-f", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = pfctl.stdin.take() {
+            stdin.write_all(rules.as_bytes())?;
+        }
+        check("pfctl", pfctl.wait_with_output()?)?;
+
+        // "Token : 1234" goes to stderr
+        let enabled = run("pfctl", &["-E"])?;
+        let text = String::from_utf8_lossy(&enabled.stderr).into_owned()
+            + &String::from_utf8_lossy(&enabled.stdout);
+        text.lines()
+            .find_map(|line| line.trim().strip_prefix("Token : "))
+            .map(|token| token.trim().to_string())
+            .ok_or_else(|| io::Error::other("pfctl -E printed no token"))
+    }
+
+    pub fn release(token: Option<String>) -> io::Result<()> {
+        run("pfctl", &["-a", ANCHOR, "-F", "all"])?;
+        if let Some(token) = token {
+            run("pfctl", &["-X", &token])?;
+        }
+        Ok(())
+    }
+}
+
+impl DistractionBlocker for FirewallBlocker {
+    fn name(&self) -> &'static str {
+        "firewall"
+    }
+
+    // Listing the rules needs the same rights as changing them
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn check(&self) -> io::Result<()> {
+        run("iptables", &["-w", "-n", "-L", "OUTPUT"]).map(drop)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn engage(&self) -> io::Result<()> {
+        log::debug!("refusing outgoing traffic outside the allowlist");
+        backend::engage(&self.settings)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn release(&self) -> io::Result<()> {
+        backend::release()
+    }
+
+    #[cfg(target_os = "macos")]
+    fn check(&self) -> io::Result<()> {
+        run("pfctl", &["-s", "info"]).map(drop)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn engage(&self) -> io::Result<()> {
+        log::debug!("refusing outgoing traffic outside the allowlist");
+        let mut token = self.token.lock().unwrap();
+        // Still enabled from an earlier focus phase: just reload the rules
+        let new = backend::engage(&self.settings)?;
+        if let Some(old) = token.replace(new) {
+            run("pfctl", &["-X", &old])?;
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn release(&self) -> io::Result<()> {
+        backend::release(self.token.lock().unwrap().take())
+    }
+
+    #[cfg(windows)]
+    fn engage(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--block firewall isn't supported on Windows",
+        ))
+    }
+
+    #[cfg(windows)]
+    fn release(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Remove rules left behind by a session that didn't exit cleanly
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn remove_stale_rules() -> io::Result<()> {
+    backend::release()
+}
+
+#[cfg(target_os = "macos")]
+pub fn remove_stale_rules() -> io::Result<()> {
+    // The reference is lost with the process; pf stays enabled, with nothing
+    // in the anchor
+    backend::release(None)
+}
+
+#[cfg(windows)]
+pub fn remove_stale_rules() -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_networks_are_rebuilt_or_refused() {
+        assert_eq!(
+            network(" 10.0.0.0/8 ").unwrap().as_deref(),
+            Some("10.0.0.0/8")
+        );
+        assert_eq!(
+            network("2001:db8::/32").unwrap().as_deref(),
+            Some("2001:db8::/32")
+        );
+        assert_eq!(network("1.2.3.4").unwrap().as_deref(), Some("1.2.3.4"));
+        assert_eq!(network("vpn.example.com").unwrap(), None);
+        for bad in [
+            "10.0.0.0/8 }",
+            "1.2.3.4/abc\npass out all",
+            "10.0.0.0/33",
+            "2001:db8::/129",
+            "vpn.example.com/24",
+        ] {
+            assert!(network(bad).is_err(), "{:?}", bad);
+        }
+        assert!(resolve(&["10.0.0.0/8 }".to_string()]).is_err());
+    }
+
+    #[test]
+    fn allowlist_becomes_accept_rules_before_the_reject() {
+        let settings = FirewallSettings {
+            allow: vec!["10.0.0.0/8".to_string(), "2001:db8::1".to_string()],
+            ports: vec![22, 443],
+            interfaces: vec!["wg".to_string()],
+        };
+        let addresses = resolve(&settings.allow).unwrap();
+        assert_eq!(addresses, ["10.0.0.0/8", "2001:db8::1"]);
+        assert!(!is_v6(&addresses[0]) && is_v6(&addresses[1]));
+
+        let rules: Vec<String> = chain_rules(&settings, &["10.0.0.0/8"])
+            .iter()
+            .map(|rule| rule.join(" "))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                "-o lo -j ACCEPT",
+                "-m conntrack --ctstate ESTABLISHED,RELATED -j ACCEPT",
+                "-o wg+ -j ACCEPT",
+                "-p udp --dport 53 -j ACCEPT",
+                "-p tcp --dport 53 -j ACCEPT",
+                "-p tcp --dport 22 -j ACCEPT",
+                "-p tcp --dport 443 -j ACCEPT",
+                "-d 10.0.0.0/8 -j ACCEPT",
+                "-j REJECT",
+            ]
+        );
+
+        let interfaces = matching_interfaces("lo0 en0 utun0 utun3 wg0", &settings.interfaces);
+        assert_eq!(interfaces, ["wg0"]);
+        assert_eq!(
+            pf_rules(&settings, &addresses, &interfaces),
+            "pass out quick on lo0 all\n\
+             pass out quick on wg0 all\n\
+             pass out quick proto { tcp udp } to any port 53\n\
+             pass out quick proto tcp to any port { 22 443 }\n\
+             pass out quick to { 10.0.0.0/8 2001:db8::1 }\n\
+             block return out all\n"
+        );
+    }
+}
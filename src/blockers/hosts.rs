@@ -0,0 +1,175 @@
+// Hosts-file blocker: distracting sites resolve to 0.0.0.0 during focus.
+//
+// The entries are appended between marker lines so they can be found and
+// removed again without touching anything else in the file:
+//   # BEGIN rust-pomo
+//   0.0.0.0 twitter.com
+//   # END rust-pomo
+// Writing the hosts file needs root (e.g. run with sudo), or an administrator
+// prompt on Windows. POMO_HOSTS_FILE points the blocker at another file
+// instead (used by the tests).
+//
+// Crash safety: the file is always replaced atomically (write a temporary file
+// next to it, then rename), so it is never half-written. If the process dies
+// while sites are blocked, the marked section is still there; every new
+// session and `pomo resume` removes such leftovers before starting.
+
+use super::DistractionBlocker;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+#[cfg(not(windows))]
+const HOSTS_PATH: &str = "/etc/hosts";
+#[cfg(windows)]
+const HOSTS_PATH: &str = r"C:\Windows\System32\drivers\etc\hosts";
+const HOSTS_PATH_ENV: &str = "POMO_HOSTS_FILE";
+const BEGIN_MARKER: &str = "# BEGIN rust-pomo";
+const END_MARKER: &str = "# END rust-pomo";
+
+/// Sites blocked when no list is configured
+pub const DEFAULT_SITES: &[&str] = &[
+    "twitter.com",
+    "x.com",
+    "youtube.com",
+    "reddit.com",
+    "facebook.com",
+    "instagram.com",
+    "news.ycombinator.com",
+];
+
+pub struct HostsBlocker {
+    path: PathBuf,
+    sites: Vec<String>,
+}
+
+impl HostsBlocker {
+    /// Block the given sites, or the defaults if the list is empty
+    pub fn new(sites: &[String]) -> Self {
+        let sites = if sites.is_empty() {
+            DEFAULT_SITES.iter().map(|s| s.to_string()).collect()
+        } else {
+            sites.to_vec()
+        };
+        HostsBlocker {
+            path: hosts_path(),
+            sites,
+        }
+    }
+}
+
+impl DistractionBlocker for HostsBlocker {
+    fn name(&self) -> &'static str {
+        "hosts"
+    }
+
+    // Both the file and its directory must be writable (write_atomic). The
+    // probe has a name of its own, apart from the temporary file of another
+    // session engaging at the same time.
+    fn check(&self) -> io::Result<()> {
+        fs::OpenOptions::new().append(true).open(&self.path)?;
+        let tmp = self
+            .path
+            .with_extension(format!("rust-pomo-check.{}", std::process::id()));
+        fs::write(&tmp, "")?;
+        fs::remove_file(&tmp)
+    }
+
+    fn engage(&self) -> io::Result<()> {
+        log::debug!(
+            "blocking {} sites in {}",
+            self.sites.len(),
+            self.path.display()
+        );
+
+        let mut contents = strip_block(&fs::read_to_string(&self.path)?);
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(BEGIN_MARKER);
+        contents.push('\n');
+        for site in &self.sites {
+            contents.push_str(&format!("0.0.0.0 {}\n", site));
+            // Bare domains are usually also served from www.
+            if site.matches('.').count() == 1 {
+                contents.push_str(&format!("0.0.0.0 www.{}\n", site));
+            }
+        }
+        contents.push_str(END_MARKER);
+        contents.push('\n');
+        write_atomic(&self.path, &contents)?;
+        flush_dns_cache();
+        Ok(())
+    }
+
+    fn release(&self) -> io::Result<()> {
+        remove_entries(&self.path)
+    }
+}
+
+fn hosts_path() -> PathBuf {
+    std::env::var_os(HOSTS_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(HOSTS_PATH))
+}
+
+// Remove entries left behind by a session that didn't exit cleanly
+pub fn remove_stale_entries() -> io::Result<()> {
+    remove_entries(&hosts_path())
+}
+
+fn remove_entries(path: &Path) -> io::Result<()> {
+    let contents = fs::read_to_string(path)?;
+    let stripped = strip_block(&contents);
+    if stripped != contents {
+        log::debug!("unblocking sites in {}", path.display());
+        write_atomic(path, &stripped)?;
+        flush_dns_cache();
+    }
+    Ok(())
+}
+
+// Remove our marked section (if any), leaving every other line untouched
+fn strip_block(contents: &str) -> String {
+    let mut out = String::with_capacity(contents.len());
+    let mut inside = false;
+    for line in contents.split_inclusive('\n') {
+        match line.trim_end() {
+            BEGIN_MARKER => inside = true,
+            END_MARKER => inside = false,
+            _ if !inside => out.push_str(line),
+            _ => {}
+        }
+    }
+    out
+}
+
+// Replace the file in one step, keeping its permissions
+fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = path.with_extension("rust-pomo.tmp");
+    fs::write(&tmp, contents)?;
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(&tmp, meta.permissions())?;
+    }
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+// Make the change visible immediately (macOS and Windows cache lookups).
+// Best effort.
+fn flush_dns_cache() {
+    if cfg!(target_os = "macos") {
+        let _ = Command::new("dscacheutil").arg("-flushcache").status();
+        let _ = Command::new("killall")
+            .args(["-HUP", "mDNSResponder"])
+            .status();
+    } else if cfg!(windows) {
+        let _ = Command::new("ipconfig")
+            .arg("/flushdns")
+            .stdout(Stdio::null())
+            .status();
+    }
+}
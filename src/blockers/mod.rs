@@ -0,0 +1,423 @@
+// Distraction blockers: things that are switched on for focus and switched
+// back off for breaks, pauses and exit. Wi-Fi was the original (and is still
+// the default) blocker; others are selected with `--block wifi,bluetooth,hosts,dnd`.
+// `firewall` is the alternative to `wifi` that keeps a VPN and SSH up.
+// `awake` isn't a distraction as such, but has the same lifetime: it keeps
+// the system from sleeping during focus. Sessions running side by side share
+// the blockers (claims.rs).
+//
+// Before the first focus starts each blocker is checked (a program missing,
+// no root rights, no wireless interface, ...), and a blocker that fails to
+// switch later on is dealt with by its failure policy:
+//   abort … end the session (the default; `pomo resume` picks it up again)
+//   warn  … say so and carry on without it
+//   retry … try again a few times, then carry on without it
+// set with `--on-failure` for all blockers, or in the config file:
+//   [on_failure]
+//   default = "abort"
+//   wifi = "retry"
+//   dnd = "warn"
+// A failed check stops the session before it starts only for blockers that
+// abort; the others get a warning.
+
+mod apps;
+mod awake;
+mod bluetooth;
+pub mod claims;
+mod dnd;
+mod firewall;
+mod hosts;
+mod wifi;
+
+use crate::{
+    dry_run::{self, DryRunBlocker},
+    error::{Error, Result},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+pub use apps::{AppAction, AppBlocker, AppSettings};
+pub use awake::AwakeBlocker;
+pub use bluetooth::BluetoothBlocker;
+pub use dnd::DndBlocker;
+pub use firewall::{FirewallBlocker, FirewallSettings, remove_stale_rules};
+pub use hosts::{HostsBlocker, remove_stale_entries};
+pub use wifi::WifiBlocker;
+
+/// Times a blocker with the `retry` policy is tried again
+const RETRY_ATTEMPTS: u32 = 3;
+
+/// Wait between those tries
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Something that removes a source of distraction during focus
+pub trait DistractionBlocker: Send + Sync {
+    /// Short name used in messages
+    fn name(&self) -> &'static str;
+
+    /// Start blocking (focus begins or is unpaused)
+    fn engage(&self) -> io::Result<()>;
+
+    /// Stop blocking (break, pause or exit)
+    fn release(&self) -> io::Result<()>;
+
+    /// Whether it can work on this machine (the programs it needs, the rights
+    /// to use them), checked before the first focus starts
+    fn check(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Whether this is one switch for the whole machine, shared with other
+    /// sessions running at the same time (claims.rs), rather than something
+    /// each session does for itself
+    fn shared(&self) -> bool {
+        true
+    }
+}
+
+/// Blockers selectable with `--block`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockerKind {
+    /// Turn Wi-Fi off
+    Wifi,
+    /// Turn Bluetooth off
+    Bluetooth,
+    /// Point distracting websites at 0.0.0.0 in the hosts file
+    Hosts,
+    /// Quit or hide distracting apps
+    Apps,
+    /// Turn on macOS Do Not Disturb
+    Dnd,
+    /// Keep the system from sleeping
+    Awake,
+    /// Refuse outgoing traffic except VPN, SSH and allowed hosts
+    Firewall,
+}
+
+/// What to do when a blocker fails to switch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// End the session (it can be resumed)
+    #[default]
+    Abort,
+    /// Say so and carry on without the blocker
+    Warn,
+    /// Try again a few times, then carry on without it
+    Retry,
+}
+
+/// `[on_failure]` in the config file: the policy for every blocker, and for
+/// each one by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FailurePolicies {
+    pub default: FailurePolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wifi: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bluetooth: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hosts: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apps: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dnd: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub awake: Option<FailurePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firewall: Option<FailurePolicy>,
+}
+
+impl FailurePolicies {
+    /// The same policy for every blocker (`--on-failure`)
+    pub fn all(policy: FailurePolicy) -> Self {
+        FailurePolicies {
+            default: policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn of(&self, kind: BlockerKind) -> FailurePolicy {
+        let policy = match kind {
+            BlockerKind::Wifi => self.wifi,
+            BlockerKind::Bluetooth => self.bluetooth,
+            BlockerKind::Hosts => self.hosts,
+            BlockerKind::Apps => self.apps,
+            BlockerKind::Dnd => self.dnd,
+            BlockerKind::Awake => self.awake,
+            BlockerKind::Firewall => self.firewall,
+        };
+        policy.unwrap_or(self.default)
+    }
+}
+
+/// Per-blocker settings, saved with the session so resumed and background
+/// sessions block the same things
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockerOptions {
+    /// Sites for the hosts blocker (built-in list when empty)
+    #[serde(default)]
+    pub sites: Vec<String>,
+    /// Network interface for the Wi-Fi blocker (detected when not set)
+    #[serde(default)]
+    pub wifi_interface: Option<String>,
+    /// Apps for the apps blocker (built-in list when empty)
+    #[serde(default)]
+    pub apps: Vec<String>,
+    #[serde(default)]
+    pub app_action: AppAction,
+    /// Start quit apps again on release
+    #[serde(default)]
+    pub relaunch_apps: bool,
+    /// What the firewall blocker keeps reachable
+    #[serde(default)]
+    pub firewall: FirewallSettings,
+    /// Keep the system awake on battery too
+    #[serde(default)]
+    pub awake_on_battery: bool,
+    /// What to do when a blocker fails
+    #[serde(default)]
+    pub on_failure: FailurePolicies,
+}
+
+impl BlockerKind {
+    /// Name as given to `--block`
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockerKind::Wifi => "wifi",
+            BlockerKind::Bluetooth => "bluetooth",
+            BlockerKind::Hosts => "hosts",
+            BlockerKind::Apps => "apps",
+            BlockerKind::Dnd => "dnd",
+            BlockerKind::Awake => "awake",
+            BlockerKind::Firewall => "firewall",
+        }
+    }
+
+    fn build(self, options: &BlockerOptions) -> io::Result<Box<dyn DistractionBlocker>> {
+        if dry_run::is_enabled() {
+            return Ok(Box::new(DryRunBlocker::new(self.name())));
+        }
+        Ok(match self {
+            BlockerKind::Wifi => Box::new(WifiBlocker::new(options.wifi_interface.as_deref())?),
+            BlockerKind::Bluetooth => Box::new(BluetoothBlocker::default()),
+            BlockerKind::Hosts => Box::new(HostsBlocker::new(&options.sites)),
+            BlockerKind::Apps => Box::new(AppBlocker::new(
+                &options.apps,
+                options.app_action,
+                options.relaunch_apps,
+            )),
+            BlockerKind::Dnd => Box::new(DndBlocker),
+            BlockerKind::Awake => Box::new(AwakeBlocker::new(options.awake_on_battery)),
+            BlockerKind::Firewall => Box::new(FirewallBlocker::new(&options.firewall)?),
+        })
+    }
+}
+
+/// The set of blockers active for a session
+pub struct Blockers {
+    /// Each with its failure policy
+    blockers: Vec<(Box<dyn DistractionBlocker>, FailurePolicy)>,
+    /// Engaged and not yet (successfully) released
+    engaged: AtomicBool,
+}
+
+impl Blockers {
+    // Set up the selected blockers; fails if one that aborts on failure can't
+    // work on this machine (e.g. no wireless interface for `wifi`), the others
+    // are left out with a warning. In a dry run they only print what they
+    // would do.
+    pub fn new(kinds: &[BlockerKind], options: &BlockerOptions) -> Result<Self> {
+        let mut unique = Vec::new();
+        for kind in kinds {
+            if !unique.contains(kind) {
+                unique.push(*kind);
+            }
+        }
+        let mut blockers = Vec::new();
+        for kind in unique {
+            let policy = options.on_failure.of(kind);
+            match kind.build(options) {
+                Ok(blocker) => blockers.push((blocker, policy)),
+                Err(e) if policy == FailurePolicy::Abort => {
+                    return Err(Error::blocker(kind.name())(e));
+                }
+                Err(e) => {
+                    log::warn!("The {} blocker can't work here: {}", kind.name(), e);
+                    log::warn!("Carrying on without the {} blocker", kind.name());
+                }
+            }
+        }
+        Ok(Blockers {
+            blockers,
+            engaged: AtomicBool::new(false),
+        })
+    }
+
+    /// Whether anything may still be blocked (so cleanup is needed)
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.load(Ordering::SeqCst)
+    }
+
+    // Check every blocker before the first focus starts: a blocker that
+    // aborts fails the session now rather than halfway, the others warn
+    pub fn preflight(&self) -> Result<()> {
+        for (blocker, policy) in &self.blockers {
+            log::debug!("checking {}", blocker.name());
+            match blocker.check() {
+                Ok(()) => {}
+                Err(e) if *policy == FailurePolicy::Abort => {
+                    return Err(Error::blocker(blocker.name())(e));
+                }
+                Err(e) => log::warn!("The {} blocker may not work: {}", blocker.name(), e),
+            }
+        }
+        Ok(())
+    }
+
+    // Engage every blocker; one that fails and aborts stops the rest
+    pub fn engage(&self) -> Result<()> {
+        // Set first: a partly engaged set still needs releasing
+        self.engaged.store(true, Ordering::SeqCst);
+        for (blocker, policy) in &self.blockers {
+            if !self.is_ours(blocker.as_ref(), claims::claim)? {
+                log::debug!("{} is already engaged by another session", blocker.name());
+                continue;
+            }
+            log::debug!("engaging {}", blocker.name());
+            let engaged = attempt(blocker.name(), "engage", *policy, || blocker.engage())
+                .map_err(Error::blocker(blocker.name()))?;
+            if !engaged {
+                log::warn!("Carrying on without the {} blocker", blocker.name());
+            }
+        }
+        Ok(())
+    }
+
+    // Release every blocker. All are attempted even if one fails, so a broken
+    // backend never leaves the others stuck on; the first error of one that
+    // aborts is returned.
+    pub fn release(&self) -> Result<()> {
+        let mut result = Ok(());
+        let mut released = true;
+        for (blocker, policy) in self.blockers.iter().rev() {
+            match self.is_ours(blocker.as_ref(), claims::release) {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::debug!("{} stays engaged for another session", blocker.name());
+                    continue;
+                }
+                Err(e) => log::warn!("Could not check who else uses {}: {}", blocker.name(), e),
+            }
+            log::debug!("releasing {}", blocker.name());
+            match attempt(blocker.name(), "release", *policy, || blocker.release()) {
+                Ok(true) => {}
+                // Tried again on the way out
+                Ok(false) => released = false,
+                Err(e) => {
+                    log::error!("Failed to release {}: {}", blocker.name(), e);
+                    released = false;
+                    if result.is_ok() {
+                        result = Err(Error::blocker(blocker.name())(e));
+                    }
+                }
+            }
+        }
+        if released {
+            self.engaged.store(false, Ordering::SeqCst);
+        }
+        result
+    }
+
+    // Take or give up this session's claim on a shared blocker (claims.rs);
+    // whether this session is the one to engage or release it. Dry runs don't
+    // claim anything.
+    fn is_ours(
+        &self,
+        blocker: &dyn DistractionBlocker,
+        claim: fn(&str) -> io::Result<bool>,
+    ) -> Result<bool> {
+        if !blocker.shared() || dry_run::is_enabled() {
+            return Ok(true);
+        }
+        claim(blocker.name()).map_err(Error::blocker(blocker.name()))
+    }
+}
+
+// Engage or release a blocker as its failure policy says: whether it worked,
+// or the error to end the session with (abort)
+fn attempt(
+    name: &str,
+    action: &str,
+    policy: FailurePolicy,
+    switch: impl Fn() -> io::Result<()>,
+) -> io::Result<bool> {
+    let mut result = switch();
+    if policy == FailurePolicy::Retry {
+        for attempt in 1..=RETRY_ATTEMPTS {
+            let Err(e) = &result else { break };
+            log::warn!(
+                "Failed to {} {}: {}; trying again ({}/{})",
+                action,
+                name,
+                e,
+                attempt,
+                RETRY_ATTEMPTS
+            );
+            thread::sleep(RETRY_DELAY);
+            result = switch();
+        }
+    }
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if policy == FailurePolicy::Abort => Err(e),
+        Err(e) => {
+            log::warn!("Failed to {} {}: {}", action, name, e);
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_policies_are_per_blocker_with_a_default() {
+        let policies: FailurePolicies =
+            toml::from_str("default = \"warn\"\nwifi = \"retry\"").unwrap();
+        assert_eq!(policies.of(BlockerKind::Wifi), FailurePolicy::Retry);
+        assert_eq!(policies.of(BlockerKind::Hosts), FailurePolicy::Warn);
+        assert_eq!(
+            FailurePolicies::default().of(BlockerKind::Dnd),
+            FailurePolicy::Abort
+        );
+        assert!(toml::from_str::<FailurePolicies>("wlan = \"warn\"").is_err());
+
+        let failing = || Err(io::Error::other("nmcli failed"));
+        assert!(attempt("wifi", "engage", FailurePolicy::Abort, failing).is_err());
+        assert!(!attempt("wifi", "engage", FailurePolicy::Warn, failing).unwrap());
+        assert!(attempt("wifi", "engage", FailurePolicy::Abort, || Ok(())).unwrap());
+    }
+
+    #[test]
+    fn blockers_that_cant_be_set_up_are_left_out_unless_they_abort() {
+        let mut options = BlockerOptions::default();
+        options.firewall.allow = vec!["10.0.0.0/33".to_string()];
+        let kinds = [BlockerKind::Firewall, BlockerKind::Dnd];
+        assert!(Blockers::new(&kinds, &options).is_err());
+
+        options.on_failure = toml::from_str("default = \"warn\"").unwrap();
+        let blockers = Blockers::new(&kinds, &options).unwrap();
+        assert_eq!(blockers.blockers.len(), 1);
+        assert_eq!(blockers.blockers[0].0.name(), "dnd");
+    }
+}
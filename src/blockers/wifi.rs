@@ -0,0 +1,536 @@
+// Wi-Fi blocker: no Wi-Fi during focus (macOS `networksetup` turns the radio
+// off).
+//
+// The wireless interface is `--wifi-interface` if given, otherwise detected
+// when the session starts:
+//   macOS   … `networksetup -listallhardwareports` (the "Wi-Fi" port)
+//   Linux   … `nmcli device`, or any interface with /sys/class/net/<name>/wireless
+//   Windows … `netsh wlan show interfaces` (usually "Wi-Fi")
+//
+// On Linux only the Wi-Fi connection goes down, through NetworkManager:
+// `nmcli device disconnect` takes the interface off its connection (and keeps
+// NetworkManager from picking another one), while wired connections and VPNs
+// elsewhere stay up. The end of focus brings the exact same connection back
+// up by its UUID. The UUID is kept in the state directory (wifi-connection),
+// so a session that died mid-focus still gets it back from `pomo resume`. If
+// Wi-Fi wasn't connected when focus started it is left disconnected
+// afterwards. Without NetworkManager the radio is switched off with `rfkill`.
+//
+// Windows can't switch the radio off without administrator rights, so there
+// focus disconnects (`netsh wlan disconnect`) and the end of focus connects
+// back to the network it left, or to the most preferred profile if that isn't
+// known (e.g. when cleaning up after a crash).
+
+use super::DistractionBlocker;
+use std::{io, process::Command};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use {crate::state, std::fs, std::path::PathBuf};
+
+#[cfg(windows)]
+use std::sync::Mutex;
+
+pub struct WifiBlocker {
+    interface: String,
+    /// The network profile focus disconnected from
+    #[cfg(windows)]
+    network: Mutex<Option<String>>,
+}
+
+impl WifiBlocker {
+    /// Control the given interface, or the detected wireless one
+    pub fn new(interface: Option<&str>) -> io::Result<Self> {
+        let interface = match interface {
+            Some(interface) => interface.to_string(),
+            None => detect_interface()?,
+        };
+        Ok(WifiBlocker {
+            interface,
+            #[cfg(windows)]
+            network: Mutex::new(None),
+        })
+    }
+}
+
+impl DistractionBlocker for WifiBlocker {
+    fn name(&self) -> &'static str {
+        "wifi"
+    }
+
+    // `networksetup -getairportpower en0` answers "Wi-Fi Power (en0): On",
+    // or e.g. "en5 is not a Wi-Fi interface."
+    #[cfg(target_os = "macos")]
+    fn check(&self) -> io::Result<()> {
+        let output = Command::new("networksetup")
+            .args(["-getairportpower", &self.interface])
+            .output()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() || !text.contains("Power") {
+            return Err(io::Error::other(format!("networksetup: {}", text.trim())));
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn engage(&self) -> io::Result<()> {
+        set_wifi_power(&self.interface, false)
+    }
+
+    #[cfg(target_os = "macos")]
+    fn release(&self) -> io::Result<()> {
+        set_wifi_power(&self.interface, true)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn check(&self) -> io::Result<()> {
+        if network_manager_running() {
+            nmcli(&[
+                "-t",
+                "-f",
+                "GENERAL.TYPE",
+                "device",
+                "show",
+                &self.interface,
+            ])?;
+            return Ok(());
+        }
+        let status = rfkill(&["list", "wlan"])?.status;
+        if !status.success() {
+            return Err(io::Error::other(format!("rfkill failed ({})", status)));
+        }
+        Ok(())
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn engage(&self) -> io::Result<()> {
+        if !network_manager_running() {
+            return rfkill_wifi(false);
+        }
+        let device = nmcli(&[
+            "-t",
+            "-f",
+            "GENERAL.CON-UUID",
+            "device",
+            "show",
+            &self.interface,
+        ])?;
+        // Not connected: nothing to take down. A connection saved by a session
+        // that died is left in place, so it still comes back at the end.
+        let Some(uuid) = parse_nmcli_field(&device, "GENERAL.CON-UUID") else {
+            log::debug!("{} is not connected", self.interface);
+            return Ok(());
+        };
+        fs::create_dir_all(state::state_dir())?;
+        fs::write(saved_connection_path(), &uuid)?;
+        log::debug!("nmcli device disconnect {}", self.interface);
+        nmcli(&["device", "disconnect", &self.interface])?;
+        Ok(())
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn release(&self) -> io::Result<()> {
+        if !network_manager_running() {
+            return rfkill_wifi(true);
+        }
+        let path = saved_connection_path();
+        let uuid = match fs::read_to_string(&path) {
+            Ok(uuid) => uuid.trim().to_string(),
+            // Wasn't connected when focus started
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        // Tried once: a network that has gone out of range isn't retried later
+        fs::remove_file(&path)?;
+        log::debug!(
+            "nmcli connection up uuid {} ifname {}",
+            uuid,
+            self.interface
+        );
+        nmcli(&[
+            "--wait",
+            "10",
+            "connection",
+            "up",
+            "uuid",
+            &uuid,
+            "ifname",
+            &self.interface,
+        ])?;
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn check(&self) -> io::Result<()> {
+        let interfaces = netsh(&["wlan", "show", "interfaces"])?;
+        if !parse_wlan_interfaces(&interfaces)
+            .iter()
+            .any(|(name, _)| *name == self.interface)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no wireless interface named \"{}\"", self.interface),
+            ));
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn engage(&self) -> io::Result<()> {
+        let interfaces = netsh(&["wlan", "show", "interfaces"])?;
+        let current = parse_wlan_interfaces(&interfaces)
+            .into_iter()
+            .find(|(name, _)| *name == self.interface)
+            .and_then(|(_, profile)| profile);
+        // Not connected: nothing to disconnect from, nothing to go back to
+        let Some(profile) = current else {
+            return Ok(());
+        };
+        log::debug!("netsh wlan disconnect interface=\"{}\"", self.interface);
+        netsh_run(&["wlan", "disconnect"], &[("interface", &self.interface)])?;
+        *self.network.lock().unwrap() = Some(profile);
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn release(&self) -> io::Result<()> {
+        let left = self.network.lock().unwrap().take();
+        let profile = match left {
+            Some(profile) => profile,
+            None => {
+                let profiles = netsh(&[
+                    "wlan",
+                    "show",
+                    "profiles",
+                    &format!("interface={}", self.interface),
+                ])?;
+                match parse_wlan_profiles(&profiles).into_iter().next() {
+                    Some(profile) => profile,
+                    None => return Ok(()),
+                }
+            }
+        };
+        log::debug!(
+            "netsh wlan connect name=\"{}\" interface=\"{}\"",
+            profile,
+            self.interface
+        );
+        netsh_run(
+            &["wlan", "connect"],
+            &[("name", &profile), ("interface", &self.interface)],
+        )
+    }
+}
+
+// Turn WiFi on/off on macOS. networksetup prints nothing when it works, and
+// may say what went wrong with a successful exit status.
+#[cfg(target_os = "macos")]
+fn set_wifi_power(interface: &str, on: bool) -> io::Result<()> {
+    let status = if on { "on" } else { "off" };
+    log::debug!("networksetup -setairportpower {} {}", interface, status);
+
+    let output = Command::new("networksetup")
+        .args(["-setairportpower", interface, status])
+        .output()?;
+    let message = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() || !message.trim().is_empty() {
+        return Err(io::Error::other(format!(
+            "networksetup -setairportpower {} {} failed: {}",
+            interface,
+            status,
+            message.trim()
+        )));
+    }
+    Ok(())
+}
+
+// Where the connection taken down for focus is kept
+#[cfg(all(unix, not(target_os = "macos")))]
+fn saved_connection_path() -> PathBuf {
+    state::state_dir().join("wifi-connection")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn network_manager_running() -> bool {
+    nmcli(&["-t", "-f", "RUNNING", "general"]).is_ok_and(|output| output.trim() == "running")
+}
+
+// Output of an nmcli command
+#[cfg(all(unix, not(target_os = "macos")))]
+fn nmcli(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("nmcli").args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "nmcli {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Without NetworkManager: the radio itself
+#[cfg(all(unix, not(target_os = "macos")))]
+fn rfkill_wifi(on: bool) -> io::Result<()> {
+    let action = if on { "unblock" } else { "block" };
+    log::debug!("rfkill {} wlan", action);
+    let status = rfkill(&[action, "wlan"])?.status;
+    if !status.success() {
+        return Err(io::Error::other(format!("rfkill failed ({})", status)));
+    }
+    Ok(())
+}
+
+// Run rfkill, saying what is missing if it isn't installed
+#[cfg(all(unix, not(target_os = "macos")))]
+fn rfkill(args: &[&str]) -> io::Result<std::process::Output> {
+    Command::new("rfkill")
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                "--block wifi needs NetworkManager (nmcli) or rfkill",
+            ),
+            _ => e,
+        })
+}
+
+// Find the wireless interface, or explain that there is none
+fn detect_interface() -> io::Result<String> {
+    find_interface().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "no wireless network interface found; pick one with --wifi-interface \
+             or leave wifi out of --block",
+        )
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn find_interface() -> Option<String> {
+    let output = Command::new("networksetup")
+        .arg("-listallhardwareports")
+        .output()
+        .ok()?;
+    parse_hardware_ports(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn find_interface() -> Option<String> {
+    let from_nmcli = Command::new("nmcli")
+        .args(["-t", "-f", "DEVICE,TYPE", "device"])
+        .output()
+        .ok()
+        .and_then(|output| parse_nmcli_devices(&String::from_utf8_lossy(&output.stdout)));
+    from_nmcli.or_else(|| {
+        let mut names: Vec<String> = std::fs::read_dir("/sys/class/net")
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.path().join("wireless").is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names.into_iter().next()
+    })
+}
+
+#[cfg(windows)]
+fn find_interface() -> Option<String> {
+    let interfaces = netsh(&["wlan", "show", "interfaces"]).ok()?;
+    parse_wlan_interfaces(&interfaces)
+        .into_iter()
+        .next()
+        .map(|(name, _)| name)
+}
+
+// Output of a `netsh` query
+#[cfg(windows)]
+fn netsh(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("netsh").args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "netsh {} failed ({})",
+            args.join(" "),
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Run a `netsh` command with `key="value"` arguments, quoted the way netsh
+// wants them (names such as "Wi-Fi 2" have spaces)
+#[cfg(windows)]
+fn netsh_run(args: &[&str], values: &[(&str, &str)]) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    let mut command = Command::new("netsh");
+    command.args(args);
+    for (key, value) in values {
+        command.raw_arg(format!("{}=\"{}\"", key, value));
+    }
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "netsh {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout).trim()
+        )))
+    }
+}
+
+// The device of the Wi-Fi port in `networksetup -listallhardwareports`, e.g.
+//   Hardware Port: Wi-Fi
+//   Device: en0
+// ("AirPort" on older macOS)
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_hardware_ports(text: &str) -> Option<String> {
+    let mut wifi_port = false;
+    for line in text.lines() {
+        if let Some(port) = line.strip_prefix("Hardware Port: ") {
+            wifi_port = matches!(port.trim(), "Wi-Fi" | "AirPort");
+        } else if let Some(device) = line.strip_prefix("Device: ")
+            && wifi_port
+        {
+            return Some(device.trim().to_string());
+        }
+    }
+    None
+}
+
+// The first wifi device in `nmcli -t -f DEVICE,TYPE device`, e.g. "wlp2s0:wifi"
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn parse_nmcli_devices(text: &str) -> Option<String> {
+    text.lines()
+        .filter_map(|line| line.rsplit_once(':'))
+        .find(|(_, kind)| *kind == "wifi")
+        .map(|(device, _)| device.to_string())
+}
+
+// A field of `nmcli -t -f <field> device show`, e.g.
+//   GENERAL.CON-UUID:6c1a2f0e-...
+// `None` if it is empty (no connection)
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn parse_nmcli_field(text: &str, field: &str) -> Option<String> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && *value != "--")
+        .map(str::to_string)
+}
+
+// The wireless interfaces in `netsh wlan show interfaces` with the profile
+// each is connected with, e.g.
+//   Name                   : Wi-Fi
+//   State                  : connected
+//   Profile                : HomeNet
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_wlan_interfaces(text: &str) -> Vec<(String, Option<String>)> {
+    let mut interfaces: Vec<(String, Option<String>)> = Vec::new();
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match (key.trim(), interfaces.last_mut()) {
+            ("Name", _) => interfaces.push((value.trim().to_string(), None)),
+            ("Profile", Some((_, profile))) => *profile = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    interfaces
+}
+
+// The profiles in `netsh wlan show profiles`, most preferred first, e.g.
+//   All User Profile     : HomeNet
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_wlan_profiles(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim().ends_with("User Profile"))
+        .map(|(_, name)| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_wifi_port_among_hardware_ports() {
+        let text = "\
+Hardware Port: Ethernet
+Device: en0
+Ethernet Address: aa:bb:cc:dd:ee:ff
+
+Hardware Port: Wi-Fi
+Device: en1
+Ethernet Address: aa:bb:cc:dd:ee:00
+";
+        assert_eq!(parse_hardware_ports(text).as_deref(), Some("en1"));
+        assert_eq!(
+            parse_hardware_ports("Hardware Port: Ethernet\nDevice: en0\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_wifi_device_in_nmcli_output() {
+        let text = "enp3s0:ethernet\np2p-dev-wlp2s0:wifi-p2p\nwlp2s0:wifi\nlo:loopback\n";
+        assert_eq!(parse_nmcli_devices(text).as_deref(), Some("wlp2s0"));
+        assert_eq!(parse_nmcli_devices("enp3s0:ethernet\n"), None);
+    }
+
+    #[test]
+    fn reads_the_connection_of_an_nmcli_device() {
+        let text = "GENERAL.CON-UUID:6c1a2f0e-3d4b-4f7a-9c1e-2b8d5e6f7a80\n";
+        assert_eq!(
+            parse_nmcli_field(text, "GENERAL.CON-UUID").as_deref(),
+            Some("6c1a2f0e-3d4b-4f7a-9c1e-2b8d5e6f7a80")
+        );
+        assert_eq!(
+            parse_nmcli_field("GENERAL.CON-UUID:\n", "GENERAL.CON-UUID"),
+            None
+        );
+        assert_eq!(parse_nmcli_field("", "GENERAL.CON-UUID"), None);
+    }
+
+    #[test]
+    fn reads_netsh_interfaces_and_profiles() {
+        let text = "\
+There is 2 interfaces on the system:
+
+    Name                   : Wi-Fi
+    Description            : Intel(R) Wi-Fi 6 AX201 160MHz
+    Physical address       : aa:bb:cc:dd:ee:ff
+    State                  : connected
+    SSID                   : HomeNet
+    Profile                : HomeNet
+
+    Name                   : Wi-Fi 2
+    State                  : disconnected
+";
+        assert_eq!(
+            parse_wlan_interfaces(text),
+            [
+                ("Wi-Fi".to_string(), Some("HomeNet".to_string())),
+                ("Wi-Fi 2".to_string(), None)
+            ]
+        );
+
+        let text = "\
+Profiles on interface Wi-Fi:
+
+Group policy profiles (read only)
+---------------------------------
+    <None>
+
+User profiles
+-------------
+    All User Profile     : HomeNet
+    All User Profile     : Cafe Guest
+";
+        assert_eq!(parse_wlan_profiles(text), ["HomeNet", "Cafe Guest"]);
+    }
+}
@@ -0,0 +1,126 @@
+// Putting things back however the process ends. Blockers must never be left
+// engaged (Wi-Fi off, sites blocked) because of a panic, an early `?` return
+// or a signal.
+//
+//   - `CleanupGuard` releases everything when dropped, which covers normal
+//     returns, early returns and panics in the main thread.
+//   - SIGINT, SIGTERM and SIGHUP interrupt the session: the runner stops,
+//     saves its position for `pomo resume` and returns, so the guard runs too.
+//     A second signal (e.g. if the runner is stuck in a slow command) releases
+//     everything right away and exits.
+//   - On the --tui dashboard the terminal is in raw mode, so Ctrl+C is read as
+//     a key; it is handled the same way.
+//   - On Windows, Ctrl+C and Ctrl+Break in the console work the same way.
+//     Closing the console window, logging off or shutting down ends the
+//     process as soon as the handler returns, too soon for the runner to
+//     stop by itself, so those release everything before returning.
+
+use crate::{daemon, runner::AppState, say};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Set by the first signal, so the second one exits
+static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+/// Releases blockers and integrations and removes the control socket when dropped
+pub struct CleanupGuard {
+    app_state: Arc<AppState>,
+}
+
+impl CleanupGuard {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        CleanupGuard { app_state }
+    }
+}
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        release_all(&self.app_state);
+    }
+}
+
+// Undo everything the session may have set up. Releasing blockers is skipped
+// if they already were, so a clean exit doesn't toggle Wi-Fi twice.
+pub fn release_all(app_state: &AppState) {
+    if app_state.blockers.is_engaged() {
+        let _ = app_state.blockers.release();
+    }
+    app_state.integrations.session_end();
+    daemon::cleanup();
+}
+
+// Stop the session as interrupted; the second time, release the blockers and
+// exit at once. Also Ctrl+C on the --tui dashboard, where it is read as a key.
+pub fn interrupt(app_state: &AppState) {
+    if SIGNALLED.swap(true, Ordering::SeqCst) {
+        crate::tui::restore();
+        log::warn!("Signal received again. Releasing blockers and exiting.");
+        release_all(app_state);
+        std::process::exit(1);
+    }
+    say!("Signal received. Releasing blockers and exiting.");
+    app_state.interrupted.store(true, Ordering::SeqCst);
+    app_state.stop.store(true, Ordering::SeqCst);
+}
+
+// Handle SIGINT, SIGTERM and SIGHUP (or the console events) as described above
+pub fn handle_signals(app_state: Arc<AppState>) -> Result<(), ctrlc::Error> {
+    #[cfg(windows)]
+    let closing = Arc::clone(&app_state);
+    ctrlc::set_handler(move || interrupt(&app_state))?;
+    // Registered last, so it is asked first
+    #[cfg(windows)]
+    console::handle_close(closing).map_err(ctrlc::Error::System)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+mod console {
+    use super::release_all;
+    use crate::runner::AppState;
+    use std::{
+        io,
+        sync::{Arc, OnceLock, atomic::Ordering},
+    };
+
+    const CTRL_CLOSE_EVENT: u32 = 2;
+    const CTRL_LOGOFF_EVENT: u32 = 5;
+    const CTRL_SHUTDOWN_EVENT: u32 = 6;
+
+    static APP_STATE: OnceLock<Arc<AppState>> = OnceLock::new();
+
+    type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+
+    // Runs on a thread of its own; returning 0 passes the event on (Ctrl+C
+    // and Ctrl+Break go to the ctrlc handler)
+    unsafe extern "system" fn on_event(event: u32) -> i32 {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => {
+                if let Some(app_state) = APP_STATE.get() {
+                    app_state.interrupted.store(true, Ordering::SeqCst);
+                    app_state.stop.store(true, Ordering::SeqCst);
+                    release_all(app_state);
+                }
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn handle_close(app_state: Arc<AppState>) -> io::Result<()> {
+        let _ = APP_STATE.set(app_state);
+        // Safety: the handler is a plain function that lives as long as the
+        // process
+        if unsafe { SetConsoleCtrlHandler(Some(on_event), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+// Where the runner gets the time from, and how it waits.
+//
+// The runner never calls `Instant::now()` or `thread::sleep` itself but asks
+// the session's Clock, so the same code runs on:
+//
+//   SystemClock   real time
+//   ScaledClock   real time sped up (`--dry-run --time-scale 60`)
+//   ManualClock   time that only moves when waited on, so tests can run a
+//                 whole phase instantly and get the same result every time
+//
+// Wall-clock readings (log timestamps) still come from the system; only the
+// monotonic time that phases are measured with is swapped. System sleep and
+// clock changes are only watched for on real time: the other clocks don't
+// move with the system's.
+//
+// `Readings` are the system's clocks read together: the monotonic clock, the
+// wall clock and two clocks since boot, one counting time suspended and one
+// not. Comparing them tells system sleep from the wall clock being set (NTP,
+// a change of time zone, by hand), without trusting the wall clock for either:
+//   since boot, with suspend  … Linux CLOCK_BOOTTIME, macOS CLOCK_MONOTONIC,
+//                               Windows GetTickCount64
+//   since boot, awake only    … Linux CLOCK_MONOTONIC, macOS CLOCK_UPTIME_RAW,
+//                               Windows QueryUnbiasedInterruptTime
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// A source of monotonic time for the runner
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wait for `duration` of this clock's time
+    fn sleep(&self, duration: Duration);
+
+    /// The system's clocks, to watch for sleep and the wall clock being set;
+    /// only real time has them
+    fn readings(&self) -> Option<Readings> {
+        None
+    }
+}
+
+/// Real time
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn readings(&self) -> Option<Readings> {
+        Some(Readings::now())
+    }
+}
+
+/// Real time running `scale` times faster
+#[derive(Debug)]
+pub struct ScaledClock {
+    start: Instant,
+    scale: u32,
+}
+
+impl ScaledClock {
+    pub fn new(scale: u32) -> Self {
+        ScaledClock {
+            start: Instant::now(),
+            scale: scale.max(1),
+        }
+    }
+}
+
+impl Clock for ScaledClock {
+    fn now(&self) -> Instant {
+        self.start + self.start.elapsed() * self.scale
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration / self.scale);
+    }
+}
+
+/// Time that stands still until someone sleeps on it; sleeping moves it on
+/// at once
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    offset: Mutex<Duration>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock {
+            start: Instant::now(),
+            offset: Mutex::new(Duration::ZERO),
+        }
+    }
+}
+
+impl ManualClock {
+    /// Move the clock on without sleeping
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock().unwrap() += duration;
+    }
+
+    /// Time passed on this clock since it was made
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+        // Let other threads (stdin, control socket) get a look in
+        thread::yield_now();
+    }
+}
+
+// A clock shared with whoever drives it (a test holding a ManualClock)
+impl<C: Clock> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        self.as_ref().now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.as_ref().sleep(duration)
+    }
+}
+
+// The clock for a session run at `scale` times real time
+pub fn with_scale(scale: u32) -> Box<dyn Clock> {
+    if scale > 1 {
+        Box::new(ScaledClock::new(scale))
+    } else {
+        Box::new(SystemClock)
+    }
+}
+
+/// The system's clocks, read at one moment
+#[derive(Debug, Clone, Copy)]
+pub struct Readings {
+    /// The monotonic clock phases are measured with
+    pub instant: Instant,
+    pub wall: SystemTime,
+    /// Time since boot, counting time suspended (where it can be told)
+    pub boot: Option<Duration>,
+    /// Time since boot, not counting time suspended
+    pub awake: Option<Duration>,
+}
+
+impl Readings {
+    pub fn now() -> Self {
+        Readings {
+            instant: Instant::now(),
+            wall: SystemTime::now(),
+            boot: since_boot(),
+            awake: awake_since_boot(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn since_boot() -> Option<Duration> {
+    read_clock(nix::time::ClockId::CLOCK_BOOTTIME)
+}
+
+#[cfg(target_os = "linux")]
+fn awake_since_boot() -> Option<Duration> {
+    read_clock(nix::time::ClockId::CLOCK_MONOTONIC)
+}
+
+// macOS's CLOCK_MONOTONIC keeps counting in sleep; CLOCK_UPTIME_RAW doesn't
+#[cfg(target_os = "macos")]
+pub fn since_boot() -> Option<Duration> {
+    read_clock(nix::time::ClockId::CLOCK_MONOTONIC)
+}
+
+#[cfg(target_os = "macos")]
+fn awake_since_boot() -> Option<Duration> {
+    read_clock(nix::time::ClockId::from_raw(nix::libc::CLOCK_UPTIME_RAW))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_clock(clock: nix::time::ClockId) -> Option<Duration> {
+    clock.now().ok().map(Duration::from)
+}
+
+// Other Unix systems: not known
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+pub fn since_boot() -> Option<Duration> {
+    None
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+fn awake_since_boot() -> Option<Duration> {
+    None
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn GetTickCount64() -> u64;
+    fn QueryUnbiasedInterruptTime(time: *mut u64) -> i32;
+}
+
+#[cfg(windows)]
+pub fn since_boot() -> Option<Duration> {
+    // Safety: takes nothing and can't fail
+    Some(Duration::from_millis(unsafe { GetTickCount64() }))
+}
+
+#[cfg(windows)]
+fn awake_since_boot() -> Option<Duration> {
+    let mut time = 0u64;
+    // Safety: writes the time in 100 ns units to `time`; returns 0 on failure
+    if unsafe { QueryUnbiasedInterruptTime(&mut time) } == 0 {
+        return None;
+    }
+    Some(Duration::from_nanos(time * 100))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_moves_only_when_slept_on() {
+        let clock = ManualClock::default();
+        let t0 = clock.now();
+        assert_eq!(clock.now(), t0);
+        clock.sleep(Duration::from_secs(90));
+        assert_eq!(clock.now() - t0, Duration::from_secs(90));
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(clock.elapsed(), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn scaled_clock_runs_faster() {
+        let clock = ScaledClock::new(1000);
+        let t0 = clock.now();
+        let real = Instant::now();
+        clock.sleep(Duration::from_secs(2));
+        assert!(real.elapsed() < Duration::from_secs(1));
+        assert!(clock.now() - t0 >= Duration::from_secs(2));
+    }
+
+    #[test]
+    fn only_real_time_reads_the_system_clocks() {
+        assert!(SystemClock.readings().is_some());
+        assert!(ScaledClock::new(60).readings().is_none());
+        assert!(ManualClock::default().readings().is_none());
+    }
+}
@@ -0,0 +1,117 @@
+// Shell completion (clap_complete), including values only known at run time:
+//
+//   pomo completions bash > ~/.local/share/bash-completion/completions/pomo
+//   pomo completions zsh > "${fpath[1]}/_pomo"
+//   pomo completions fish > ~/.config/fish/completions/pomo.fish
+//
+// The script calls back into `pomo` (with COMPLETE=<shell> set) on each TAB,
+// so besides subcommands and flags it completes:
+//   --profile   the profiles in the config file, with their description
+//   --task      tasks from the session log, most recent first
+//   --tag       tags from the session log, most used first
+//
+// pomo has to be on the PATH for the script to find it.
+
+use crate::{config, history};
+use clap_complete::{Shell, engine::CompletionCandidate, env::Shells};
+use std::{collections::HashMap, ffi::OsStr, io};
+
+/// Variable the completion script sets when it calls back into `pomo`
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Write the completion script for `shell`
+pub fn write_script(shell: Shell, out: &mut dyn io::Write) -> io::Result<()> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("no completions for {}", name),
+        )
+    })?;
+    completer.write_registration(COMPLETE_VAR, "pomo", "pomo", "pomo", out)
+}
+
+// Profiles in the config file
+pub fn profiles(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(config) = config::load() else {
+        return Vec::new();
+    };
+    config
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            CompletionCandidate::new(name).help(profile.description.clone().map(Into::into))
+        })
+        .filter(|candidate| starts_with(candidate, current))
+        .collect()
+}
+
+// Tasks in the session log, most recent first
+pub fn tasks(current: &OsStr) -> Vec<CompletionCandidate> {
+    let mut tasks: Vec<String> = Vec::new();
+    for record in history::load().unwrap_or_default().into_iter().rev() {
+        if let Some(task) = record.task
+            && !tasks.contains(&task)
+        {
+            tasks.push(task);
+        }
+    }
+    candidates(tasks, current)
+}
+
+// Tags in the session log, most used first
+pub fn tags(current: &OsStr) -> Vec<CompletionCandidate> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for record in history::load().unwrap_or_default() {
+        for tag in record.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    candidates(by_use(counts), current)
+}
+
+// Names ordered by how often they were used, then by name
+fn by_use(counts: HashMap<String, usize>) -> Vec<String> {
+    let mut names: Vec<(String, usize)> = counts.into_iter().collect();
+    names.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    names.into_iter().map(|(name, _)| name).collect()
+}
+
+fn candidates(values: Vec<String>, current: &OsStr) -> Vec<CompletionCandidate> {
+    values
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .filter(|candidate| starts_with(candidate, current))
+        .collect()
+}
+
+fn starts_with(candidate: &CompletionCandidate, current: &OsStr) -> bool {
+    let current = current.to_string_lossy();
+    candidate
+        .get_value()
+        .to_string_lossy()
+        .starts_with(current.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_ordered_by_use() {
+        let counts = HashMap::from([
+            ("work".to_string(), 3),
+            ("home".to_string(), 1),
+            ("code".to_string(), 3),
+        ]);
+        assert_eq!(by_use(counts), ["code", "work", "home"]);
+
+        let values = vec!["write report".to_string(), "review".to_string()];
+        let found: Vec<String> = candidates(values, OsStr::new("wr"))
+            .iter()
+            .map(|c| c.get_value().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(found, ["write report"]);
+    }
+}
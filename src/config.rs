@@ -0,0 +1,206 @@
+// Optional config file with defaults for settings that don't fit comfortably
+// on the command line. Command-line flags always take precedence.
+//
+// Location: ~/.config/rust-pomo/config.toml (or the platform's config dir)
+//
+//   sound = "chime"
+//   auto_advance = false
+//   daily_goal = 8
+//   report_at = "18:00"
+//   overtime = false
+//   strict = false
+//   git = true
+//   no_wifi = false
+//   schedule = "3x25m/5m,25m/15m"
+//   long_break = "15m"
+//   long_break_every = 4
+//   enforce_break = "lock"
+//   media = "pause-on-focus"
+//   serve = "127.0.0.1:7878"
+//   serve_token = "..."
+//   data_dir = "~/Private/pomo"
+//   theme = "tomato"
+//
+//   [progress]
+//   focus_color = "red"
+//
+//   [encryption]
+//   key_file = "~/.config/rust-pomo/log.key"
+//
+//   [notifications]
+//   events = ["start", "end", "warning"]
+//   warn_before = "2m"
+//   sound = true
+//   speak = false
+//
+//   [hooks]
+//   on_focus_start = "~/bin/slack-status focusing"
+//   on_break_start = "~/bin/slack-status clear"
+//
+//   [slack]
+//   token = "xoxp-..."
+//
+//   [time_tracking]
+//   service = "toggl"
+//   workspace = "1234567"
+//
+//   [apps]
+//   names = ["Slack", "Discord"]
+//   action = "hide"
+//
+//   [firewall]
+//   allow = ["vpn.example.com", "10.0.0.0/8"]
+//
+//   [shortcuts]
+//   on_focus = "Deep Work"
+//   after_focus = "Deep Work Off"
+//
+//   [webhook]
+//   url = "https://example.com/hook"
+//
+//   [mqtt]
+//   broker = "mqtt://192.168.1.10:1883"
+//   topic_prefix = "pomo"
+//
+//   [idle]
+//   after = "5m"
+//   action = "pause"
+//
+//   [battery]
+//   keep_awake = false
+//   warn = true
+//
+//   [on_failure]
+//   default = "abort"
+//   wifi = "retry"
+//
+//   [profiles.writing]
+//   focus = "50m"
+//   break_time = "10m"
+//   block = ["wifi", "apps"]
+
+use crate::{
+    battery::BatterySettings,
+    blockers::{AppSettings, FailurePolicies, FirewallSettings},
+    crypt::EncryptionSettings,
+    enforce::BreakEnforcement,
+    error::{Error, Result},
+    hooks::Hooks,
+    integrations::{
+        MediaMode, ShortcutSettings, SlackSettings, TimeTrackingSettings, WebhookSettings,
+    },
+    mqtt::MqttSettings,
+    notify::NotifySettings,
+    power::IdleSettings,
+    profile::Profile,
+    schedule::Schedule,
+    sound::Sound,
+    theme::{ProgressSettings, Theme},
+};
+use chrono::NaiveTime;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub sound: Option<Sound>,
+    pub auto_advance: bool,
+    /// Pomodoros to complete per day
+    pub daily_goal: Option<u32>,
+    /// Time of day for the end-of-day report (see report.rs)
+    #[serde(deserialize_with = "crate::duration::deserialize_optional_time")]
+    pub report_at: Option<NaiveTime>,
+    /// Keep counting past the end of focus (`--overtime`)
+    pub overtime: bool,
+    /// A paused or skipped pomodoro doesn't count (`--strict`)
+    pub strict: bool,
+    /// Record the git repository and branch with each phase (`--git`)
+    pub git: bool,
+    /// Never touch Wi-Fi, whatever `--block` says
+    pub no_wifi: bool,
+    /// Default schedule when no lengths are given on the command line
+    pub schedule: Option<Schedule>,
+    /// Length of every `long_break_every`th break (`--long-break`)
+    #[serde(deserialize_with = "crate::duration::deserialize_optional_duration")]
+    pub long_break: Option<u64>,
+    pub long_break_every: Option<u32>,
+    pub notifications: NotifySettings,
+    pub hooks: Hooks,
+    /// Slack status during focus (see integrations/slack.rs)
+    pub slack: Option<SlackSettings>,
+    /// Toggl Track or Clockify entries for focus (see integrations/time_tracking.rs)
+    pub time_tracking: Option<TimeTrackingSettings>,
+    /// Apps for the apps blocker
+    pub apps: AppSettings,
+    /// What the firewall blocker keeps reachable
+    pub firewall: FirewallSettings,
+    /// macOS Shortcuts to run when focus starts and ends
+    pub shortcuts: ShortcutSettings,
+    /// Address for the HTTP status server (`--serve`)
+    pub serve: Option<SocketAddr>,
+    /// Bearer token the HTTP status server asks for
+    pub serve_token: Option<String>,
+    /// Timer state published to an MQTT broker
+    pub mqtt: Option<MqttSettings>,
+    /// Webhook called at phase transitions
+    pub webhook: WebhookSettings,
+    /// Pause or play media at phase transitions (`--media`)
+    pub media: Option<MediaMode>,
+    /// Lock the screen or cover the terminal during breaks
+    pub enforce_break: Option<BreakEnforcement>,
+    /// Idle detection during focus
+    pub idle: IdleSettings,
+    /// Keep-awake and warnings on battery (see battery.rs)
+    pub battery: BatterySettings,
+    /// What to do when a blocker fails (`--on-failure`), per blocker
+    pub on_failure: FailurePolicies,
+    /// Where the session log and the plan are kept, instead of the
+    /// platform's data directory
+    pub data_dir: Option<PathBuf>,
+    /// Progress bar layout (see theme.rs)
+    pub theme: Theme,
+    /// Progress bar colours and characters
+    pub progress: ProgressSettings,
+    /// Encrypt the session log at rest (see crypt.rs)
+    pub encryption: EncryptionSettings,
+    /// Named sets of settings picked with `--profile` (see profile.rs)
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+// Location of the config file
+pub fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rust-pomo")
+        .join("config.toml")
+}
+
+// A path from the config file, with a leading `~` for the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+// Read the config file; a missing file means all defaults
+pub fn load() -> Result<Config> {
+    let path = config_path();
+    match fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| Error::Config {
+            message: e.to_string().trim_end().to_string(),
+            path,
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(Error::Config {
+            message: e.to_string(),
+            path,
+        }),
+    }
+}
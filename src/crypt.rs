@@ -0,0 +1,253 @@
+// Encryption at rest for the session log, for task names that shouldn't be
+// synced in plaintext:
+//
+//   [encryption]
+//   key_file = "~/.config/rust-pomo/log.key"
+//   # or
+//   passphrase_command = "pass show pomo"
+//
+// The secret is the contents of the key file, or the passphrase printed by
+// the command (POMO_PASSPHRASE in the environment is used instead of running
+// it). A key file can hold anything hard to guess, e.g. the output of
+// `head -c 32 /dev/urandom | base64`; keep it out of the synced directory.
+//
+// Each line of the log is sealed on its own (XChaCha20-Poly1305 with a random
+// nonce), so the log can still be appended to a line at a time:
+//
+//   enc1:<salt>:<nonce and ciphertext>     (both base64)
+//
+// The key is derived from the secret with Argon2id and a salt picked once per
+// log (new lines reuse the salt of the first sealed one), so it is derived
+// once per run. The inbox (inbox.rs) and the plan (plan.rs) next to the log are
+// sealed with the same key. Lines written before encryption was turned on stay
+// readable; `pomo encrypt-log` seals them, and the inbox and plan too.
+//
+// Not covered: the running session's state (session.json, for `pomo resume`)
+// and the time-tracking entries waiting to be sent (time-tracking.json) keep
+// the task name and tags in plaintext in the state directory, which is local
+// and left out of `data_dir`.
+
+use crate::{config::expand_home, hooks::shell};
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, Generate},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, io,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// Start of a sealed line
+const PREFIX: &str = "enc1:";
+
+/// Variable that holds the passphrase instead of `passphrase_command`
+pub const PASSPHRASE_VAR: &str = "POMO_PASSPHRASE";
+
+pub type Salt = [u8; 16];
+type Key = [u8; 32];
+
+/// `[encryption]` in the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionSettings {
+    /// File whose contents are the secret
+    pub key_file: Option<PathBuf>,
+    /// Command that prints the passphrase (e.g. from a password manager)
+    pub passphrase_command: Option<String>,
+}
+
+impl EncryptionSettings {
+    pub fn enabled(&self) -> bool {
+        self.key_file.is_some() || self.passphrase_command.is_some()
+    }
+}
+
+/// Seals and opens lines with the configured secret, deriving a key per salt
+/// only once
+pub struct Keyring {
+    settings: EncryptionSettings,
+    secret: OnceLock<Result<Vec<u8>, String>>,
+    keys: Mutex<HashMap<Salt, Key>>,
+}
+
+impl Keyring {
+    pub fn new(settings: EncryptionSettings) -> Self {
+        Keyring {
+            settings,
+            secret: OnceLock::new(),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A fresh salt for a log with no sealed lines yet
+    pub fn new_salt() -> Salt {
+        Salt::generate()
+    }
+
+    /// Seal one line of the log
+    pub fn seal(&self, line: &str, salt: &Salt) -> io::Result<String> {
+        let key = self.key(salt)?;
+        let nonce = XNonce::generate();
+        let sealed = XChaCha20Poly1305::new(&key.into())
+            .encrypt(&nonce, line.as_bytes())
+            .map_err(|_| io::Error::other("failed to encrypt a line of the session log"))?;
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&sealed);
+        Ok(format!(
+            "{}{}:{}",
+            PREFIX,
+            STANDARD.encode(salt),
+            STANDARD.encode(payload)
+        ))
+    }
+
+    /// Open a sealed line
+    pub fn open(&self, line: &str) -> io::Result<String> {
+        let (salt, payload) = parse(line)?;
+        let key = self.key(&salt)?;
+        let (nonce, sealed) = payload
+            .split_at_checked(24)
+            .ok_or_else(|| invalid("too short"))?;
+        let nonce = XNonce::try_from(nonce).map_err(|_| invalid("bad nonce"))?;
+        let plain = XChaCha20Poly1305::new(&key.into())
+            .decrypt(&nonce, sealed)
+            .map_err(|_| invalid("wrong passphrase or key file, or the line was altered"))?;
+        String::from_utf8(plain).map_err(|_| invalid("not text"))
+    }
+
+    /// Get the secret and derive the key for `salt` now
+    pub fn unlock(&self, salt: &Salt) -> io::Result<()> {
+        self.key(salt).map(drop)
+    }
+
+    fn key(&self, salt: &Salt) -> io::Result<Key> {
+        if let Some(key) = self.keys.lock().unwrap().get(salt) {
+            return Ok(*key);
+        }
+        let secret = self
+            .secret
+            .get_or_init(|| secret(&self.settings))
+            .as_ref()
+            .map_err(|message| io::Error::other(message.clone()))?;
+        let key = derive(secret, salt)?;
+        self.keys.lock().unwrap().insert(*salt, key);
+        Ok(key)
+    }
+}
+
+/// Whether a line of the log is sealed
+pub fn is_sealed(line: &str) -> bool {
+    line.starts_with(PREFIX)
+}
+
+/// The salt a sealed line was sealed with
+pub fn salt_of(line: &str) -> Option<Salt> {
+    parse(line).ok().map(|(salt, _)| salt)
+}
+
+fn parse(line: &str) -> io::Result<(Salt, Vec<u8>)> {
+    let (salt, payload) = line
+        .trim()
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(|| invalid("not a sealed line"))?;
+    let salt = STANDARD
+        .decode(salt)
+        .ok()
+        .and_then(|salt| Salt::try_from(salt).ok())
+        .ok_or_else(|| invalid("bad salt"))?;
+    let payload = STANDARD
+        .decode(payload)
+        .map_err(|_| invalid("bad base64"))?;
+    Ok((salt, payload))
+}
+
+fn derive(secret: &[u8], salt: &Salt) -> io::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| io::Error::other(format!("failed to derive the log key: {}", e)))?;
+    Ok(key)
+}
+
+// The key file's contents, or the passphrase
+fn secret(settings: &EncryptionSettings) -> Result<Vec<u8>, String> {
+    if let Some(path) = &settings.key_file {
+        let path = expand_home(path);
+        let secret = std::fs::read(&path)
+            .map_err(|e| format!("can't read key file {}: {}", path.display(), e))?;
+        return non_empty(secret, "the key file");
+    }
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return non_empty(passphrase.into_bytes(), PASSPHRASE_VAR);
+    }
+    let Some(command) = &settings.passphrase_command else {
+        return Err("no key_file or passphrase_command in [encryption]".to_string());
+    };
+    let output = shell(command)
+        .output()
+        .map_err(|e| format!("passphrase_command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("passphrase_command failed ({})", output.status));
+    }
+    // Drop the newline the command ends with
+    let mut passphrase = output.stdout;
+    while passphrase
+        .last()
+        .is_some_and(|b| *b == b'\n' || *b == b'\r')
+    {
+        passphrase.pop();
+    }
+    non_empty(passphrase, "passphrase_command")
+}
+
+fn non_empty(secret: Vec<u8>, from: &str) -> Result<Vec<u8>, String> {
+    if secret.iter().all(u8::is_ascii_whitespace) {
+        return Err(format!("{} gave an empty secret", from));
+    }
+    Ok(secret)
+}
+
+fn invalid(why: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("can't decrypt the session log: {}", why),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_sealed_and_opened_with_the_same_secret() {
+        let keyring = Keyring::new(EncryptionSettings::default());
+        let salt = Keyring::new_salt();
+        // No secret configured: hand the keyring its key
+        let key = derive(b"correct horse", &salt).unwrap();
+        keyring.keys.lock().unwrap().insert(salt, key);
+
+        let line = r#"{"task":"secret project"}"#;
+        let sealed = keyring.seal(line, &salt).unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("secret"));
+        assert_eq!(salt_of(&sealed), Some(salt));
+        assert_eq!(keyring.open(&sealed).unwrap(), line);
+        // A random nonce per line
+        assert_ne!(keyring.seal(line, &salt).unwrap(), sealed);
+
+        let other = Keyring::new(EncryptionSettings::default());
+        other
+            .keys
+            .lock()
+            .unwrap()
+            .insert(salt, derive(b"wrong", &salt).unwrap());
+        assert!(other.open(&sealed).is_err());
+        assert!(!is_sealed(line));
+    }
+}
@@ -0,0 +1,421 @@
+// Control channel for a running session. Every session (foreground or daemon)
+// listens on a Unix socket in the state directory, and the `pause`, `resume`,
+// `skip`, `stop`, `extend`, `snooze` and `status` subcommands connect to it to control
+// the timer. Named sessions (`--name`) each have a socket of their own, which
+// is how `pomo list` finds them. On Windows the session listens on a loopback
+// TCP port instead, and the socket file holds the port number and a random
+// token; a connection whose first line isn't the token is dropped, so only
+// someone who can read the file (in the user's own state directory) gets in.
+//
+// The protocol is one line per request and one line per response:
+//   client -> "pause\n"
+//   daemon -> "ok: paused\n"   (or "error: ...\n")
+// `extend 5m` (or `extend -5m`) changes the length of the running phase;
+// `snooze` (or `snooze 10m`) puts off the phase waiting to start;
+// `pause phone call` pauses and notes why (for a pause already under way, it
+// only notes why); `note call the bank` adds a note to the inbox (inbox.rs).
+// `status-json` replies with the session state as a JSON object instead, and
+// `statusline` with the compact line of `pomo statusline`. `follow` keeps the
+// connection open and streams the session's events (follow.rs).
+
+use crate::{
+    duration::{format_duration, parse_duration, parse_signed_duration},
+    error::{Error, Result},
+    follow,
+    output::{Snapshot, Status},
+    runner::{self, AppState},
+    session::cycle_of,
+    state, statusline,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{Arc, atomic::Ordering},
+    thread,
+    time::Duration,
+};
+
+/// How long a follower may keep the session waiting on an event
+const FOLLOW_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Location of the control socket
+pub fn socket_path() -> PathBuf {
+    socket_path_of(state::name().as_deref())
+}
+
+fn socket_path_of(name: Option<&str>) -> PathBuf {
+    state::session_file(name, "pomo", "sock")
+}
+
+// Location of the daemon's stdout/stderr log
+pub fn log_path() -> PathBuf {
+    state::session_file(state::name().as_deref(), "daemon", "log")
+}
+
+// Whether a session is currently listening on the control socket
+pub fn is_running() -> bool {
+    is_running_named(state::name().as_deref())
+}
+
+// Whether the session of that name (`None`: the unnamed one) is running
+pub fn is_running_named(name: Option<&str>) -> bool {
+    transport::connect(&socket_path_of(name)).is_ok()
+}
+
+// Names of the sessions listening on a control socket, the unnamed one as
+// "default"
+pub fn running_sessions() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(state::state_dir()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file = entry.file_name().into_string().ok()?;
+            let name = file.strip_prefix("pomo")?.strip_suffix(".sock")?;
+            match name.strip_prefix('.') {
+                Some(name) => Some(name.to_string()),
+                None if name.is_empty() => Some(state::DEFAULT_NAME.to_string()),
+                None => None,
+            }
+        })
+        .filter(|name| is_running_named(session_name(name)))
+        .collect();
+    names.sort();
+    names
+}
+
+// The session name as used for file names (`None` for "default")
+pub fn session_name(name: &str) -> Option<&str> {
+    Some(name).filter(|&name| name != state::DEFAULT_NAME)
+}
+
+// Send a command to the session of that name rather than the selected one
+pub fn send_to(name: &str, command: &str) -> Result<String> {
+    request(socket_path_of(session_name(name)), command)
+}
+
+// Re-run this executable as a detached `daemon` process with the given timer arguments
+pub fn spawn(args: &[String]) -> io::Result<u32> {
+    if is_running() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a session is already running",
+        ));
+    }
+
+    fs::create_dir_all(state::state_dir())?;
+    let log = File::create(log_path())?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("daemon")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    detach(&mut command);
+    Ok(command.spawn()?.id())
+}
+
+// Own process group, so Ctrl+C in the launching terminal doesn't reach it
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+// No console, and a process group of its own for the same reason
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+// Send a single command to the running session and return its reply
+pub fn send(command: &str) -> Result<String> {
+    request(socket_path(), command)
+}
+
+// Follow the running session: its events, one line each, until it ends
+pub fn follow() -> Result<impl BufRead> {
+    let mut stream = transport::connect(&socket_path()).map_err(Error::NotRunning)?;
+    writeln!(stream, "follow")?;
+    Ok(BufReader::new(stream))
+}
+
+fn request(socket: PathBuf, command: &str) -> Result<String> {
+    let mut stream = transport::connect(&socket).map_err(Error::NotRunning)?;
+    writeln!(stream, "{}", command)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+// Bind the control socket and handle requests on a background thread
+pub fn listen(app_state: Arc<AppState>) -> Result<()> {
+    let path = socket_path();
+    if is_running() {
+        return Err(Error::AlreadyRunning);
+    }
+
+    // A socket file left behind by a session that died is stale; replace it
+    fs::create_dir_all(state::state_dir())?;
+    let _ = fs::remove_file(&path);
+    let listener = transport::bind(&path)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = handle_client(stream, &app_state) {
+                log::warn!("Control connection failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+// Remove the control socket when the session ends
+pub fn cleanup() {
+    let _ = fs::remove_file(socket_path());
+}
+
+fn handle_client(mut stream: transport::Stream, app_state: &AppState) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    if !transport::authenticate(&mut reader)? {
+        return Ok(());
+    }
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    // `is_running` probes connect and hang up without sending anything
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    if line.trim() == "follow" {
+        stream.set_write_timeout(Some(FOLLOW_WRITE_TIMEOUT))?;
+        return follow::attach(stream, &status_json(app_state));
+    }
+    writeln!(stream, "{}", handle_command(line.trim(), app_state))
+}
+
+#[cfg(unix)]
+mod transport {
+    use std::{
+        io::{self, BufRead},
+        os::unix::net::{UnixListener, UnixStream},
+        path::Path,
+    };
+
+    pub type Stream = UnixStream;
+
+    pub fn connect(path: &Path) -> io::Result<UnixStream> {
+        UnixStream::connect(path)
+    }
+
+    pub fn bind(path: &Path) -> io::Result<UnixListener> {
+        UnixListener::bind(path)
+    }
+
+    // Anyone who can reach the socket may use it
+    pub fn authenticate(_: &mut impl BufRead) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+// std has no Unix sockets on Windows: listen on a loopback port and leave
+// the port number where the socket would be, with a token that clients send
+// first. Any local user can reach the port, only the owner can read the file.
+#[cfg(windows)]
+mod transport {
+    use crate::crypt::Keyring;
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use std::{
+        fs,
+        io::{self, BufRead, Write},
+        net::{Ipv4Addr, TcpListener, TcpStream},
+        path::Path,
+        sync::OnceLock,
+    };
+
+    pub type Stream = TcpStream;
+
+    /// The token of the session listening in this process
+    static TOKEN: OnceLock<String> = OnceLock::new();
+
+    pub fn connect(path: &Path) -> io::Result<TcpStream> {
+        let control = fs::read_to_string(path)?;
+        let (port, token) = control
+            .trim()
+            .split_once(' ')
+            .and_then(|(port, token)| Some((port.parse::<u16>().ok()?, token)))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "control file holds no port")
+            })?;
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+        writeln!(stream, "{}", token)?;
+        Ok(stream)
+    }
+
+    pub fn bind(path: &Path) -> io::Result<TcpListener> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let token = TOKEN.get_or_init(|| STANDARD.encode(Keyring::new_salt()));
+        let port = listener.local_addr()?.port();
+        fs::write(path, format!("{} {}", port, token))?;
+        Ok(listener)
+    }
+
+    // Whether the connection starts with this session's token
+    pub fn authenticate(reader: &mut impl BufRead) -> io::Result<bool> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        Ok(TOKEN.get().is_some_and(|token| line.trim_end() == token))
+    }
+}
+
+// Carry out a control command and return the reply line (also used by the
+// HTTP server)
+pub fn handle_command(command: &str, app_state: &AppState) -> String {
+    log::trace!("control request: {}", command);
+    match command {
+        "pause" => {
+            if !app_state.pause(None) {
+                return "error: nothing running to pause".to_string();
+            }
+            "ok: paused".to_string()
+        }
+        _ if command.starts_with("pause ") => {
+            let reason = command["pause ".len()..].trim();
+            if !app_state.pause(Some(reason.to_string())) {
+                return "error: nothing running to pause".to_string();
+            }
+            format!("ok: paused ({})", reason)
+        }
+        "resume" | "skip" if app_state.waiting.load(Ordering::SeqCst) => {
+            app_state.waiting.store(false, Ordering::SeqCst);
+            "ok: starting next phase".to_string()
+        }
+        "resume" => {
+            app_state.paused.store(false, Ordering::SeqCst);
+            "ok: running".to_string()
+        }
+        "skip" => {
+            app_state.skip.store(true, Ordering::SeqCst);
+            "ok: skipping current phase".to_string()
+        }
+        "stop" => {
+            app_state.stop.store(true, Ordering::SeqCst);
+            "ok: stopping".to_string()
+        }
+        _ if command.starts_with("note ") => match app_state.note(&command["note ".len()..]) {
+            Ok(()) => "ok: noted".to_string(),
+            Err(e) => format!("error: {}", e),
+        },
+        _ if command.starts_with("extend ") => extend(&command["extend ".len()..], app_state),
+        "snooze" => snooze(runner::SNOOZE_STEP, app_state),
+        _ if command.starts_with("snooze ") => match parse_duration(&command["snooze ".len()..]) {
+            Ok(0) => "error: snooze for how long? (e.g. 10m)".to_string(),
+            Ok(seconds) => snooze(seconds, app_state),
+            Err(e) => format!("error: {}", e),
+        },
+        "status" => status_line(app_state),
+        "status-json" => status_json(app_state).to_json(),
+        "statusline" => statusline_text(app_state),
+        other => format!("error: unknown command '{}'", other),
+    }
+}
+
+// `snooze 10m`: start the phase waiting to start that much later
+fn snooze(seconds: u64, app_state: &AppState) -> String {
+    if !app_state.snooze(seconds) {
+        return "error: no phase is waiting to start".to_string();
+    }
+    format!("ok: snoozing the next phase {}", format_duration(seconds))
+}
+
+// `extend 5m` / `extend -5m`: lengthen or shorten the running phase
+fn extend(by: &str, app_state: &AppState) -> String {
+    let seconds = match parse_signed_duration(by) {
+        Ok(0) => return "error: extend by how much? (e.g. 5m or -5m)".to_string(),
+        Ok(seconds) => seconds,
+        Err(e) => return format!("error: {}", e),
+    };
+    if !app_state.extend(seconds) {
+        return "error: no phase is running".to_string();
+    }
+    format!(
+        "ok: {} the current phase by {}",
+        if seconds > 0 {
+            "extending"
+        } else {
+            "shortening"
+        },
+        format_duration(seconds.unsigned_abs())
+    )
+}
+
+// One-line summary of the current session
+fn status_line(app_state: &AppState) -> String {
+    let session = app_state.session.lock().unwrap();
+    match session.as_ref() {
+        Some(s) => format!(
+            "Cycle {}: {:?} {} / {}{}{}",
+            cycle_of(s.cycle, s.cycles),
+            s.phase,
+            format_duration(s.elapsed),
+            format_duration(s.phase_len()),
+            s.label().map(|l| format!(" [{}]", l)).unwrap_or_default(),
+            if app_state.overtime.load(Ordering::SeqCst) {
+                format!(" (overtime +{})", format_duration(s.overtime_elapsed))
+            } else if app_state.snoozing.load(Ordering::SeqCst) {
+                " (snoozed)".to_string()
+            } else if app_state.waiting.load(Ordering::SeqCst) {
+                " (waiting to start)".to_string()
+            } else if app_state.paused.load(Ordering::SeqCst) {
+                match s.interruptions.last().and_then(|i| i.reason.as_ref()) {
+                    Some(reason) => format!(" (paused {}: {})", format_duration(s.paused), reason),
+                    None => format!(" (paused {})", format_duration(s.paused)),
+                }
+            } else {
+                String::new()
+            }
+        ),
+        None => "Starting".to_string(),
+    }
+}
+
+// The same, for `pomo status --json`
+pub fn status_json(app_state: &AppState) -> Status {
+    let session = app_state.session.lock().unwrap();
+    let state = if session.is_none() {
+        "starting"
+    } else {
+        app_state.run_state()
+    };
+    Status {
+        running: true,
+        state: Some(state),
+        snapshot: session.as_ref().map(Snapshot::of),
+    }
+}
+
+// And for `pomo statusline` (empty until the first phase has started)
+pub fn statusline_text(app_state: &AppState) -> String {
+    let session = app_state.session.lock().unwrap();
+    let note = match app_state.run_state() {
+        "waiting" => Some("ready"),
+        "snoozed" => Some("snoozed"),
+        "paused" => Some("paused"),
+        "overtime" => Some("overtime"),
+        _ => None,
+    };
+    session
+        .as_ref()
+        .map(|s| statusline::render(s, note))
+        .unwrap_or_default()
+}
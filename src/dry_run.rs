@@ -0,0 +1,89 @@
+// `--dry-run`: go through a session without touching anything, to try out
+// schedules, hooks and blockers. Every action with an effect outside pomo is
+// printed instead of performed:
+//
+//   $ pomo --dry-run --block wifi,apps --time-scale 60
+//   [dry run] engage wifi
+//   [dry run] engage apps
+//   [dry run] run hook on_focus_start: ~/bin/slack-status focusing
+//   [dry run] notify: Focus started
+//
+// That covers blockers, hooks, notifications, sounds, integrations (Slack,
+// webhook, media, ...), the MQTT broker, screen locking and the session log.
+// The state file and control socket are still used, so `pomo status`, `pomo
+// skip` and `pomo resume` work as usual (a resumed dry run stays dry).
+//
+// `--time-scale 60` (dry runs only) makes the clock run 60 times faster, so a
+// whole schedule can be watched in a few minutes.
+
+use crate::{blockers::DistractionBlocker, notify::Notifier, say};
+use std::{
+    fmt, io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Print actions instead of performing them for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Report an action that a real run would perform
+pub fn action(what: impl fmt::Display) {
+    say!("[dry run] {}", what);
+}
+
+/// Stands in for a blocker, printing what it would do
+pub struct DryRunBlocker {
+    name: &'static str,
+}
+
+impl DryRunBlocker {
+    pub fn new(name: &'static str) -> Self {
+        DryRunBlocker { name }
+    }
+}
+
+impl DistractionBlocker for DryRunBlocker {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn engage(&self) -> io::Result<()> {
+        action(format!("engage {}", self.name));
+        Ok(())
+    }
+
+    fn release(&self) -> io::Result<()> {
+        action(format!("release {}", self.name));
+        Ok(())
+    }
+}
+
+/// Prints notifications instead of showing them
+pub struct DryRunNotifier;
+
+impl Notifier for DryRunNotifier {
+    fn notify(
+        &self,
+        _title: &str,
+        subtitle: Option<&str>,
+        message: &str,
+        sound: bool,
+    ) -> io::Result<()> {
+        let mut text = format!("notify: {}", message);
+        if let Some(subtitle) = subtitle {
+            text.push_str(&format!(" ({})", subtitle));
+        }
+        if sound {
+            text.push_str(" with sound");
+        }
+        action(text);
+        Ok(())
+    }
+}
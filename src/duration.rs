@@ -0,0 +1,180 @@
+// Human-friendly durations for the command line, e.g. `25m`, `1h30m`, `90s`.
+// A bare number is taken as seconds so existing invocations keep working.
+// Times of day (`--until 14:30`, `report_at = "18:00"`) are HH:MM.
+
+use chrono::NaiveTime;
+use serde::Deserialize;
+
+/// Parse a duration string into seconds (used as a Clap value parser)
+pub fn parse_duration(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("duration is empty (expected e.g. 25m, 1h30m, 90s)".to_string());
+    }
+
+    // Plain seconds
+    if let Ok(seconds) = input.parse::<u64>() {
+        return Ok(seconds);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let multiplier = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => {
+                return Err(format!("unexpected '{}' (use h, m and s, e.g. 1h30m)", c));
+            }
+        };
+        if number.is_empty() {
+            return Err(format!("missing number before '{}'", c));
+        }
+
+        let value: u64 = number.parse().map_err(|_| "number too large".to_string())?;
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|v| total.checked_add(v))
+            .ok_or_else(|| "duration too long".to_string())?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!("missing unit after '{}' (use h, m or s)", number));
+    }
+    Ok(total)
+}
+
+/// Parse a duration with an optional sign, e.g. "5m", "+5m" or "-2m30s"
+pub fn parse_signed_duration(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    let (sign, rest) = match input.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, input.strip_prefix('+').unwrap_or(input)),
+    };
+    let seconds = i64::try_from(parse_duration(rest)?).map_err(|_| "duration too long")?;
+    Ok(sign * seconds)
+}
+
+/// Format seconds back into the same compact form, e.g. 5400 -> "1h30m"
+pub fn format_duration(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    let mut out = String::new();
+    if h > 0 {
+        out.push_str(&format!("{}h", h));
+    }
+    if m > 0 {
+        out.push_str(&format!("{}m", m));
+    }
+    if s > 0 || out.is_empty() {
+        out.push_str(&format!("{}s", s));
+    }
+    out
+}
+
+/// Signed form of `format_duration`, e.g. -300 -> "-5m"
+pub fn format_signed_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_duration(seconds.unsigned_abs()))
+}
+
+/// Serde helper: accept either seconds (as saved in the state file) or a
+/// duration string like "2m" (as written in the config file)
+pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(seconds) => Ok(seconds),
+        Raw::Text(text) => parse_duration(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Serde helper for optional durations (use with `#[serde(default)]`)
+pub fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_duration(deserializer).map(Some)
+}
+
+/// Parse a time of day like "18:00"
+pub fn parse_time(input: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(input.trim(), "%H:%M")
+        .map_err(|_| format!("'{}' is not a time of day (use HH:MM)", input.trim()))
+}
+
+/// Serde helper for an optional time of day (use with `#[serde(default)]`)
+pub fn deserialize_optional_time<'de, D>(deserializer: D) -> Result<Option<NaiveTime>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_time(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_with_units_or_as_seconds() {
+        // Bare seconds, as before units were accepted
+        assert_eq!(parse_duration("1500"), Ok(1500));
+        assert_eq!(parse_duration(" 90s "), Ok(90));
+        assert_eq!(parse_duration("25m"), Ok(1500));
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert_eq!(parse_duration("1h0m5s"), Ok(3605));
+
+        for bad in ["", "5x", "1.5h", "m", "1h30", "-5m"] {
+            assert!(parse_duration(bad).is_err(), "{:?}", bad);
+        }
+        assert_eq!(
+            parse_duration("99999999999999999999s"),
+            Err("number too large".to_string())
+        );
+        assert_eq!(
+            parse_duration("9999999999999999h"),
+            Err("duration too long".to_string())
+        );
+
+        assert_eq!(parse_signed_duration("5m"), Ok(300));
+        assert_eq!(parse_signed_duration("+5m"), Ok(300));
+        assert_eq!(parse_signed_duration("-2m30s"), Ok(-150));
+        assert!(parse_signed_duration("--5m").is_err());
+        assert_eq!(format_signed_duration(-300), "-5m");
+    }
+
+    #[test]
+    fn durations_format_back_to_what_parses() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(90), "1m30s");
+        assert_eq!(format_duration(5400), "1h30m");
+        for seconds in [0, 1, 59, 60, 61, 1500, 3600, 3605, 86_399, 90_061] {
+            assert_eq!(parse_duration(&format_duration(seconds)), Ok(seconds));
+        }
+
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(parse_time("18:00"), Ok(time(18, 0)));
+        assert_eq!(parse_time(" 9:05 "), Ok(time(9, 5)));
+        for bad in ["24:00", "18", "6pm", "18:60"] {
+            assert!(parse_time(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
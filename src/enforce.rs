@@ -0,0 +1,124 @@
+// Break enforcement, for anyone who keeps skipping breaks:
+//
+//   --enforce-break lock      lock the screen when a break starts
+//   --enforce-break overlay   cover the terminal with the break countdown
+//
+// or `enforce_break = "lock"` in the config file. Both last for the break
+// countdown only; waiting for the next phase is not enforced.
+//
+// lock … the screen is locked again whenever it is unlocked during the break.
+//        The third unlock in one break is let through (the escape hatch).
+// overlay … the terminal goes full screen with a countdown and swallows
+//        every key, Ctrl+C included; Esc three times in a row ends the break
+//        early (see tui.rs).
+//
+// `pomo skip` and `pomo stop` work as usual in both modes.
+
+use crate::{
+    dry_run,
+    power::{lock_screen, screen_locked},
+    runner::AppState,
+    say,
+    tui::Overlay,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// Unlocks during one break that are let through
+const ESCAPE_UNLOCKS: u32 = 3;
+
+/// How often the overlay is redrawn (and the break checked for its end)
+const POLL: Duration = Duration::from_millis(250);
+
+/// The lock state is checked every this many polls
+const LOCK_POLLS: u32 = 8;
+
+/// How a break is enforced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BreakEnforcement {
+    /// Lock the screen
+    Lock,
+    /// Full-screen countdown in the terminal
+    Overlay,
+}
+
+// Run the break countdown (`run`) with the break enforced
+pub fn during_break<T>(mode: BreakEnforcement, app_state: &AppState, run: impl FnOnce() -> T) -> T {
+    let done = AtomicBool::new(false);
+    thread::scope(|scope| {
+        match mode {
+            BreakEnforcement::Lock => {
+                scope.spawn(|| keep_locked(&done));
+            }
+            BreakEnforcement::Overlay => match Overlay::start() {
+                Ok(overlay) => {
+                    scope.spawn(|| {
+                        while !done.load(Ordering::SeqCst) {
+                            overlay.draw(app_state);
+                            thread::sleep(POLL);
+                        }
+                        drop(overlay);
+                    });
+                }
+                Err(e) => log::warn!("Could not show the break overlay: {}", e),
+            },
+        }
+        let result = run();
+        done.store(true, Ordering::SeqCst);
+        result
+    })
+}
+
+// Lock the screen, and again each time it is unlocked, until the break is done
+// or it has been unlocked ESCAPE_UNLOCKS times
+fn keep_locked(done: &AtomicBool) {
+    say!("Break time; locking the screen.");
+    if dry_run::is_enabled() {
+        dry_run::action("lock the screen");
+        return;
+    }
+    if let Err(e) = lock_screen() {
+        log::warn!("Could not lock the screen: {}", e);
+        return;
+    }
+    let mut unlocks = 0;
+    // Seen locked since the last lock; locking takes a moment, so an unlock
+    // only counts after that
+    let mut seen_locked = false;
+    let mut polls = 0u32;
+    while !done.load(Ordering::SeqCst) {
+        thread::sleep(POLL);
+        polls += 1;
+        if !polls.is_multiple_of(LOCK_POLLS) {
+            continue;
+        }
+        match screen_locked() {
+            Some(true) => seen_locked = true,
+            Some(false) if seen_locked => {
+                unlocks += 1;
+                seen_locked = false;
+                if unlocks >= ESCAPE_UNLOCKS {
+                    say!(
+                        "Unlocked {} times; no longer locking for this break.",
+                        unlocks
+                    );
+                    return;
+                }
+                say!("Still on a break; locking the screen again.");
+                if let Err(e) = lock_screen() {
+                    log::warn!("Could not lock the screen: {}", e);
+                    return;
+                }
+            }
+            Some(false) => {}
+            // The lock state can't be told: it was locked once, that's all
+            None => return,
+        }
+    }
+}
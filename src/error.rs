@@ -0,0 +1,175 @@
+// Errors that end `pomo`, told apart so the message can say what to do about
+// them and scripts can tell them apart by exit status:
+//
+//   0  done: the session ran to the end, or the command succeeded
+//   1  any other error (reading or writing the state file, session log, ...)
+//   2  invalid command-line arguments (including an --until time already past, or
+//      a task number not in `pomo plan list`)
+//   3  the session was stopped early with `pomo stop` or q (not an error)
+//   4  no session is running (`pomo pause`, `skip`, `stop`, `extend`, ...)
+//   5  a session is already running, or the running one refused the command
+//   6  the config file can't be read, or `--profile` names no profile in it
+//   7  a blocker failed (a Wi-Fi, Bluetooth, hosts, ... command didn't work)
+//   8  a program pomo needs isn't installed (networksetup, blueutil, osascript, ...)
+//   9  permission denied (e.g. the hosts blocker without sudo)
+//
+// Modules below keep returning io::Result; the error is classified where it
+// is known what was being attempted.
+
+use chrono::NaiveTime;
+use std::{io, path::PathBuf};
+
+/// Exit status of a session stopped before the end
+pub const EXIT_STOPPED: i32 = 3;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("config file {}: {message}", path.display())]
+    Config { path: PathBuf, message: String },
+
+    #[error("no profile named '{name}' in the config file")]
+    UnknownProfile { name: String, known: Vec<String> },
+
+    /// A blocker couldn't be set up, engaged or released
+    #[error("{blocker} blocker: {source}")]
+    Blocker {
+        blocker: &'static str,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A desktop notification couldn't be shown (never fatal)
+    #[error("notification failed: {0}")]
+    Notification(#[source] io::Error),
+
+    /// The sound player isn't there (never fatal)
+    #[error("sound can't be played: {0}")]
+    Sound(#[source] io::Error),
+
+    #[error("no running session ({0})")]
+    NotRunning(#[source] io::Error),
+
+    #[error("a session is already running")]
+    AlreadyRunning,
+
+    /// `--until` a time of day that has already passed
+    #[error("{} has already passed", .0.format("%H:%M"))]
+    TimePassed(NaiveTime),
+
+    #[error("there is no task {0} in the plan")]
+    NotInPlan(usize),
+
+    /// The running session replied with an error
+    #[error("{0}")]
+    Refused(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Exit status for this error (see the table above)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::TimePassed(_) | Error::NotInPlan(_) => 2,
+            Error::NotRunning(_) => 4,
+            Error::AlreadyRunning | Error::Refused(_) => 5,
+            Error::Config { .. } | Error::UnknownProfile { .. } => 6,
+            Error::Blocker { source, .. } | Error::Notification(source) | Error::Sound(source) => {
+                match source.kind() {
+                    io::ErrorKind::NotFound => 8,
+                    io::ErrorKind::PermissionDenied => 9,
+                    _ => 7,
+                }
+            }
+            Error::Io(_) => 1,
+        }
+    }
+
+    /// What the user can do about it, if there is something
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::Config { .. } => Some(
+                "fix the line mentioned above, or move the file aside to use the defaults".into(),
+            ),
+            Error::UnknownProfile { known, .. } if known.is_empty() => Some(
+                "add a [profiles.NAME] table to the config file (see `pomo profile list`)".into(),
+            ),
+            Error::UnknownProfile { known, .. } => {
+                Some(format!("profiles in the config file: {}", known.join(", ")))
+            }
+            Error::Blocker { blocker, source } => Some(match source.kind() {
+                io::ErrorKind::NotFound => format!(
+                    "install the program it needs, or leave `{}` out of --block",
+                    blocker
+                ),
+                io::ErrorKind::PermissionDenied => {
+                    format!("run pomo with sudo, or leave `{}` out of --block", blocker)
+                }
+                _ => format!("leave `{}` out of --block to run without it", blocker),
+            }),
+            Error::Notification(source) if source.kind() == io::ErrorKind::NotFound => Some(
+                "desktop notifications need `osascript` (macOS), `notify-send` (Linux) or \
+                 PowerShell (Windows); turn them off with --no-notify"
+                    .into(),
+            ),
+            Error::Sound(source) if source.kind() == io::ErrorKind::NotFound => Some(
+                "sounds need `afplay` (macOS), `paplay` or `aplay` (Linux) or PowerShell \
+                 (Windows); turn them off with --sound none"
+                    .into(),
+            ),
+            Error::TimePassed(_) => {
+                Some("give a later time today, or a length with --focus".into())
+            }
+            Error::NotInPlan(_) => {
+                Some("`pomo plan list` shows the tasks with their numbers".into())
+            }
+            Error::NotRunning(_) => Some("start one with `pomo` or `pomo start`".into()),
+            Error::AlreadyRunning => Some(
+                "control it with `pomo status`, `pomo stop` and the like, or run another \
+                 one next to it with --name"
+                    .into(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The blocker error for a failure of `blocker`
+    pub fn blocker(blocker: &'static str) -> impl FnOnce(io::Error) -> Error {
+        move |source| Error::Blocker { blocker, source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes_follow_the_cause() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "networksetup not found");
+        let wifi = Error::blocker("wifi")(missing);
+        assert_eq!(wifi.exit_code(), 8);
+        assert_eq!(wifi.to_string(), "wifi blocker: networksetup not found");
+        assert!(wifi.hint().unwrap().contains("leave `wifi` out of --block"));
+
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "/etc/hosts");
+        assert_eq!(Error::blocker("hosts")(denied).exit_code(), 9);
+        assert_eq!(
+            Error::blocker("wifi")(io::Error::other("failed")).exit_code(),
+            7
+        );
+
+        let profile = Error::UnknownProfile {
+            name: "reading".into(),
+            known: vec!["coding".into(), "writing".into()],
+        };
+        assert_eq!(profile.exit_code(), 6);
+        assert_eq!(
+            profile.hint().as_deref(),
+            Some("profiles in the config file: coding, writing")
+        );
+        assert_eq!(Error::Io(io::Error::other("disk full")).exit_code(), 1);
+    }
+}
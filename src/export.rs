@@ -0,0 +1,332 @@
+// `pomo export`: the session log as CSV or JSON, one row per phase, for
+// spreadsheets and time-tracking tools.
+//
+//   pomo export --format csv --from 2024-01-01 --to today > focus.csv
+//
+// Dates are YYYY-MM-DD, `today` or `yesterday`; a phase belongs to the day it
+// started. Both ends of the range are included.
+//
+//   pomo export --ics --from 2024-01-01 > focus.ics
+//
+// writes completed focus phases as calendar events instead (titled with the
+// task), to import into Google Calendar, Fantastical and the like.
+
+use crate::{
+    duration::format_duration,
+    history::{Outcome, PhaseRecord},
+    session::Phase,
+};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::ValueEnum;
+use std::io::{self, Write};
+
+/// Export file format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    /// iCalendar events, for completed focus phases only
+    Ics,
+}
+
+const CSV_HEADER: &[&str] = &[
+    "date",
+    "phase",
+    "cycle",
+    "started_at",
+    "ended_at",
+    "planned",
+    "adjusted",
+    "actual",
+    "paused",
+    "pauses",
+    "overtime",
+    "idle",
+    "outcome",
+    "task",
+    "tags",
+    "schedule_entry",
+    "estimate",
+    "repo",
+    "branch",
+];
+
+/// Parse a date argument (used as a Clap value parser)
+pub fn parse_date(input: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    match input.trim() {
+        "today" => Ok(today),
+        "yesterday" => Ok(today.pred_opt().unwrap_or(today)),
+        date => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+            format!(
+                "'{}' is not a date (use YYYY-MM-DD, today or yesterday)",
+                date
+            )
+        }),
+    }
+}
+
+// Records that started within the range
+pub fn filter(
+    records: &[PhaseRecord],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Vec<&PhaseRecord> {
+    records
+        .iter()
+        .filter(|r| {
+            let date = r.started_at.date_naive();
+            from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to)
+        })
+        .collect()
+}
+
+// Write the records in the given format
+pub fn write(
+    out: &mut impl Write,
+    records: &[&PhaseRecord],
+    format: ExportFormat,
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => write_csv(out, records),
+        ExportFormat::Ics => write_ics(out, records),
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, records)?;
+            writeln!(out)
+        }
+    }
+}
+
+fn write_csv(out: &mut impl Write, records: &[&PhaseRecord]) -> io::Result<()> {
+    writeln!(out, "{}", CSV_HEADER.join(","))?;
+    for r in records {
+        let fields = [
+            r.started_at.date_naive().to_string(),
+            json_name(&r.phase),
+            r.cycle.to_string(),
+            r.started_at.to_rfc3339(),
+            r.ended_at.to_rfc3339(),
+            r.planned.to_string(),
+            r.adjusted.to_string(),
+            r.actual.to_string(),
+            r.paused.to_string(),
+            r.pauses.to_string(),
+            r.overtime.to_string(),
+            r.idle.to_string(),
+            json_name(&r.outcome),
+            r.task.clone().unwrap_or_default(),
+            r.tags.join(";"),
+            r.schedule_entry.map(|e| e.to_string()).unwrap_or_default(),
+            r.estimate.map(|e| e.to_string()).unwrap_or_default(),
+            r.repo.clone().unwrap_or_default(),
+            r.branch.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(out, "{}", row.join(","))?;
+    }
+    Ok(())
+}
+
+// An iCalendar file (RFC 5545) with one event per completed focus phase
+fn write_ics(out: &mut impl Write, records: &[&PhaseRecord]) -> io::Result<()> {
+    let stamp = ics_time(&Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//rust-pomo//pomo export//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for r in records {
+        if r.phase != Phase::Focus || r.outcome != Outcome::Completed {
+            continue;
+        }
+        let start = r.started_at.with_timezone(&Utc);
+        let title = r.task.as_deref().unwrap_or("Focus");
+        let mut description = format!("Pomodoro {}: {} focus", r.cycle, format_duration(r.actual));
+        if r.overtime > 0 {
+            description.push_str(&format!(" + {} overtime", format_duration(r.overtime)));
+        }
+        match r.adjusted {
+            0 => {}
+            by if by > 0 => {
+                description.push_str(&format!(", extended by {}", format_duration(by as u64)))
+            }
+            by => description.push_str(&format!(
+                ", shortened by {}",
+                format_duration(by.unsigned_abs())
+            )),
+        }
+        if r.idle > 0 {
+            description.push_str(&format!(", idle {}", format_duration(r.idle)));
+        }
+        if r.paused > 0 {
+            description.push_str(&format!(", paused {}", format_duration(r.paused)));
+        }
+
+        lines.push("BEGIN:VEVENT".to_string());
+        // The start time identifies the phase, so importing twice doesn't
+        // duplicate events
+        lines.push(format!("UID:{}@rust-pomo", ics_time(&start)));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", ics_time(&start)));
+        lines.push(format!(
+            "DTEND:{}",
+            ics_time(&r.ended_at.with_timezone(&Utc))
+        ));
+        lines.push(format!("SUMMARY:{}", ics_text(title)));
+        lines.push(format!("DESCRIPTION:{}", ics_text(&description)));
+        if !r.tags.is_empty() {
+            let tags: Vec<String> = r.tags.iter().map(|t| ics_text(t)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        lines.push("TRANSP:OPAQUE".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    for line in lines {
+        write!(out, "{}\r\n", fold(&line))?;
+    }
+    Ok(())
+}
+
+fn ics_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Escape a TEXT value
+fn ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+        .replace('\r', "")
+}
+
+// Lines longer than 75 bytes continue on the next line after a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+// The name an enum is logged under ("focus", "completed", ...)
+fn json_name(value: &impl serde::Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+// Quote a field if it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history::Outcome, session::Phase};
+    use chrono::TimeZone;
+
+    fn record(day: u32, task: Option<&str>) -> PhaseRecord {
+        let started_at = Local.with_ymd_and_hms(2024, 1, day, 9, 0, 0).unwrap();
+        PhaseRecord {
+            phase: Phase::Focus,
+            cycle: 1,
+            started_at,
+            ended_at: started_at + chrono::Duration::minutes(25),
+            planned: 1500,
+            adjusted: 0,
+            actual: 1500,
+            paused: 60,
+            pauses: 1,
+            interruptions: Vec::new(),
+            overtime: 0,
+            idle: 0,
+            outcome: Outcome::Completed,
+            schedule_entry: None,
+            task: task.map(str::to_string),
+            tags: vec!["work".to_string(), "writing".to_string()],
+            estimate: None,
+            repo: None,
+            branch: None,
+            power: None,
+        }
+    }
+
+    #[test]
+    fn range_includes_both_ends() {
+        let records: Vec<PhaseRecord> = (1..=5).map(|day| record(day, None)).collect();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day);
+        assert_eq!(filter(&records, date(2), date(4)).len(), 3);
+        assert_eq!(filter(&records, None, date(1)).len(), 1);
+        assert_eq!(filter(&records, None, None).len(), 5);
+    }
+
+    #[test]
+    fn csv_rows_are_quoted_when_needed() {
+        let mut record = record(2, Some("report, \"draft\""));
+        record.repo = Some("rust-pomo".to_string());
+        record.branch = Some("main".to_string());
+        let mut out = Vec::new();
+        write(&mut out, &[&record], ExportFormat::Csv).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let row = text.lines().nth(1).unwrap();
+        assert!(row.starts_with("2024-01-02,focus,1,"));
+        assert!(row.ends_with(
+            ",1500,0,1500,60,1,0,0,completed,\"report, \"\"draft\"\"\",work;writing,,,rust-pomo,main"
+        ));
+    }
+
+    #[test]
+    fn ics_has_an_event_per_completed_focus() {
+        let mut skipped = record(3, None);
+        skipped.outcome = Outcome::Skipped;
+        let record = record(2, Some("report; draft"));
+        let mut out = Vec::new();
+        write(&mut out, &[&record, &skipped], ExportFormat::Ics).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(text.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(text.matches("BEGIN:VEVENT").count(), 1);
+        assert!(text.contains("\r\nSUMMARY:report\\; draft\r\n"));
+        assert!(text.contains("\r\nCATEGORIES:work,writing\r\n"));
+        let start = record.started_at.with_timezone(&Utc);
+        assert!(text.contains(&format!("\r\nDTSTART:{}\r\n", ics_time(&start))));
+    }
+
+    #[test]
+    fn long_ics_lines_are_folded() {
+        let line = format!("SUMMARY:{}", "x".repeat(100));
+        let folded = fold(&line);
+        let parts: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].len(), 75);
+        assert!(parts[1].starts_with(' '));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn dates_accept_keywords() {
+        assert_eq!(parse_date("today"), Ok(Local::now().date_naive()));
+        assert_eq!(
+            parse_date("2024-01-31"),
+            Ok(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())
+        );
+        assert!(parse_date("31/01/2024").is_err());
+    }
+}
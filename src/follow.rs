@@ -0,0 +1,126 @@
+// Live event stream of the running session, for overlays and launchers (OBS,
+// Raycast, Alfred, a status bar) that want a countdown without polling:
+//
+//   pomo follow             … the events of the running session, one JSON
+//                             object per line, until it ends
+//   pomo follow --wait      … wait for a session to start, and keep following
+//                             from one session to the next
+//   pomo --name emails follow
+//
+// The first line is the session as it stands, like `pomo status --json`:
+//   {"time":"...","event":"status","running":true,"state":"running","phase":"focus",...}
+// and then come the events of `--output json` (output.rs) as they happen:
+// tick once a second while a phase runs, phase_start, pause, resume, extend,
+// phase_end, waiting and finished. With --wait a line with "running":false
+// says when there is no session to follow.
+//
+// `pomo follow` sends `follow` on the control socket (daemon.rs); the session
+// keeps that connection and writes each event to it. A follower that stops
+// reading is dropped rather than holding up the timer.
+
+use crate::{
+    daemon,
+    error::{Error, Result},
+    output::Status,
+};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    io::{self, BufRead, Write},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+/// How often `--wait` looks for a session
+const POLL: Duration = Duration::from_secs(1);
+
+/// Connections to followers of this session
+static FOLLOWERS: Mutex<Vec<Box<dyn Write + Send>>> = Mutex::new(Vec::new());
+
+#[derive(Serialize)]
+struct StatusLine<'a> {
+    time: DateTime<Local>,
+    event: &'static str,
+    #[serde(flatten)]
+    status: &'a Status,
+}
+
+// The first line a follower gets: the session as it stands
+fn status_line(status: &Status) -> String {
+    let line = StatusLine {
+        time: Local::now(),
+        event: "status",
+        status,
+    };
+    serde_json::to_string(&line).unwrap_or_default()
+}
+
+// Start sending events to a follower, after telling it where the session is
+pub fn attach<W: Write + Send + 'static>(mut follower: W, status: &Status) -> io::Result<()> {
+    writeln!(follower, "{}", status_line(status))?;
+    follower.flush()?;
+    FOLLOWERS.lock().unwrap().push(Box::new(follower));
+    Ok(())
+}
+
+/// Whether anyone is following this session
+pub fn is_followed() -> bool {
+    !FOLLOWERS.lock().unwrap().is_empty()
+}
+
+// Send an event line to every follower, dropping those that are gone
+pub fn send(line: &str) {
+    FOLLOWERS.lock().unwrap().retain_mut(|follower| {
+        writeln!(follower, "{}", line)
+            .and_then(|_| follower.flush())
+            .is_ok()
+    });
+}
+
+// `pomo follow`: copy the running session's events to stdout
+pub fn run(wait: bool) -> Result<()> {
+    let mut stdout = io::stdout();
+    // A reader that has gone away (`| head`) ends it quietly
+    let mut print = |line: &str| writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+    let mut idle_told = false;
+    loop {
+        match daemon::follow() {
+            Ok(events) => {
+                idle_told = false;
+                // The connection closes when the session ends
+                for line in events.lines() {
+                    let Ok(line) = line else { break };
+                    if print(&line).is_err() {
+                        return Ok(());
+                    }
+                }
+                if !wait {
+                    return Ok(());
+                }
+            }
+            Err(Error::NotRunning(_)) if wait => {
+                if !idle_told && print(&status_line(&Status::not_running())).is_err() {
+                    return Ok(());
+                }
+                idle_told = true;
+            }
+            Err(e) => return Err(e),
+        }
+        thread::sleep(POLL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn the_first_line_is_the_status() {
+        let line: Value = serde_json::from_str(&status_line(&Status::not_running())).unwrap();
+        assert_eq!(line["event"], "status");
+        assert_eq!(line["running"], false);
+        assert!(line["time"].is_string());
+    }
+}
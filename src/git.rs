@@ -0,0 +1,73 @@
+// The git repository a session is run from, as an automatic label for the
+// session log:
+//
+//   pomo --git              (or `git = true` in the config file)
+//   pomo stats --by-repo
+//
+// At the start of the session, the repository of the working directory (its
+// top-level directory name) and the checked-out branch are looked up with
+// `git rev-parse` and recorded with every phase. `pomo stats --by-repo` then
+// counts pomodoros per repository and per branch. Outside a repository (or
+// without git) nothing is recorded.
+
+use serde::{Deserialize, Serialize};
+use std::{path::Path, process::Command};
+
+/// Where the session was started from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitContext {
+    /// Name of the repository's top-level directory
+    pub repo: String,
+    /// Checked-out branch; `None` on a detached HEAD
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+// The repository and branch of the working directory, if it is in one
+pub fn current() -> Option<GitContext> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel", "--abbrev-ref", "HEAD"])
+        .output()
+        .map_err(|e| log::debug!("git: {}", e))
+        .ok()?;
+    if !output.status.success() {
+        log::debug!("not in a git repository; nothing to record");
+        return None;
+    }
+    parse_rev_parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+// `git rev-parse --show-toplevel --abbrev-ref HEAD` prints the top-level
+// directory, then the branch ("HEAD" when detached)
+fn parse_rev_parse(output: &str) -> Option<GitContext> {
+    let mut lines = output.lines().map(str::trim);
+    let toplevel = lines.next().filter(|line| !line.is_empty())?;
+    let repo = Path::new(toplevel)
+        .file_name()?
+        .to_string_lossy()
+        .into_owned();
+    let branch = lines
+        .next()
+        .filter(|&branch| !branch.is_empty() && branch != "HEAD")
+        .map(str::to_string);
+    Some(GitContext { repo, branch })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rev_parse_output_names_repo_and_branch() {
+        assert_eq!(
+            parse_rev_parse("/home/me/src/rust-pomo\nfeature/git-stats\n"),
+            Some(GitContext {
+                repo: "rust-pomo".to_string(),
+                branch: Some("feature/git-stats".to_string()),
+            })
+        );
+        let detached = parse_rev_parse("/home/me/src/rust-pomo\nHEAD\n").unwrap();
+        assert_eq!(detached.branch, None);
+        assert_eq!(parse_rev_parse(""), None);
+    }
+}
@@ -0,0 +1,140 @@
+// Daily goal and streak, from the session log.
+//
+//   daily_goal = 8        (config file: completed pomodoros per day)
+//
+// The streak is the number of consecutive days the goal was met, up to today.
+// A day that isn't over yet doesn't break it: until today's goal is met the
+// streak counts from yesterday.
+
+use crate::{
+    history::{Outcome, PhaseRecord},
+    session::Phase,
+};
+use chrono::NaiveDate;
+use std::{collections::HashMap, fmt};
+
+/// How today is going against the daily goal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Pomodoros completed today
+    pub done: u32,
+    pub goal: u32,
+    /// Consecutive days the goal was met
+    pub streak: u32,
+}
+
+impl Progress {
+    pub fn of(records: &[PhaseRecord], goal: u32, today: NaiveDate) -> Self {
+        let per_day = completed_per_day(records);
+        let done_on = |date: &NaiveDate| per_day.get(date).copied().unwrap_or(0);
+
+        let done = done_on(&today);
+        let mut day = if done >= goal {
+            Some(today)
+        } else {
+            today.pred_opt()
+        };
+        let mut streak = 0;
+        while let Some(date) = day
+            && done_on(&date) >= goal
+        {
+            streak += 1;
+            day = date.pred_opt();
+        }
+        Progress { done, goal, streak }
+    }
+
+    pub fn is_met(&self) -> bool {
+        self.done >= self.goal
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} pomodoros today", self.done, self.goal)?;
+        match self.streak {
+            0 => Ok(()),
+            1 => write!(f, " · 1-day streak"),
+            n => write!(f, " · {}-day streak", n),
+        }
+    }
+}
+
+// Completed focus phases per day (by start date)
+fn completed_per_day(records: &[PhaseRecord]) -> HashMap<NaiveDate, u32> {
+    let mut per_day = HashMap::new();
+    for record in records {
+        if record.phase == Phase::Focus && record.outcome == Outcome::Completed {
+            *per_day.entry(record.started_at.date_naive()).or_default() += 1;
+        }
+    }
+    per_day
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn focus(day: u32, outcome: Outcome) -> PhaseRecord {
+        let started_at = Local.with_ymd_and_hms(2024, 3, day, 10, 0, 0).unwrap();
+        PhaseRecord {
+            phase: Phase::Focus,
+            cycle: 1,
+            started_at,
+            ended_at: started_at,
+            planned: 1500,
+            adjusted: 0,
+            actual: 1500,
+            paused: 0,
+            pauses: 0,
+            interruptions: Vec::new(),
+            overtime: 0,
+            idle: 0,
+            outcome,
+            schedule_entry: None,
+            task: None,
+            tags: Vec::new(),
+            estimate: None,
+            repo: None,
+            branch: None,
+            power: None,
+        }
+    }
+
+    // `count` completed pomodoros on each of the given days
+    fn log(days: &[(u32, usize)]) -> Vec<PhaseRecord> {
+        days.iter()
+            .flat_map(|&(day, count)| (0..count).map(move |_| focus(day, Outcome::Completed)))
+            .collect()
+    }
+
+    fn march(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    #[test]
+    fn counts_completed_focus_today() {
+        let mut records = log(&[(10, 3)]);
+        records.push(focus(10, Outcome::Skipped));
+        let progress = Progress::of(&records, 4, march(10));
+        assert_eq!(progress.done, 3);
+        assert!(!progress.is_met());
+        assert_eq!(progress.to_string(), "3/4 pomodoros today");
+    }
+
+    #[test]
+    fn streak_runs_back_from_today_or_yesterday() {
+        let records = log(&[(7, 2), (8, 2), (9, 2), (10, 1)]);
+        // Today isn't done yet, so the streak so far is 7th to 9th
+        assert_eq!(Progress::of(&records, 2, march(10)).streak, 3);
+
+        let records = log(&[(7, 2), (8, 1), (9, 2), (10, 2)]);
+        let progress = Progress::of(&records, 2, march(10));
+        assert_eq!(progress.streak, 2);
+        assert_eq!(progress.to_string(), "2/2 pomodoros today · 2-day streak");
+
+        // A missed day yesterday
+        assert_eq!(Progress::of(&log(&[(8, 2)]), 2, march(10)).streak, 0);
+    }
+}
@@ -0,0 +1,261 @@
+// `pomo stats --heatmap`: when the focus time happens, from the session log.
+//
+//   Focus by day (last 26 weeks)
+//         Jun     Jul       Aug     ...
+//   Mon · ░ ▒ █ ▓ ...
+//       ...
+//       less · ░ ▒ ▓ █ more  (busiest day 4h10m)
+//
+//   Focus by hour
+//     09:00  ████████████████          3h20m
+//     10:00  ████████████████████████  5h00m
+//
+// The calendar has a column per week (Monday first) and a row per weekday,
+// shaded by that day's focus time against the busiest day shown, as on a
+// GitHub profile. The histogram adds up focus time per hour of the day over
+// the whole log; a phase running past the hour counts toward both hours.
+// Overtime counts as focus time, as in the rest of `pomo stats`.
+
+use crate::{duration::format_duration, history::PhaseRecord, session::Phase};
+use chrono::{Datelike, Duration, NaiveDate, Timelike};
+use console::style;
+use std::{collections::HashMap, fmt};
+
+/// Weeks shown in the calendar (it fits an 80-column terminal)
+const WEEKS: i64 = 26;
+
+/// Shades from no focus to the busiest day
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Width of the longest bar in the histogram
+const BAR_WIDTH: u64 = 40;
+
+/// Room for the weekday names left of the calendar
+const LABEL_WIDTH: usize = 4;
+
+/// Focus time by day and by hour of the day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    /// Monday of the first week shown
+    first: NaiveDate,
+    today: NaiveDate,
+    /// Focus seconds per day
+    days: HashMap<NaiveDate, u64>,
+    /// Focus seconds per hour of the day (0-23)
+    hours: [u64; 24],
+}
+
+impl Heatmap {
+    pub fn of(records: &[PhaseRecord], today: NaiveDate) -> Self {
+        let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let mut heatmap = Heatmap {
+            first: monday - Duration::weeks(WEEKS - 1),
+            today,
+            days: HashMap::new(),
+            hours: [0; 24],
+        };
+        for record in records.iter().filter(|r| r.phase == Phase::Focus) {
+            let seconds = record.actual + record.overtime;
+            *heatmap
+                .days
+                .entry(record.started_at.date_naive())
+                .or_default() += seconds;
+
+            // Spread the phase over the hours it ran in
+            let mut at = record.started_at.time();
+            let mut left = seconds;
+            while left > 0 {
+                let to_next_hour = u64::from(3600 - at.minute() * 60 - at.second());
+                let part = left.min(to_next_hour);
+                heatmap.hours[at.hour() as usize] += part;
+                left -= part;
+                at = at
+                    .with_minute(0)
+                    .and_then(|t| t.with_second(0))
+                    .unwrap_or(at)
+                    + Duration::hours(1);
+            }
+        }
+        heatmap
+    }
+
+    /// Whether there is any focus time to show
+    pub fn is_empty(&self) -> bool {
+        self.hours.iter().all(|&seconds| seconds == 0)
+    }
+
+    // Focus time of the busiest day in the calendar
+    fn busiest_day(&self) -> u64 {
+        self.days
+            .iter()
+            .filter(|(day, _)| (self.first..=self.today).contains(day))
+            .map(|(_, &seconds)| seconds)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// Shade for `seconds` against the busiest day (`max`): none, then quarters
+fn level(seconds: u64, max: u64) -> usize {
+    if seconds == 0 || max == 0 {
+        0
+    } else {
+        (seconds * 4).div_ceil(max).clamp(1, 4) as usize
+    }
+}
+
+fn cell(level: usize) -> String {
+    let shade = SHADES[level].to_string();
+    match level {
+        0 => style(shade).dim().to_string(),
+        _ => style(shade).green().to_string(),
+    }
+}
+
+impl fmt::Display for Heatmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = self.busiest_day();
+        writeln!(f, "Focus by day (last {} weeks)", WEEKS)?;
+
+        // The month's name over the week it starts in
+        let mut months = " ".repeat(LABEL_WIDTH);
+        for week in 0..WEEKS {
+            let monday = self.first + Duration::weeks(week);
+            let column = LABEL_WIDTH + week as usize * 2;
+            if let Some(start) = (0..7)
+                .map(|d| monday + Duration::days(d))
+                .find(|day| day.day() == 1 && *day <= self.today)
+                && months.chars().count() <= column
+            {
+                months.push_str(&" ".repeat(column - months.chars().count()));
+                months.push_str(&start.format("%b").to_string());
+            }
+        }
+        writeln!(f, "{}", months.trim_end())?;
+
+        for weekday in 0..7 {
+            let name = match weekday {
+                0 => "Mon",
+                2 => "Wed",
+                4 => "Fri",
+                _ => "",
+            };
+            let mut row = format!("{:<width$}", name, width = LABEL_WIDTH);
+            for week in 0..WEEKS {
+                let day = self.first + Duration::weeks(week) + Duration::days(weekday);
+                if day > self.today {
+                    break;
+                }
+                let seconds = self.days.get(&day).copied().unwrap_or(0);
+                row.push_str(&cell(level(seconds, max)));
+                row.push(' ');
+            }
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        let legend: Vec<String> = (0..SHADES.len()).map(cell).collect();
+        write!(
+            f,
+            "{:<width$}less {} more",
+            "",
+            legend.join(" "),
+            width = LABEL_WIDTH
+        )?;
+        if max > 0 {
+            write!(f, "  (busiest day {})", format_duration(max))?;
+        }
+
+        let busiest_hour = self.hours.iter().copied().max().unwrap_or(0);
+        let Some(from) = self.hours.iter().position(|&seconds| seconds > 0) else {
+            return Ok(());
+        };
+        let to = self
+            .hours
+            .iter()
+            .rposition(|&seconds| seconds > 0)
+            .unwrap_or(from);
+        write!(f, "\n\nFocus by hour")?;
+        for hour in from..=to {
+            let seconds = self.hours[hour];
+            let bar = "█".repeat((seconds * BAR_WIDTH).div_ceil(busiest_hour) as usize);
+            write!(
+                f,
+                "\n  {:02}:00  {}{}  {:>6}",
+                hour,
+                style(&bar).green(),
+                " ".repeat(BAR_WIDTH as usize - bar.chars().count()),
+                format_duration(seconds)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{history::Outcome, session::PomodoroSession};
+    use chrono::{Local, TimeZone};
+
+    fn focus(day: u32, hour: u32, minute: u32, seconds: u64) -> PhaseRecord {
+        let mut session = PomodoroSession::new(seconds, 300, 1);
+        session.elapsed = seconds;
+        session.phase_started_at = Some(
+            Local
+                .with_ymd_and_hms(2024, 3, day, hour, minute, 0)
+                .unwrap(),
+        );
+        PhaseRecord::from_session(&session, Outcome::Completed)
+    }
+
+    #[test]
+    fn focus_is_spread_over_days_and_hours() {
+        let records = vec![
+            focus(4, 9, 50, 1500),
+            focus(4, 10, 30, 1500),
+            focus(6, 14, 0, 3000),
+        ];
+        // A Thursday
+        let today = NaiveDate::from_ymd_opt(2024, 3, 7).unwrap();
+        let heatmap = Heatmap::of(&records, today);
+        assert_eq!(heatmap.first.weekday(), chrono::Weekday::Mon);
+        assert_eq!(
+            heatmap.days[&NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()],
+            3000
+        );
+        // 10 minutes before 10:00 and 15 after, then another 25
+        assert_eq!(heatmap.hours[9], 600);
+        assert_eq!(heatmap.hours[10], 900 + 1500);
+        assert_eq!(heatmap.hours[14], 3000);
+        assert_eq!(heatmap.busiest_day(), 3000);
+
+        assert_eq!(level(0, 3000), 0);
+        assert_eq!(level(1, 3000), 1);
+        assert_eq!(level(1500, 3000), 2);
+        assert_eq!(level(3000, 3000), 4);
+
+        let text = console::strip_ansi_codes(&heatmap.to_string()).into_owned();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("        Oct") && lines[1].ends_with("Mar"));
+        assert!(lines[2].starts_with("Mon "));
+        assert!(lines[2].ends_with('█') && lines[4].ends_with('█'));
+        // This week is the last column and stops at today
+        assert!(lines[5].ends_with('·'));
+        assert_eq!(
+            lines[6][LABEL_WIDTH..]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .count(),
+            WEEKS as usize - 1
+        );
+        assert_eq!(
+            lines.last(),
+            Some(&format!("  14:00  {}     50m", "█".repeat(40)).as_str())
+        );
+        assert!(text.contains(&format!("\n  09:00  {} ", "█".repeat(8))));
+        assert!(
+            !Heatmap::of(&[], today)
+                .to_string()
+                .contains("Focus by hour")
+        );
+    }
+}
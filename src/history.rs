@@ -0,0 +1,462 @@
+// Session log: one JSON line per finished (or cut short) phase, appended to
+// history.jsonl in the user's data directory (or `data_dir` from the config
+// file). This is what `pomo stats` reads.
+//
+// With `[encryption]` in the config file each line is sealed before it is
+// written and opened again when the log is read (crypt.rs), so `stats`,
+// `export` and the rest see plain records either way; the plan and the inbox
+// kept next to the log are sealed the same way. The log and those files are
+// only readable by their owner on Unix. Appending and encrypting the log or
+// the inbox take turns (history.lock), so a line a session adds while
+// `pomo encrypt-log` rewrites the file isn't lost.
+
+use crate::{
+    battery::{self, PowerSource},
+    config::{self, expand_home},
+    crypt::{self, Keyring},
+    session::{Interruption, Phase, PomodoroSession},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Where the log is kept and how, from the config file
+struct Storage {
+    dir: PathBuf,
+    keyring: Option<Keyring>,
+    /// The salt the log's lines are sealed with, read from the log once
+    salt: OnceLock<crypt::Salt>,
+}
+
+// Read from the config file on first use. A config file that can't be read
+// stops the commands that write the log before they get here, so falling back
+// to the defaults only affects reading.
+fn storage() -> &'static Storage {
+    static STORAGE: OnceLock<Storage> = OnceLock::new();
+    STORAGE.get_or_init(|| {
+        let config = config::load().unwrap_or_default();
+        Storage {
+            dir: config
+                .data_dir
+                .as_deref()
+                .map(expand_home)
+                .unwrap_or_else(default_dir),
+            keyring: config
+                .encryption
+                .enabled()
+                .then(|| Keyring::new(config.encryption)),
+            salt: OnceLock::new(),
+        }
+    })
+}
+
+fn default_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rust-pomo")
+}
+
+/// How a phase ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// Ran for its full length
+    Completed,
+    /// Cut short with `skip`
+    Skipped,
+    /// The session was stopped during this phase
+    Stopped,
+    /// Paused for longer than `--max-pause` allows, or idle with
+    /// `--on-idle abandon`
+    Abandoned,
+    /// Paused or skipped under `--strict`, so it doesn't count
+    Void,
+}
+
+/// One line of the session log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseRecord {
+    pub phase: Phase,
+    pub cycle: u32,
+    pub started_at: DateTime<Local>,
+    pub ended_at: DateTime<Local>,
+    /// Planned length in seconds
+    pub planned: u64,
+    /// Seconds added to the planned length with `pomo extend` (negative when
+    /// the phase was shortened)
+    #[serde(default)]
+    pub adjusted: i64,
+    /// Time actually spent in the phase in seconds, not counting pauses
+    pub actual: u64,
+    /// Time spent paused in seconds
+    #[serde(default)]
+    pub paused: u64,
+    /// How many times the phase was paused
+    #[serde(default)]
+    pub pauses: u32,
+    /// Each pause, with its reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interruptions: Vec<Interruption>,
+    /// Time counted past the planned end with `--overtime`, in seconds
+    #[serde(default)]
+    pub overtime: u64,
+    /// Time the user was idle (`--idle-after`), in seconds; not counted in
+    /// `actual`
+    #[serde(default)]
+    pub idle: u64,
+    pub outcome: Outcome,
+    /// Entry of the `--schedule` the phase came from (1-based)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule_entry: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Pomodoros the task was estimated at in the planning queue (`--plan`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate: Option<u32>,
+    /// Git repository the session was started in (`--git`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    /// Its branch at the time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// AC or battery power when the phase ended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<PowerSource>,
+}
+
+impl PhaseRecord {
+    /// Record for the session's current phase, ending now
+    pub fn from_session(session: &PomodoroSession, outcome: Outcome) -> Self {
+        let ended_at = Local::now();
+        PhaseRecord {
+            phase: session.phase,
+            cycle: session.cycle,
+            started_at: session.phase_started_at.unwrap_or(ended_at),
+            ended_at,
+            planned: session.planned_len(),
+            adjusted: session.adjusted,
+            actual: session.elapsed,
+            paused: session.paused,
+            pauses: session.pauses,
+            interruptions: session.interruptions.clone(),
+            overtime: session.overtime_elapsed,
+            idle: session.idle_elapsed,
+            outcome,
+            schedule_entry: session.schedule_entry(),
+            task: session.task.clone(),
+            tags: session.tags.clone(),
+            estimate: session.planned.map(|planned| planned.estimate),
+            repo: session.git.as_ref().map(|git| git.repo.clone()),
+            branch: session.git.as_ref().and_then(|git| git.branch.clone()),
+            power: battery::power_source(),
+        }
+    }
+}
+
+// Location of the session log
+pub fn history_path() -> PathBuf {
+    storage().path()
+}
+
+// Add a record to the end of the log
+pub fn append(record: &PhaseRecord) -> io::Result<()> {
+    storage().append(record)
+}
+
+// Read every record; a missing log is simply empty
+pub fn load() -> io::Result<Vec<PhaseRecord>> {
+    storage().load()
+}
+
+// Seal the lines written before encryption was turned on; returns how many
+// there were
+pub fn encrypt_all() -> io::Result<usize> {
+    storage().encrypt_all()
+}
+
+// Make sure the log can be written and read with the configured secret
+// before a session starts, rather than find out when the first phase ends
+pub fn unlock() -> io::Result<()> {
+    let storage = storage();
+    let Some(keyring) = &storage.keyring else {
+        return Ok(());
+    };
+    let lines = storage.read_lines()?;
+    match lines.iter().find(|line| crypt::is_sealed(line)) {
+        Some(line) => keyring.open(line).map(drop),
+        None => keyring.unlock(&storage.salt()?),
+    }
+}
+
+// Take turns with other writers of the log and the inbox, until the returned
+// file is dropped
+pub fn lock() -> io::Result<File> {
+    let storage = storage();
+    fs::create_dir_all(&storage.dir)?;
+    storage.lock()
+}
+
+// Seal other files kept next to the log (plan, inbox), when the log is sealed
+pub fn seal(text: &str) -> io::Result<String> {
+    let storage = storage();
+    match &storage.keyring {
+        Some(keyring) => keyring.seal(text, &storage.salt()?),
+        None => Ok(text.to_string()),
+    }
+}
+
+// Open what `seal` sealed; anything else is returned as is
+pub fn open(text: &str) -> io::Result<String> {
+    match &storage().keyring {
+        _ if !crypt::is_sealed(text) => Ok(text.to_string()),
+        Some(keyring) => keyring.open(text),
+        None => Err(io::Error::other(
+            "the file is encrypted; set up [encryption] in the config file",
+        )),
+    }
+}
+
+impl Storage {
+    fn path(&self) -> PathBuf {
+        self.dir.join("history.jsonl")
+    }
+
+    fn append(&self, record: &PhaseRecord) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let _lock = self.lock()?;
+        let mut line = serde_json::to_string(record)?;
+        if let Some(keyring) = &self.keyring {
+            line = keyring.seal(&line, &self.salt()?)?;
+        }
+        let mut file = open_private(OpenOptions::new().create(true).append(true), &self.path())?;
+        writeln!(file, "{}", line)
+    }
+
+    fn load(&self) -> io::Result<Vec<PhaseRecord>> {
+        let mut records = Vec::new();
+        let mut unsealed = 0;
+        let mut failed = Vec::new();
+        for (n, line) in self.read_lines()?.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line = if crypt::is_sealed(line) {
+                let Some(keyring) = &self.keyring else {
+                    return Err(io::Error::other(
+                        "the session log is encrypted; set up [encryption] in the config file",
+                    ));
+                };
+                match keyring.open(line) {
+                    Ok(line) => {
+                        unsealed += 1;
+                        line
+                    }
+                    Err(e) => {
+                        failed.push((n + 1, e));
+                        continue;
+                    }
+                }
+            } else {
+                line.clone()
+            };
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                // One bad line (e.g. from a crash mid-write) shouldn't hide the rest
+                Err(e) => log::warn!("Skipping line {} of the session log: {}", n + 1, e),
+            }
+        }
+        // Not a bad line or two, but the wrong secret
+        if unsealed == 0 && !failed.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "can't decrypt the session log: wrong passphrase or key file?",
+            ));
+        }
+        for (n, e) in failed {
+            log::warn!("Skipping line {} of the session log: {}", n, e);
+        }
+        Ok(records)
+    }
+
+    fn encrypt_all(&self) -> io::Result<usize> {
+        let Some(keyring) = &self.keyring else {
+            return Err(io::Error::other(
+                "no [encryption] in the config file to encrypt the session log with",
+            ));
+        };
+        fs::create_dir_all(&self.dir)?;
+        // No session appends while the log is read and replaced
+        let _lock = self.lock()?;
+        let lines = self.read_lines()?;
+        let salt = self.salt()?;
+        let mut sealed = 0;
+        let mut text = String::new();
+        for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+            if crypt::is_sealed(line) {
+                text.push_str(line);
+            } else {
+                text.push_str(&keyring.seal(line, &salt)?);
+                sealed += 1;
+            }
+            text.push('\n');
+        }
+        if sealed > 0 {
+            // Replace the log in one step, so a failure leaves the old one
+            let path = self.path();
+            let new = path.with_extension("jsonl.new");
+            open_private(
+                OpenOptions::new().create(true).write(true).truncate(true),
+                &new,
+            )?
+            .write_all(text.as_bytes())?;
+            fs::rename(new, path)?;
+        }
+        Ok(sealed)
+    }
+
+    // The salt the log's lines are sealed with, or the one picked for the
+    // first
+    fn salt(&self) -> io::Result<crypt::Salt> {
+        if let Some(salt) = self.salt.get() {
+            return Ok(*salt);
+        }
+        let salt = self
+            .read_lines()?
+            .iter()
+            .find_map(|line| crypt::salt_of(line))
+            .unwrap_or_else(Keyring::new_salt);
+        Ok(*self.salt.get_or_init(|| salt))
+    }
+
+    fn read_lines(&self) -> io::Result<Vec<String>> {
+        match File::open(self.path()) {
+            Ok(file) => BufReader::new(file).lines().collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Held while the log is written, until the returned file is dropped
+    fn lock(&self) -> io::Result<File> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join("history.lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+}
+
+// Open the log or a file next to it for writing, readable by its owner only
+// (also when it was created readable by others)
+pub fn open_private(options: &mut OpenOptions, path: &Path) -> io::Result<File> {
+    let file = private(options).open(path)?;
+    restrict(&file)?;
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn restrict(file: &File) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = file.metadata()?.permissions();
+    if permissions.mode() & 0o077 != 0 {
+        permissions.set_mode(permissions.mode() & 0o700);
+        file.set_permissions(permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restrict(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+// Create the log readable by its owner only
+#[cfg(unix)]
+fn private(options: &mut OpenOptions) -> &mut OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600)
+}
+
+#[cfg(not(unix))]
+fn private(options: &mut OpenOptions) -> &mut OpenOptions {
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypt::EncryptionSettings;
+
+    // A log in a scratch directory, sealed with this secret if given
+    fn storage(dir: &Path, secret: Option<&str>) -> Storage {
+        let keyring = secret.map(|secret| {
+            let key_file = dir.join(format!("{}.key", secret));
+            fs::write(&key_file, secret).unwrap();
+            Keyring::new(EncryptionSettings {
+                key_file: Some(key_file),
+                ..Default::default()
+            })
+        });
+        Storage {
+            dir: dir.to_path_buf(),
+            keyring,
+            salt: OnceLock::new(),
+        }
+    }
+
+    fn record(task: &str) -> PhaseRecord {
+        let mut session = PomodoroSession::new(1500, 300, 1);
+        session.task = Some(task.to_string());
+        PhaseRecord::from_session(&session, Outcome::Completed)
+    }
+
+    #[test]
+    fn sealed_log_opens_with_the_right_secret_only() {
+        let dir = std::env::temp_dir().join(format!("pomo-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // Written before encryption was turned on
+        storage(&dir, None).append(&record("plain")).unwrap();
+        let sealed = storage(&dir, Some("correct horse"));
+        assert_eq!(sealed.encrypt_all().unwrap(), 1);
+        assert_eq!(sealed.encrypt_all().unwrap(), 0);
+        sealed.append(&record("secret project")).unwrap();
+
+        let text = fs::read_to_string(sealed.path()).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.lines().all(crypt::is_sealed));
+        assert!(!text.contains("secret project"));
+        let tasks: Vec<String> = sealed
+            .load()
+            .unwrap()
+            .into_iter()
+            .filter_map(|record| record.task)
+            .collect();
+        assert_eq!(tasks, ["plain", "secret project"]);
+
+        assert!(storage(&dir, None).load().is_err());
+        let wrong = storage(&dir, Some("wrong")).load().unwrap_err();
+        assert_eq!(wrong.kind(), io::ErrorKind::InvalidData);
+
+        // Made readable by others since: owner-only again on the next write
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(sealed.path(), fs::Permissions::from_mode(0o644)).unwrap();
+            sealed.append(&record("again")).unwrap();
+            let mode = fs::metadata(sealed.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
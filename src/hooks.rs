@@ -0,0 +1,310 @@
+// User hook commands run at phase transitions, so the timer can drive things
+// we don't integrate with directly (chat status, smart lights, music, ...).
+// They are set in the `[hooks]` table of the config file:
+//
+//   [hooks]
+//   on_focus_start = "osascript -e 'tell application \"Music\" to play'"
+//   on_break_start = "~/bin/lights warm"
+//   on_complete = "say 'All done'"
+//   timeout = "30s"          (or --hook-timeout: kill hooks still running after this)
+//
+// Each command runs through `sh -c` (`cmd /C` on Windows) in the background with
+// these variables set:
+//   POMO_EVENT      … focus_start, focus_end, break_start, break_end, pause, resume or complete
+//   POMO_PHASE      … focus or break
+//   POMO_CYCLE, POMO_TOTAL_CYCLES (also POMO_CYCLES)
+//   POMO_ELAPSED, POMO_REMAINING, POMO_DURATION … seconds in the current phase
+//   POMO_PLANNED_SECS   … length of the phase as planned (before `pomo extend`)
+//   POMO_ACTUAL_SECS    … seconds spent in the phase so far: overtime counts, pauses don't
+//   POMO_INTERRUPTIONS  … times the phase has been paused
+//   POMO_TASK, POMO_TAGS (comma-separated)
+//
+// A hook's output (stdout and stderr) is logged with `-v`; when it fails or
+// times out, the failure is logged along with the last lines of its output.
+
+use crate::{
+    dry_run,
+    duration::{deserialize_optional_duration, format_duration},
+    session::{Phase, PomodoroSession},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often a running hook is checked on
+const POLL: Duration = Duration::from_millis(100);
+
+/// How long output is still read once a hook has exited (processes it
+/// started may hold on to its stdout)
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+
+/// Lines of output logged with a failed hook
+const FAILURE_LINES: usize = 20;
+
+/// Shell commands to run, by event
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    pub on_focus_start: Option<String>,
+    pub on_focus_end: Option<String>,
+    pub on_break_start: Option<String>,
+    pub on_break_end: Option<String>,
+    pub on_pause: Option<String>,
+    pub on_resume: Option<String>,
+    /// All cycles finished (not run when the session is stopped)
+    pub on_complete: Option<String>,
+    /// Seconds a hook may run before it is killed (no limit if unset)
+    #[serde(
+        deserialize_with = "deserialize_optional_duration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<u64>,
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    PhaseStart,
+    PhaseEnd,
+    Pause,
+    Resume,
+    Complete,
+}
+
+impl Hooks {
+    // Start the hook for this event in the background, if one is set
+    pub fn run(&self, event: HookEvent, session: &PomodoroSession) -> io::Result<()> {
+        let (name, command) = self.command(event, session.phase);
+        let Some(command) = command else {
+            return Ok(());
+        };
+        if dry_run::is_enabled() {
+            dry_run::action(format!("run hook on_{}: {}", name, command));
+            return Ok(());
+        }
+
+        log::debug!("hook on_{}: {}", name, command);
+        let mut command = shell(command);
+        command
+            .envs(env(name, session))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // In a group of its own, so a timeout kills whatever the hook started too
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let child = command.spawn()?;
+        let timeout = self.timeout.map(Duration::from_secs);
+        thread::spawn(move || reap(name, child, timeout));
+        Ok(())
+    }
+
+    // The POMO_EVENT name and configured command for an event in a phase
+    fn command(&self, event: HookEvent, phase: Phase) -> (&'static str, Option<&String>) {
+        match (event, phase) {
+            (HookEvent::PhaseStart, Phase::Focus) => ("focus_start", self.on_focus_start.as_ref()),
+            (HookEvent::PhaseEnd, Phase::Focus) => ("focus_end", self.on_focus_end.as_ref()),
+            (HookEvent::PhaseStart, Phase::Break) => ("break_start", self.on_break_start.as_ref()),
+            (HookEvent::PhaseEnd, Phase::Break) => ("break_end", self.on_break_end.as_ref()),
+            (HookEvent::Pause, _) => ("pause", self.on_pause.as_ref()),
+            (HookEvent::Resume, _) => ("resume", self.on_resume.as_ref()),
+            (HookEvent::Complete, _) => ("complete", self.on_complete.as_ref()),
+        }
+    }
+}
+
+// Wait for a hook to finish (or kill it once it has run for `timeout`),
+// keeping its output out of the progress bar and in the log
+fn reap(name: &str, mut child: Child, timeout: Option<Duration>) {
+    let (sender, lines) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, sender);
+    }
+
+    let started = Instant::now();
+    let mut output = Vec::new();
+    let failure = loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break None,
+            Ok(Some(status)) => break Some(status.to_string()),
+            Ok(None) => {}
+            Err(e) => break Some(e.to_string()),
+        }
+        if let Some(timeout) = timeout
+            && started.elapsed() >= timeout
+        {
+            kill(&mut child);
+            break Some(format!(
+                "timed out after {}",
+                format_duration(timeout.as_secs())
+            ));
+        }
+        if let Ok(line) = lines.recv_timeout(POLL) {
+            output.push(line);
+        }
+    };
+    let deadline = Instant::now() + OUTPUT_GRACE;
+    while let Ok(line) = lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        output.push(line);
+    }
+
+    for line in &output {
+        log::debug!("hook on_{}: {}", name, line);
+    }
+    if let Some(reason) = failure {
+        log::warn!("Hook on_{} failed ({})", name, reason);
+        if !log::log_enabled!(log::Level::Debug) {
+            for line in last_lines(&output) {
+                log::warn!("  {}", line);
+            }
+        }
+    }
+}
+
+// Pass the lines of a hook's stdout or stderr on to `reap`
+fn forward(pipe: impl Read + Send + 'static, lines: Sender<String>) {
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// The end of a hook's output, for the failure message
+fn last_lines(output: &[String]) -> &[String] {
+    &output[output.len().saturating_sub(FAILURE_LINES)..]
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    use nix::{
+        sys::signal::{Signal, killpg},
+        unistd::Pid,
+    };
+    if killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL).is_err() {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+}
+
+#[cfg(windows)]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// The command line run through the system shell
+#[cfg(not(windows))]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    let mut shell = Command::new("cmd");
+    // Passed as is: cmd has its own quoting rules
+    shell.arg("/C").raw_arg(command);
+    shell
+}
+
+// Environment variables describing the session to a hook
+fn env(event: &str, session: &PomodoroSession) -> Vec<(&'static str, String)> {
+    let phase = match session.phase {
+        Phase::Focus => "focus",
+        Phase::Break => "break",
+    };
+    vec![
+        ("POMO_EVENT", event.to_string()),
+        ("POMO_PHASE", phase.to_string()),
+        ("POMO_CYCLE", session.cycle.to_string()),
+        ("POMO_TOTAL_CYCLES", session.cycles.to_string()),
+        ("POMO_CYCLES", session.cycles.to_string()),
+        ("POMO_ELAPSED", session.elapsed.to_string()),
+        ("POMO_REMAINING", session.remaining().to_string()),
+        ("POMO_DURATION", session.phase_len().to_string()),
+        ("POMO_PLANNED_SECS", session.planned_len().to_string()),
+        ("POMO_ACTUAL_SECS", session.actual_len().to_string()),
+        ("POMO_INTERRUPTIONS", session.pauses.to_string()),
+        ("POMO_TASK", session.task.clone().unwrap_or_default()),
+        ("POMO_TAGS", session.tags.join(",")),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_command_for_the_phase() {
+        let hooks = Hooks {
+            on_focus_start: Some("focus".to_string()),
+            on_break_end: Some("break".to_string()),
+            ..Hooks::default()
+        };
+        assert_eq!(
+            hooks.command(HookEvent::PhaseStart, Phase::Focus),
+            ("focus_start", Some(&"focus".to_string()))
+        );
+        assert_eq!(
+            hooks.command(HookEvent::PhaseStart, Phase::Break),
+            ("break_start", None)
+        );
+        assert_eq!(
+            hooks.command(HookEvent::PhaseEnd, Phase::Break),
+            ("break_end", Some(&"break".to_string()))
+        );
+    }
+
+    #[test]
+    fn environment_describes_the_session() {
+        let mut session = PomodoroSession::new(1500, 300, 4);
+        session.elapsed = 100;
+        session.pauses = 2;
+        session.extend(60);
+        session.task = Some("report".to_string());
+        session.tags = vec!["work".to_string(), "writing".to_string()];
+
+        let env = env("focus_start", &session);
+        let get = |key: &str| env.iter().find(|(k, _)| *k == key).unwrap().1.clone();
+        assert_eq!(get("POMO_EVENT"), "focus_start");
+        assert_eq!(get("POMO_PHASE"), "focus");
+        assert_eq!(get("POMO_CYCLE"), "1");
+        assert_eq!(get("POMO_TOTAL_CYCLES"), "4");
+        assert_eq!(get("POMO_CYCLES"), "4");
+        assert_eq!(get("POMO_REMAINING"), "1460");
+        assert_eq!(get("POMO_DURATION"), "1560");
+        assert_eq!(get("POMO_PLANNED_SECS"), "1500");
+        assert_eq!(get("POMO_ACTUAL_SECS"), "100");
+        assert_eq!(get("POMO_INTERRUPTIONS"), "2");
+        assert_eq!(get("POMO_TASK"), "report");
+        assert_eq!(get("POMO_TAGS"), "work,writing");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hooks_are_killed_after_the_timeout() {
+        use std::os::unix::process::CommandExt;
+        let child = shell("echo started; sleep 10")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let started = Instant::now();
+        reap("pause", child, Some(Duration::from_millis(200)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
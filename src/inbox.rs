@@ -0,0 +1,151 @@
+// Interruption inbox: the thoughts that come up during focus ("call the
+// bank"), written down without breaking off and looked at in the break (the
+// "inform & note" step of the Pomodoro technique).
+//
+//   i call the bank           … typed at the terminal (ENTER), or `i` then the
+//                               note in the --tui dashboard
+//   pomo note call the bank   … from anywhere, e.g. a keybinding
+//   pomo inbox                … everything noted so far
+//   pomo inbox --clear
+//
+// The timer keeps running. Notes are appended to inbox.jsonl next to the
+// session log, with the time and the task in focus, and encrypted like it with
+// `[encryption]` in the config file. The ones taken since the last break are
+// shown when the next break starts.
+
+use crate::{crypt, dry_run, history};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// One note
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub at: DateTime<Local>,
+    pub text: String,
+    /// The task in focus when it was noted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+}
+
+impl Note {
+    pub fn new(text: &str, task: Option<String>) -> Self {
+        Note {
+            at: Local::now(),
+            text: text.trim().to_string(),
+            task,
+        }
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}", self.at.format("%Y-%m-%d %H:%M"), self.text)?;
+        if let Some(task) = &self.task {
+            write!(f, "  ({})", task)?;
+        }
+        Ok(())
+    }
+}
+
+// Location of the inbox
+pub fn inbox_path() -> PathBuf {
+    history::history_path().with_file_name("inbox.jsonl")
+}
+
+// Add a note to the end of the inbox (a dry run only says so)
+pub fn add(note: &Note) -> io::Result<()> {
+    if dry_run::is_enabled() {
+        dry_run::action(format!("note \"{}\" in the inbox", note.text));
+        return Ok(());
+    }
+    let path = inbox_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = history::seal(&serde_json::to_string(note)?)?;
+    let _lock = history::lock()?;
+    let mut file = history::open_private(OpenOptions::new().create(true).append(true), &path)?;
+    writeln!(file, "{}", line)
+}
+
+// Seal the notes taken before encryption was turned on (`pomo encrypt-log`);
+// returns how many there were
+pub fn encrypt() -> io::Result<usize> {
+    let path = inbox_path();
+    // No note is added while the inbox is read and replaced
+    let _lock = history::lock()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut sealed = 0;
+    let mut new_text = String::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        if crypt::is_sealed(line) {
+            new_text.push_str(line);
+        } else {
+            new_text.push_str(&history::seal(line)?);
+            sealed += 1;
+        }
+        new_text.push('\n');
+    }
+    if sealed > 0 {
+        let new = path.with_extension("jsonl.new");
+        history::open_private(
+            OpenOptions::new().create(true).write(true).truncate(true),
+            &new,
+        )?
+        .write_all(new_text.as_bytes())?;
+        fs::rename(new, path)?;
+    }
+    Ok(sealed)
+}
+
+// Every note, oldest first; a missing inbox is an empty one
+pub fn load() -> io::Result<Vec<Note>> {
+    let text = match fs::read_to_string(inbox_path()) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(&history::open(line)?).map_err(io::Error::other))
+        .collect()
+}
+
+// Empty the inbox
+pub fn clear() -> io::Result<()> {
+    match fs::remove_file(inbox_path()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn notes_show_time_text_and_task() {
+        let at = Local.with_ymd_and_hms(2026, 10, 15, 14, 3, 0).unwrap();
+        let note = Note {
+            at,
+            ..Note::new("  call the bank ", Some("write report".to_string()))
+        };
+        assert_eq!(
+            note.to_string(),
+            "2026-10-15 14:03  call the bank  (write report)"
+        );
+        let line = serde_json::to_string(&note).unwrap();
+        assert_eq!(serde_json::from_str::<Note>(&line).unwrap(), note);
+    }
+}
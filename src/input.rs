@@ -0,0 +1,155 @@
+// Lines typed at the terminal while a session runs (the --tui dashboard reads
+// single keys instead, see tui.rs). One command per line:
+//
+//   p [reason]   pause (saying why), or resume when paused
+//   i [note]     note for the inbox; the timer keeps running
+//   q            stop the session
+//   done         the planned task is finished (--plan)
+//   z [10m]      snooze the phase waiting to start
+//   + / -        lengthen / shorten the running phase by 5 minutes (+10m, -2m)
+//   ENTER        start the phase waiting to start
+//
+// A bare `p` asks why and a bare `i` asks for the note. The line after that is
+// the answer even when it looks like a command: a note "p later" doesn't pause
+// the session, and ENTER with no reason doesn't start the waiting phase.
+
+use crate::{
+    duration::{parse_duration, parse_signed_duration},
+    runner,
+};
+
+/// A question asked by the previous line, which the next line answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// Why the session was just paused
+    Reason,
+    /// The note to take
+    Note,
+}
+
+/// What a line typed at the terminal asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The reason for the pause just made
+    Reason(String),
+    /// A note for the inbox
+    Note(String),
+    /// Ask for the note on the next line
+    AskNote,
+    /// Pause (with the reason, if given), or resume when paused
+    TogglePause(Option<String>),
+    Stop,
+    /// The task of a --plan session is finished
+    Done,
+    /// Snooze the phase waiting to start by this many seconds
+    Snooze(u64),
+    /// Start the phase waiting to start (also ends overtime)
+    StartNext,
+    /// Lengthen (or shorten) the running phase by this many seconds
+    Extend(i64),
+    /// Nothing to do
+    Ignore,
+}
+
+// What the line means, given the question it answers (if any) and whether
+// the next phase is waiting to start
+pub fn action(line: &str, prompt: Option<Prompt>, waiting: bool) -> Action {
+    let line = line.trim();
+    match prompt {
+        // An empty answer: no reason given, nothing noted
+        Some(_) if line.is_empty() => return Action::Ignore,
+        Some(Prompt::Reason) => return Action::Reason(line.to_string()),
+        Some(Prompt::Note) => return Action::Note(line.to_string()),
+        None => {}
+    }
+    if let Some(note) = argument(line, 'i') {
+        return match note {
+            "" => Action::AskNote,
+            note => Action::Note(note.to_string()),
+        };
+    }
+    if let Some(reason) = argument(line, 'p') {
+        return Action::TogglePause(Some(reason.to_string()).filter(|reason| !reason.is_empty()));
+    }
+    match line {
+        "q" => Action::Stop,
+        "done" => Action::Done,
+        _ => match snooze(line) {
+            Some(by) => Action::Snooze(by),
+            None if waiting => Action::StartNext,
+            None => extension(line).map_or(Action::Ignore, Action::Extend),
+        },
+    }
+}
+
+// The rest of a line that is the one-letter command, e.g. `p phone call`
+fn argument(line: &str, command: char) -> Option<&str> {
+    line.strip_prefix(command)
+        .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+        .map(str::trim)
+}
+
+// `+` or `-` typed at the terminal (5 minutes either way), or `+10m`, `-2m`
+fn extension(input: &str) -> Option<i64> {
+    match input {
+        "+" => Some(runner::EXTEND_STEP),
+        "-" => Some(-runner::EXTEND_STEP),
+        _ if input.starts_with(['+', '-']) => parse_signed_duration(input).ok(),
+        _ => None,
+    }
+}
+
+// `z` snoozes the phase waiting to start by the usual step, `z 10m` by
+// that much
+fn snooze(input: &str) -> Option<u64> {
+    match input.strip_prefix('z')?.trim() {
+        "" => Some(runner::SNOOZE_STEP),
+        by => parse_duration(by).ok().filter(|&seconds| seconds > 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_read_from_lines() {
+        assert_eq!(action("p", None, false), Action::TogglePause(None));
+        assert_eq!(
+            action("p phone call", None, false),
+            Action::TogglePause(Some("phone call".to_string()))
+        );
+        assert_eq!(action("i", None, false), Action::AskNote);
+        assert_eq!(
+            action("i call the bank", None, false),
+            Action::Note("call the bank".to_string())
+        );
+        assert_eq!(action(" q ", None, false), Action::Stop);
+        assert_eq!(action("done", None, true), Action::Done);
+        assert_eq!(action("+10m", None, false), Action::Extend(600));
+        assert_eq!(action("-", None, false), Action::Extend(-300));
+        assert_eq!(action("z 10m", None, true), Action::Snooze(600));
+        assert_eq!(action("", None, true), Action::StartNext);
+        assert_eq!(action("", None, false), Action::Ignore);
+        assert_eq!(action("pizza", None, false), Action::Ignore);
+    }
+
+    #[test]
+    fn answers_come_before_commands() {
+        assert_eq!(
+            action("q", Some(Prompt::Reason), false),
+            Action::Reason("q".to_string())
+        );
+        assert_eq!(
+            action("p later", Some(Prompt::Note), false),
+            Action::Note("p later".to_string())
+        );
+        assert_eq!(
+            action("i forgot", Some(Prompt::Reason), true),
+            Action::Reason("i forgot".to_string())
+        );
+        // Just ENTER: no answer, and the waiting phase doesn't start
+        assert_eq!(action("", Some(Prompt::Reason), true), Action::Ignore);
+        assert_eq!(action(" ", Some(Prompt::Note), false), Action::Ignore);
+    }
+}
@@ -0,0 +1,234 @@
+// Media player control at phase transitions:
+//
+//   --media pause-on-focus   pause what's playing when focus starts, resume it on break
+//   --media play-on-focus    play (a focus playlist, say) during focus, pause on break
+//   --media off              leave the player alone (the default)
+//
+// or `media = "pause-on-focus"` in the config file.
+//
+//   macOS … Music and Spotify, via AppleScript
+//   Linux … any MPRIS player, via `playerctl`
+//
+// With pause-on-focus only a player that was actually playing is resumed, so
+// a break never starts music that was off.
+
+use super::Integration;
+use crate::session::{Phase, PomodoroSession};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// What happens to the media player
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaMode {
+    #[default]
+    Off,
+    /// Pause during focus, resume on break
+    PauseOnFocus,
+    /// Play during focus, pause on break
+    PlayOnFocus,
+}
+
+pub struct MediaControl {
+    mode: MediaMode,
+    /// Playback was paused here, so it is resumed later
+    paused_here: AtomicBool,
+    /// The last phase started was focus
+    in_focus: AtomicBool,
+}
+
+impl MediaControl {
+    pub fn new(mode: MediaMode) -> Self {
+        MediaControl {
+            mode,
+            paused_here: AtomicBool::new(false),
+            in_focus: AtomicBool::new(false),
+        }
+    }
+
+    // Focus is over (break or end of session)
+    fn focus_over(&self) -> io::Result<()> {
+        if !self.in_focus.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        match self.mode {
+            MediaMode::PauseOnFocus if self.paused_here.swap(false, Ordering::SeqCst) => {
+                log::debug!("resuming media playback");
+                control(Action::Play)
+            }
+            MediaMode::PlayOnFocus => {
+                log::debug!("pausing media playback");
+                control(Action::Pause)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Integration for MediaControl {
+    fn name(&self) -> &'static str {
+        "media"
+    }
+
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()> {
+        match session.phase {
+            Phase::Focus => {
+                if self.in_focus.swap(true, Ordering::SeqCst) {
+                    return Ok(());
+                }
+                match self.mode {
+                    // Assume nothing is playing if that can't be told
+                    MediaMode::PauseOnFocus if is_playing().unwrap_or(false) => {
+                        log::debug!("pausing media playback");
+                        control(Action::Pause)?;
+                        self.paused_here.store(true, Ordering::SeqCst);
+                        Ok(())
+                    }
+                    MediaMode::PlayOnFocus => {
+                        log::debug!("starting media playback");
+                        control(Action::Play)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Phase::Break => self.focus_over(),
+        }
+    }
+
+    fn session_end(&self) -> io::Result<()> {
+        self.focus_over()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Play,
+    Pause,
+}
+
+// Players are asked in turn; the first one running is the one used
+#[cfg(target_os = "macos")]
+const PLAYERS: &[&str] = &["Spotify", "Music"];
+
+#[cfg(target_os = "macos")]
+fn control(action: Action) -> io::Result<()> {
+    let verb = match action {
+        Action::Play => "play",
+        Action::Pause => "pause",
+    };
+    for player in PLAYERS {
+        if osascript(&format!(
+            "if application \"{0}\" is running then\n\
+             tell application \"{0}\" to {1}\n\
+             return \"yes\"\n\
+             end if",
+            player, verb
+        ))? {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn is_playing() -> Option<bool> {
+    let mut running = false;
+    for player in PLAYERS {
+        let output = Command::new("osascript")
+            .args([
+                "-e",
+                &format!(
+                    "if application \"{0}\" is running then \
+                     tell application \"{0}\" to return player state as text",
+                    player
+                ),
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "playing" => return Some(true),
+            "" => {}
+            _ => running = true,
+        }
+    }
+    running.then_some(false)
+}
+
+// Run AppleScript; true if it returned "yes"
+#[cfg(target_os = "macos")]
+fn osascript(script: &str) -> io::Result<bool> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .stderr(Stdio::null())
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "yes")
+}
+
+#[cfg(not(target_os = "macos"))]
+fn control(action: Action) -> io::Result<()> {
+    let verb = match action {
+        Action::Play => "play",
+        Action::Pause => "pause",
+    };
+    // playerctl fails when no player is running; there's nothing to control then
+    Command::new("playerctl")
+        .arg(verb)
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                "--media needs `playerctl` to control MPRIS players",
+            ),
+            _ => e,
+        })?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn is_playing() -> Option<bool> {
+    let output = Command::new("playerctl")
+        .arg("status")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_playerctl_status(&String::from_utf8_lossy(&output.stdout))
+}
+
+// `playerctl status`: Playing, Paused or Stopped (nothing without a player)
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+fn parse_playerctl_status(text: &str) -> Option<bool> {
+    match text.trim() {
+        "Playing" => Some(true),
+        "Paused" | "Stopped" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_playerctl_status() {
+        assert_eq!(parse_playerctl_status("Playing\n"), Some(true));
+        assert_eq!(parse_playerctl_status("Paused\n"), Some(false));
+        assert_eq!(parse_playerctl_status(""), None);
+    }
+
+    #[test]
+    fn modes_are_kebab_case() {
+        let mode: MediaMode = serde_json::from_str("\"pause-on-focus\"").unwrap();
+        assert_eq!(mode, MediaMode::PauseOnFocus);
+        assert_eq!(
+            MediaMode::from_str("play-on-focus", false),
+            Ok(MediaMode::PlayOnFocus)
+        );
+    }
+}
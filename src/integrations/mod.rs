@@ -0,0 +1,120 @@
+// Integrations with outside services that should follow the session, such as
+// setting a chat status during focus, running macOS Shortcuts, pausing the
+// media player, tracking focus time on Toggl or Clockify or calling a webhook. Unlike hooks
+// (hooks.rs) these are built in, configured in the config file (shortcuts
+// also by flag), and handle credentials and retries themselves.
+//
+// Integrations are best effort: a failure is reported but never stops the timer.
+// In a dry run they are only named, not called.
+
+mod media;
+mod shortcuts;
+mod slack;
+mod time_tracking;
+mod webhook;
+
+use crate::{config::Config, dry_run, history::Outcome, session::PomodoroSession};
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub use media::{MediaControl, MediaMode};
+pub use shortcuts::{ShortcutSettings, Shortcuts};
+pub use slack::{SlackSettings, SlackStatus};
+pub use time_tracking::{TimeTracking, TimeTrackingSettings, TrackingService};
+pub use webhook::{Webhook, WebhookSettings};
+
+/// A service told about phase transitions
+pub trait Integration: Send + Sync {
+    /// Short name used in messages
+    fn name(&self) -> &'static str;
+
+    /// A phase starts or is resumed. Called before blockers are engaged for
+    /// focus and after they are released for a break, so the network is up.
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()>;
+
+    /// A phase is over, however it ended (not when the session is interrupted)
+    fn phase_end(&self, _session: &PomodoroSession, _outcome: Outcome) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The session is over (finished, stopped or interrupted)
+    fn session_end(&self) -> io::Result<()>;
+}
+
+/// The integrations enabled for a session
+#[derive(Default)]
+pub struct Integrations {
+    integrations: Vec<Box<dyn Integration>>,
+    /// `session_end` has run since the last phase started
+    ended: AtomicBool,
+}
+
+impl Integrations {
+    // Set up what the config file and the session ask for
+    pub fn new(config: &Config, session: &PomodoroSession) -> io::Result<Self> {
+        let mut integrations: Vec<Box<dyn Integration>> = Vec::new();
+        if let Some(slack) = &config.slack {
+            integrations.push(Box::new(SlackStatus::new(slack)?));
+        }
+        if let Some(tracking) = &config.time_tracking {
+            integrations.push(Box::new(TimeTracking::new(tracking)?));
+        }
+        if !session.shortcuts.is_empty() {
+            integrations.push(Box::new(Shortcuts::new(&session.shortcuts)));
+        }
+        if let Some(url) = &session.webhook_url {
+            integrations.push(Box::new(Webhook::new(url)));
+        }
+        if session.media != MediaMode::Off {
+            integrations.push(Box::new(MediaControl::new(session.media)));
+        }
+        Ok(Integrations {
+            integrations,
+            ended: AtomicBool::new(false),
+        })
+    }
+
+    pub fn phase_start(&self, session: &PomodoroSession) {
+        self.ended.store(false, Ordering::SeqCst);
+        for integration in &self.integrations {
+            if dry_run::is_enabled() {
+                dry_run::action(format!("{}: phase start", integration.name()));
+            } else if let Err(e) = integration.phase_start(session) {
+                log::warn!("{}: {}", integration.name(), e);
+            }
+        }
+    }
+
+    pub fn phase_end(&self, session: &PomodoroSession, outcome: Outcome) {
+        for integration in &self.integrations {
+            if dry_run::is_enabled() {
+                let what = format!("{}: phase end ({:?})", integration.name(), outcome);
+                dry_run::action(what.to_lowercase());
+            } else if let Err(e) = integration.phase_end(session, outcome) {
+                log::warn!("{}: {}", integration.name(), e);
+            }
+        }
+    }
+
+    // Safe to call more than once (normal exit, then cleanup); only the
+    // first call does anything
+    pub fn session_end(&self) {
+        if self.ended.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        for integration in &self.integrations {
+            if dry_run::is_enabled() {
+                dry_run::action(format!("{}: session end", integration.name()));
+            } else if let Err(e) = integration.session_end() {
+                log::warn!("{}: {}", integration.name(), e);
+            }
+        }
+    }
+}
+
+// Quote a value for a curl config file
+fn curl_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
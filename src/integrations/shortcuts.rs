@@ -0,0 +1,121 @@
+// macOS Shortcuts run at the edges of focus, for people who already automate
+// Focus modes, Slack, lights and so on there:
+//
+//   --shortcut-on-focus "Deep Work"         (when focus starts or is resumed)
+//   --shortcut-after-focus "Deep Work Off"  (when focus ends: break or session end)
+//
+// or in the config file:
+//
+//   [shortcuts]
+//   on_focus = "Deep Work"
+//   after_focus = "Deep Work Off"
+//
+// Shortcuts are run with `shortcuts run` (macOS 12 and later) and waited for,
+// so whatever they switch on is in place before the blockers engage.
+
+use super::Integration;
+use crate::session::{Phase, PomodoroSession};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Shortcut names, from the flags or the `[shortcuts]` table; saved with the
+/// session so resumed and background sessions run the same ones
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShortcutSettings {
+    pub on_focus: Option<String>,
+    pub after_focus: Option<String>,
+}
+
+impl ShortcutSettings {
+    pub fn is_empty(&self) -> bool {
+        self.on_focus.is_none() && self.after_focus.is_none()
+    }
+}
+
+pub struct Shortcuts {
+    settings: ShortcutSettings,
+    /// The last phase started was focus
+    in_focus: AtomicBool,
+}
+
+impl Shortcuts {
+    pub fn new(settings: &ShortcutSettings) -> Self {
+        Shortcuts {
+            settings: settings.clone(),
+            in_focus: AtomicBool::new(false),
+        }
+    }
+
+    // Run the `after_focus` shortcut if focus was on
+    fn focus_over(&self) -> io::Result<()> {
+        if self.in_focus.swap(false, Ordering::SeqCst) {
+            run(self.settings.after_focus.as_deref())?;
+        }
+        Ok(())
+    }
+}
+
+impl Integration for Shortcuts {
+    fn name(&self) -> &'static str {
+        "shortcuts"
+    }
+
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()> {
+        match session.phase {
+            Phase::Focus => {
+                self.in_focus.store(true, Ordering::SeqCst);
+                run(self.settings.on_focus.as_deref())
+            }
+            Phase::Break => self.focus_over(),
+        }
+    }
+
+    fn session_end(&self) -> io::Result<()> {
+        self.focus_over()
+    }
+}
+
+// Run a shortcut by name and wait for it
+fn run(name: Option<&str>) -> io::Result<()> {
+    let Some(name) = name else {
+        return Ok(());
+    };
+    log::debug!("shortcuts run '{}'", name);
+    let status = Command::new("shortcuts")
+        .args(["run", name])
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                "the `shortcuts` command was not found (it needs macOS 12 or later)",
+            ),
+            _ => e,
+        })?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "shortcut '{}' failed ({})",
+            name, status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_come_from_the_shortcuts_table() {
+        let settings: ShortcutSettings = toml::from_str("on_focus = \"Deep Work\"").unwrap();
+        assert_eq!(settings.on_focus.as_deref(), Some("Deep Work"));
+        assert_eq!(settings.after_focus, None);
+        assert!(!settings.is_empty());
+        assert!(ShortcutSettings::default().is_empty());
+        assert!(toml::from_str::<ShortcutSettings>("on_break = \"x\"").is_err());
+    }
+}
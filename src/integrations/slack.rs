@@ -0,0 +1,272 @@
+// Slack status during focus, e.g. ":tomato: Focusing — back at 14:25", cleared
+// when the break starts and when the session ends.
+//
+//   [slack]
+//   token = "xoxp-..."     # user token with the users.profile:write scope
+//   status_text = "Focusing — back at {end}"
+//   status_emoji = ":tomato:"
+//
+// The token can also be given in the POMO_SLACK_TOKEN environment variable
+// (which wins over the config file). Requests go through `curl` with the token
+// and body passed on stdin, so they never show up in `ps`. The status is set to
+// expire by itself at the end of focus, so a crashed session doesn't leave it
+// behind, and requests that fail for a temporary reason (no network yet after
+// Wi-Fi comes back, rate limiting) are retried.
+//
+// Discord has no supported API for setting a user's custom status (only by
+// automating a user token, which its terms forbid), so there is no Discord
+// counterpart; an `on_focus_start` hook can post to a Discord webhook instead.
+
+use super::{Integration, curl_quote};
+use crate::session::{Phase, PomodoroSession};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+const API_URL: &str = "https://slack.com/api/users.profile.set";
+
+/// Environment variable that can hold the token instead of the config file
+const TOKEN_ENV: &str = "POMO_SLACK_TOKEN";
+
+/// Tries per status update, and the wait between them
+const ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// The `[slack]` table of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlackSettings {
+    pub token: Option<String>,
+    /// Status text during focus; `{end}` becomes the time focus ends (HH:MM)
+    pub status_text: String,
+    pub status_emoji: String,
+}
+
+impl Default for SlackSettings {
+    fn default() -> Self {
+        SlackSettings {
+            token: None,
+            status_text: "Focusing — back at {end}".to_string(),
+            status_emoji: ":tomato:".to_string(),
+        }
+    }
+}
+
+/// Sets the Slack status for focus and clears it afterwards
+pub struct SlackStatus {
+    token: String,
+    text: String,
+    emoji: String,
+    /// Bumped by every update, so a retrying update that has been superseded
+    /// (e.g. a slow clear when the next focus starts) gives up
+    generation: Arc<AtomicU64>,
+}
+
+impl SlackStatus {
+    pub fn new(settings: &SlackSettings) -> io::Result<Self> {
+        let token = std::env::var(TOKEN_ENV)
+            .ok()
+            .or_else(|| settings.token.clone())
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("no Slack token (set `token` in [slack] or {})", TOKEN_ENV),
+                )
+            })?;
+        Ok(SlackStatus {
+            token,
+            text: settings.status_text.clone(),
+            emoji: settings.status_emoji.clone(),
+            generation: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    // Profile fields for focus that ends at `end`
+    fn focus_profile(&self, end: DateTime<Local>) -> Value {
+        json!({
+            "status_text": self.text.replace("{end}", &end.format("%H:%M").to_string()),
+            "status_emoji": self.emoji,
+            "status_expiration": end.timestamp(),
+        })
+    }
+
+    // Start a new update, superseding any that is still retrying
+    fn next_generation(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+impl Integration for SlackStatus {
+    fn name(&self) -> &'static str {
+        "Slack status"
+    }
+
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()> {
+        let generation = self.next_generation();
+        match session.phase {
+            Phase::Focus => {
+                // Set it now, while the network is still up
+                let end = Local::now() + TimeDelta::seconds(session.remaining() as i64);
+                update(
+                    &self.token,
+                    &self.focus_profile(end),
+                    &self.generation,
+                    generation,
+                )
+            }
+            Phase::Break => {
+                // Wi-Fi may still be reconnecting; keep trying in the background
+                // rather than holding up the break
+                let token = self.token.clone();
+                let current = Arc::clone(&self.generation);
+                thread::spawn(move || {
+                    if let Err(e) = update(&token, &cleared(), &current, generation) {
+                        log::warn!("Slack status: {}", e);
+                    }
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn session_end(&self) -> io::Result<()> {
+        let generation = self.next_generation();
+        update(&self.token, &cleared(), &self.generation, generation)
+    }
+}
+
+// Profile fields that clear the status
+fn cleared() -> Value {
+    json!({
+        "status_text": "",
+        "status_emoji": "",
+        "status_expiration": 0,
+    })
+}
+
+// Apply a status update, retrying temporary failures. Gives up quietly if a
+// newer update (`current` moved past `generation`) has started meanwhile.
+fn update(token: &str, profile: &Value, current: &AtomicU64, generation: u64) -> io::Result<()> {
+    let mut attempt = 1;
+    loop {
+        if current.load(Ordering::SeqCst) != generation {
+            return Ok(());
+        }
+        match set_profile(token, profile) {
+            Ok(()) => return Ok(()),
+            Err(failure) if failure.temporary && attempt < ATTEMPTS => {
+                attempt += 1;
+                thread::sleep(RETRY_DELAY);
+            }
+            Err(failure) => return Err(failure.error),
+        }
+    }
+}
+
+/// Why a request failed, and whether trying again might help
+struct Failure {
+    error: io::Error,
+    temporary: bool,
+}
+
+// Call users.profile.set through curl
+fn set_profile(token: &str, profile: &Value) -> Result<(), Failure> {
+    let temporary = |error: io::Error| Failure {
+        error,
+        temporary: true,
+    };
+
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--max-time",
+            "10",
+            "--config",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| Failure {
+            error,
+            temporary: false,
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(curl_config(token, profile).as_bytes())
+            .map_err(temporary)?;
+    }
+    let output = child.wait_with_output().map_err(temporary)?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(temporary(io::Error::other(message)));
+    }
+
+    let reply: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| temporary(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    if reply["ok"] == true {
+        return Ok(());
+    }
+    let error = reply["error"]
+        .as_str()
+        .unwrap_or("unknown error")
+        .to_string();
+    Err(Failure {
+        temporary: error == "ratelimited",
+        error: io::Error::other(format!("Slack API error: {}", error)),
+    })
+}
+
+// Request for `curl --config -`: keeps the token off the command line
+fn curl_config(token: &str, profile: &Value) -> String {
+    let body = json!({ "profile": profile }).to_string();
+    format!(
+        "url = {}\nrequest = \"POST\"\nheader = {}\nheader = \"Content-Type: application/json; charset=utf-8\"\ndata-raw = {}\n",
+        curl_quote(API_URL),
+        curl_quote(&format!("Authorization: Bearer {}", token)),
+        curl_quote(&body)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn focus_status_says_when_focus_ends() {
+        let slack = SlackStatus::new(&SlackSettings {
+            token: Some("xoxp-test".to_string()),
+            ..SlackSettings::default()
+        })
+        .unwrap();
+        let end = Local.with_ymd_and_hms(2024, 5, 1, 14, 25, 0).unwrap();
+
+        let profile = slack.focus_profile(end);
+        assert_eq!(profile["status_text"], "Focusing — back at 14:25");
+        assert_eq!(profile["status_emoji"], ":tomato:");
+        assert_eq!(profile["status_expiration"], end.timestamp());
+    }
+
+    #[test]
+    fn curl_config_quotes_token_and_body() {
+        let config = curl_config("xoxp-1", &json!({ "status_text": "say \"hi\"" }));
+        assert!(config.contains("header = \"Authorization: Bearer xoxp-1\"\n"));
+        assert!(
+            config.contains(r#"data-raw = "{\"profile\":{\"status_text\":\"say \\\"hi\\\"\"}}""#)
+        );
+    }
+}
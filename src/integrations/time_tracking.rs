@@ -0,0 +1,591 @@
+// Time tracking: a Toggl Track or Clockify time entry for each focus phase,
+// described by the task (and tagged with the session's tags, on Toggl).
+//
+//   [time_tracking]
+//   service = "toggl"          # or "clockify"
+//   token = "..."              # API token (or POMO_TIME_TRACKING_TOKEN)
+//   workspace = "1234567"      # workspace id
+//   project = "89012345"       # project id (optional)
+//
+// The entry is started when focus starts, while the network is still up, and
+// stopped at the time focus ended (pauses count towards it). Requests that
+// can't be sent, as when Wi-Fi hasn't reconnected yet after focus, wait in a
+// queue kept on disk (time-tracking.json in the state directory) and are
+// retried until they go through, by a later session if need be. An entry
+// whose start never got out is created whole once it has ended. Breaks
+// aren't tracked.
+
+use super::{Integration, curl_quote};
+use crate::{
+    dry_run,
+    history::Outcome,
+    session::{Phase, PomodoroSession},
+    state,
+};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const TOGGL_API: &str = "https://api.track.toggl.com/api/v9";
+const CLOCKIFY_API: &str = "https://api.clockify.me/api/v1";
+
+/// Environment variable that can hold the token instead of the config file
+const TOKEN_ENV: &str = "POMO_TIME_TRACKING_TOKEN";
+
+/// Wait before trying a request again
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Longest wait at the end of a session for the queue to empty
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Which service keeps the time entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingService {
+    Toggl,
+    Clockify,
+}
+
+/// The `[time_tracking]` table of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeTrackingSettings {
+    pub service: TrackingService,
+    pub token: Option<String>,
+    pub workspace: String,
+    pub project: Option<String>,
+}
+
+/// The time entry of one focus phase
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    start: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop: Option<DateTime<Local>>,
+}
+
+/// A request waiting to be sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "job", rename_all = "snake_case")]
+enum Job {
+    /// Start a running entry
+    Start(Entry),
+    /// Stop the entry if it is running, or else create it whole
+    Finish(Entry),
+}
+
+/// Requests not sent yet and the entry running on the service, as kept on
+/// disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Queue {
+    #[serde(default)]
+    jobs: Vec<Job>,
+    /// Id and start of the running entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    running: Option<(String, DateTime<Local>)>,
+    /// The first job is being sent
+    #[serde(skip)]
+    sending: bool,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl Queue {
+    // What an earlier session left behind, if anything
+    fn load(path: PathBuf) -> Self {
+        let queue = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("Ignoring {}: {}", path.display(), e);
+                Queue::default()
+            }),
+            Err(_) => Queue::default(),
+        };
+        Queue { path, ..queue }
+    }
+
+    fn save(&self) {
+        let result = if self.jobs.is_empty() && self.running.is_none() {
+            match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        } else {
+            self.path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| serde_json::to_string_pretty(self).map_err(io::Error::other))
+                .and_then(|json| fs::write(&self.path, json))
+        };
+        if let Err(e) = result {
+            log::warn!("time tracking: could not save the queue: {}", e);
+        }
+    }
+
+    // Queue the end of an entry. A start that hasn't gone out yet is dropped:
+    // the entry is created whole instead.
+    fn finish(&mut self, entry: Entry) {
+        let waiting = self
+            .jobs
+            .iter()
+            .position(|job| matches!(job, Job::Start(start) if start.start == entry.start));
+        if let Some(index) = waiting
+            && !(index == 0 && self.sending)
+        {
+            self.jobs.remove(index);
+        }
+        self.jobs.push(Job::Finish(entry));
+    }
+}
+
+/// Tracks focus time on Toggl Track or Clockify
+pub struct TimeTracking {
+    service: TrackingService,
+    queue: Arc<Mutex<Queue>>,
+    wake: Mutex<Sender<()>>,
+    /// The entry of the focus phase under way
+    open: Mutex<Option<Entry>>,
+}
+
+impl TimeTracking {
+    pub fn new(settings: &TimeTrackingSettings) -> io::Result<Self> {
+        let client = Client::new(settings)?;
+        let service = settings.service;
+        let (wake, woken) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(Queue::load(state::session_file(
+            state::name().as_deref(),
+            "time-tracking",
+            "json",
+        ))));
+        // A dry run leaves the queue alone
+        if !dry_run::is_enabled() {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || send_all(&client, &queue, woken));
+        }
+        Ok(TimeTracking {
+            service,
+            queue,
+            wake: Mutex::new(wake),
+            open: Mutex::new(None),
+        })
+    }
+
+    // Add to the queue and have the sender look at it
+    fn queue(&self, change: impl FnOnce(&mut Queue)) {
+        let mut queue = self.queue.lock().unwrap();
+        change(&mut queue);
+        queue.save();
+        let _ = self.wake.lock().unwrap().send(());
+    }
+
+    // Queue the end of the open entry, if there is one
+    fn close(&self) {
+        if let Some(mut entry) = self.open.lock().unwrap().take() {
+            entry.stop = Some(Local::now());
+            self.queue(|queue| queue.finish(entry));
+        }
+    }
+}
+
+impl Integration for TimeTracking {
+    fn name(&self) -> &'static str {
+        match self.service {
+            TrackingService::Toggl => "Toggl",
+            TrackingService::Clockify => "Clockify",
+        }
+    }
+
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()> {
+        if session.phase == Phase::Break {
+            return Ok(());
+        }
+        // A resumed focus phase gets an entry of its own
+        self.close();
+        let entry = Entry {
+            description: session.task.clone().unwrap_or_else(|| "Focus".to_string()),
+            tags: session.tags.clone(),
+            start: Local::now(),
+            stop: None,
+        };
+        *self.open.lock().unwrap() = Some(entry.clone());
+        self.queue(|queue| queue.jobs.push(Job::Start(entry)));
+        Ok(())
+    }
+
+    fn phase_end(&self, session: &PomodoroSession, _outcome: Outcome) -> io::Result<()> {
+        if session.phase == Phase::Focus {
+            self.close();
+        }
+        Ok(())
+    }
+
+    fn session_end(&self) -> io::Result<()> {
+        self.close();
+        // Give what is queued a chance to go out; the rest waits for the next
+        // session
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        while Instant::now() < deadline {
+            if self.queue.lock().unwrap().jobs.is_empty() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        let left = self.queue.lock().unwrap().jobs.len();
+        Err(io::Error::other(format!(
+            "{} request{} not sent yet; the next session will try again",
+            left,
+            if left == 1 { "" } else { "s" }
+        )))
+    }
+}
+
+// Send queued requests one at a time, in order, waiting a while after one
+// fails for a reason that may pass (no network, the service is down)
+fn send_all(client: &Client, queue: &Mutex<Queue>, wake: Receiver<()>) {
+    loop {
+        let (job, running) = {
+            let mut queue = queue.lock().unwrap();
+            let job = queue.jobs.first().cloned();
+            queue.sending = job.is_some();
+            (job, queue.running.clone())
+        };
+        let Some(job) = job else {
+            match wake.recv() {
+                Ok(()) => continue,
+                Err(_) => return,
+            }
+        };
+
+        let result = match &job {
+            Job::Start(entry) => client.create(entry).map(|id| Some((id, entry.start))),
+            Job::Finish(entry) => {
+                match running.clone().filter(|(_, start)| *start == entry.start) {
+                    Some((id, _)) => client.update(&id, entry).map(|()| None),
+                    None => client.create(entry).map(|_| running),
+                }
+            }
+        };
+        let mut queue = queue.lock().unwrap();
+        queue.sending = false;
+        match result {
+            Ok(running) => {
+                queue.jobs.remove(0);
+                queue.running = running;
+                queue.save();
+            }
+            Err(failure) if failure.temporary => {
+                drop(queue);
+                log::debug!("time tracking: {} (will try again)", failure.error);
+                if let Err(RecvTimeoutError::Disconnected) = wake.recv_timeout(RETRY_DELAY) {
+                    return;
+                }
+            }
+            Err(failure) => {
+                log::warn!("time tracking: {}", failure.error);
+                queue.jobs.remove(0);
+                queue.save();
+            }
+        }
+    }
+}
+
+/// Why a request failed, and whether trying again might help
+struct Failure {
+    error: io::Error,
+    temporary: bool,
+}
+
+/// Requests to the service's API
+struct Client {
+    service: TrackingService,
+    token: String,
+    workspace: String,
+    project: Option<String>,
+}
+
+impl Client {
+    fn new(settings: &TimeTrackingSettings) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let token = std::env::var(TOKEN_ENV)
+            .ok()
+            .or_else(|| settings.token.clone())
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                invalid(format!(
+                    "no API token (set `token` in [time_tracking] or {})",
+                    TOKEN_ENV
+                ))
+            })?;
+        // Toggl's ids are numbers
+        if settings.service == TrackingService::Toggl {
+            for (name, id) in [
+                ("workspace", Some(&settings.workspace)),
+                ("project", settings.project.as_ref()),
+            ] {
+                if let Some(id) = id
+                    && id.parse::<u64>().is_err()
+                {
+                    return Err(invalid(format!(
+                        "{} '{}' is not a Toggl {} id (a number)",
+                        name, id, name
+                    )));
+                }
+            }
+        }
+        Ok(Client {
+            service: settings.service,
+            token,
+            workspace: settings.workspace.clone(),
+            project: settings.project.clone(),
+        })
+    }
+
+    // Create an entry (running, if it has no stop yet) and return its id
+    fn create(&self, entry: &Entry) -> Result<String, Failure> {
+        let reply = self.request("POST", &self.entries_url(), &self.body(entry))?;
+        match &reply["id"] {
+            Value::String(id) => Ok(id.clone()),
+            Value::Number(id) => Ok(id.to_string()),
+            _ => Err(Failure {
+                error: io::Error::new(io::ErrorKind::InvalidData, "no id in the reply"),
+                temporary: false,
+            }),
+        }
+    }
+
+    // Give a running entry its stop time
+    fn update(&self, id: &str, entry: &Entry) -> Result<(), Failure> {
+        let url = format!("{}/{}", self.entries_url(), id);
+        self.request("PUT", &url, &self.body(entry)).map(drop)
+    }
+
+    fn entries_url(&self) -> String {
+        match self.service {
+            TrackingService::Toggl => {
+                format!("{}/workspaces/{}/time_entries", TOGGL_API, self.workspace)
+            }
+            TrackingService::Clockify => {
+                format!(
+                    "{}/workspaces/{}/time-entries",
+                    CLOCKIFY_API, self.workspace
+                )
+            }
+        }
+    }
+
+    // The entry as the service's API has it
+    fn body(&self, entry: &Entry) -> Value {
+        let start = utc(entry.start);
+        match self.service {
+            TrackingService::Toggl => {
+                let number = |id: &str| id.parse::<u64>().unwrap_or_default();
+                let mut body = json!({
+                    "created_with": "rust-pomo",
+                    "workspace_id": number(&self.workspace),
+                    "description": entry.description,
+                    "tags": entry.tags,
+                    "start": start,
+                    // Negative for a running entry
+                    "duration": entry
+                        .stop
+                        .map_or(-1, |stop| (stop - entry.start).num_seconds()),
+                });
+                if let Some(stop) = entry.stop {
+                    body["stop"] = json!(utc(stop));
+                }
+                if let Some(project) = &self.project {
+                    body["project_id"] = json!(number(project));
+                }
+                body
+            }
+            TrackingService::Clockify => {
+                let mut body = json!({
+                    "description": entry.description,
+                    "start": start,
+                });
+                if let Some(stop) = entry.stop {
+                    body["end"] = json!(utc(stop));
+                }
+                if let Some(project) = &self.project {
+                    body["projectId"] = json!(project);
+                }
+                body
+            }
+        }
+    }
+
+    // Send a request through curl and return the JSON reply
+    fn request(&self, method: &str, url: &str, body: &Value) -> Result<Value, Failure> {
+        let temporary = |error: io::Error| Failure {
+            error,
+            temporary: true,
+        };
+        let mut child = Command::new("curl")
+            .args([
+                "--silent",
+                "--show-error",
+                "--max-time",
+                "10",
+                "--write-out",
+                "\n%{http_code}",
+                "--config",
+                "-",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| Failure {
+                error,
+                temporary: false,
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(self.curl_config(method, url, body).as_bytes())
+                .map_err(temporary)?;
+        }
+        let output = child.wait_with_output().map_err(temporary)?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Err(temporary(io::Error::other(message)));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (reply, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+        match status.trim().parse::<u16>().unwrap_or(0) {
+            200..=299 => Ok(serde_json::from_str(reply).unwrap_or(Value::Null)),
+            status => Err(Failure {
+                error: io::Error::other(format!(
+                    "HTTP {}: {}",
+                    status,
+                    reply.trim().chars().take(200).collect::<String>()
+                )),
+                temporary: status == 0 || status == 429 || status >= 500,
+            }),
+        }
+    }
+
+    // Request for `curl --config -`: keeps the token off the command line
+    fn curl_config(&self, method: &str, url: &str, body: &Value) -> String {
+        let auth = match self.service {
+            TrackingService::Toggl => {
+                format!(
+                    "user = {}",
+                    curl_quote(&format!("{}:api_token", self.token))
+                )
+            }
+            TrackingService::Clockify => {
+                format!(
+                    "header = {}",
+                    curl_quote(&format!("X-Api-Key: {}", self.token))
+                )
+            }
+        };
+        format!(
+            "url = {}\nrequest = {}\n{}\nheader = \"Content-Type: application/json\"\ndata-raw = {}\n",
+            curl_quote(url),
+            curl_quote(method),
+            auth,
+            curl_quote(&body.to_string())
+        )
+    }
+}
+
+// A time as both APIs take it, e.g. 2024-05-01T01:00:00Z
+fn utc(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(minute: u32) -> Entry {
+        Entry {
+            description: "report".to_string(),
+            tags: vec!["work".to_string()],
+            start: Local.with_ymd_and_hms(2024, 5, 1, 10, minute, 0).unwrap(),
+            stop: None,
+        }
+    }
+
+    #[test]
+    fn an_unsent_start_is_folded_into_the_finish() {
+        let mut queue = Queue::default();
+        queue.jobs.push(Job::Start(entry(0)));
+        queue.finish(entry(0));
+        assert_eq!(queue.jobs, vec![Job::Finish(entry(0))]);
+
+        // One being sent has to be stopped once it is running
+        queue.jobs = vec![Job::Start(entry(30))];
+        queue.sending = true;
+        queue.finish(entry(30));
+        assert_eq!(
+            queue.jobs,
+            vec![Job::Start(entry(30)), Job::Finish(entry(30))]
+        );
+    }
+
+    #[test]
+    fn bodies_follow_each_api() {
+        let settings = TimeTrackingSettings {
+            service: TrackingService::Toggl,
+            token: Some("secret".to_string()),
+            workspace: "123".to_string(),
+            project: Some("456".to_string()),
+        };
+        let toggl = Client::new(&settings).unwrap();
+        let mut done = entry(0);
+        done.stop = Some(done.start + chrono::TimeDelta::minutes(25));
+
+        let running = toggl.body(&entry(0));
+        assert_eq!(running["workspace_id"], 123);
+        assert_eq!(running["project_id"], 456);
+        assert_eq!(running["duration"], -1);
+        assert_eq!(running["tags"], json!(["work"]));
+        assert!(running.get("stop").is_none());
+        assert_eq!(toggl.body(&done)["duration"], 1500);
+        assert!(
+            toggl
+                .curl_config("POST", &toggl.entries_url(), &running)
+                .contains("user = \"secret:api_token\"\n")
+        );
+
+        let clockify = Client::new(&TimeTrackingSettings {
+            service: TrackingService::Clockify,
+            workspace: "abc".to_string(),
+            ..settings.clone()
+        })
+        .unwrap();
+        let body = clockify.body(&done);
+        assert_eq!(body["start"], utc(done.start));
+        assert_eq!(body["end"], utc(done.stop.unwrap()));
+        assert_eq!(body["projectId"], "456");
+        assert!(body.get("tags").is_none());
+
+        assert!(
+            Client::new(&TimeTrackingSettings {
+                workspace: "abc".to_string(),
+                ..settings
+            })
+            .is_err()
+        );
+    }
+}
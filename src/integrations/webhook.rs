@@ -0,0 +1,249 @@
+// Webhook: a JSON POST to a URL at every phase transition, for Home Assistant,
+// n8n, IFTTT and the like.
+//
+//   --webhook-url https://example.com/hook     (or `url` in [webhook])
+//
+// Each request carries the event, the phase and where the session is:
+//
+//   {"event":"phase_start","time":"2024-05-01T10:00:00+09:00","phase":"focus",
+//    "cycle":1,"cycles":4,"task":"report","tags":["work"],
+//    "started_at":"2024-05-01T10:00:00+09:00","ends_at":"2024-05-01T10:25:00+09:00",
+//    "planned_secs":1500,"actual_secs":0,"interruptions":0,...}
+//
+// `planned_secs` is the length of the phase as planned, `actual_secs` the time
+// spent in it (overtime counts, pauses don't) and `interruptions` the number
+// of pauses.
+//
+// Events: phase_start, phase_end (with the outcome) and session_end.
+//
+// Requests are sent in order from a background thread, so a slow endpoint
+// never holds up the timer, and retried for a while: when focus ends the
+// network is often still coming back. The URL (webhook URLs often embed a secret) is
+// passed to `curl` on stdin rather than on the command line.
+
+use super::{Integration, curl_quote};
+use crate::{history::Outcome, output::Snapshot, session::PomodoroSession};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::{
+        Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Tries per request, and the wait between them
+const ATTEMPTS: u32 = 10;
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+
+/// Longest wait at the end of a session for requests still being sent
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The `[webhook]` table of the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookSettings {
+    pub url: Option<String>,
+}
+
+/// The JSON body of a request
+#[derive(Debug, Serialize)]
+struct Payload {
+    event: &'static str,
+    time: DateTime<Local>,
+    #[serde(flatten)]
+    snapshot: Option<Snapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<DateTime<Local>>,
+    /// When the phase is due to end (phase_start)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ends_at: Option<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<Outcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    planned_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interruptions: Option<u32>,
+}
+
+impl Payload {
+    fn new(event: &'static str, session: Option<&PomodoroSession>) -> Self {
+        Payload {
+            event,
+            time: Local::now(),
+            snapshot: session.map(Snapshot::of),
+            started_at: session.and_then(|s| s.phase_started_at),
+            ends_at: None,
+            outcome: None,
+            planned_secs: session.map(PomodoroSession::planned_len),
+            actual_secs: session.map(PomodoroSession::actual_len),
+            interruptions: session.map(|s| s.pauses),
+        }
+    }
+}
+
+/// A request for the sender thread; `done` is told once it has been sent
+struct Job {
+    body: String,
+    done: Option<Sender<()>>,
+}
+
+/// Posts phase transitions to a URL
+pub struct Webhook {
+    jobs: Mutex<Sender<Job>>,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Self {
+        let (jobs, queue) = mpsc::channel();
+        let url = url.to_string();
+        thread::spawn(move || send_all(&url, queue));
+        Webhook {
+            jobs: Mutex::new(jobs),
+        }
+    }
+
+    fn queue(&self, payload: &Payload, done: Option<Sender<()>>) -> io::Result<()> {
+        let body = serde_json::to_string(payload).map_err(io::Error::other)?;
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(Job { body, done })
+            .map_err(|_| io::Error::other("the webhook sender has stopped"))
+    }
+}
+
+impl Integration for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn phase_start(&self, session: &PomodoroSession) -> io::Result<()> {
+        let mut payload = Payload::new("phase_start", Some(session));
+        // Integrations hear of a new phase just before its start is noted
+        payload.started_at = payload.started_at.or(Some(payload.time));
+        payload.ends_at = Some(Local::now() + TimeDelta::seconds(session.remaining() as i64));
+        self.queue(&payload, None)
+    }
+
+    fn phase_end(&self, session: &PomodoroSession, outcome: Outcome) -> io::Result<()> {
+        let mut payload = Payload::new("phase_end", Some(session));
+        payload.outcome = Some(outcome);
+        self.queue(&payload, None)
+    }
+
+    fn session_end(&self) -> io::Result<()> {
+        // Wait (a while) for this and anything still queued to go out
+        let (done, sent) = mpsc::channel();
+        self.queue(&Payload::new("session_end", None), Some(done))?;
+        sent.recv_timeout(FLUSH_TIMEOUT)
+            .map_err(|_| io::Error::other("gave up waiting for the webhook"))
+    }
+}
+
+// Send queued requests one at a time, in order
+fn send_all(url: &str, queue: Receiver<Job>) {
+    for job in queue {
+        let mut attempt = 1;
+        loop {
+            match post(url, &job.body) {
+                Ok(()) => break,
+                Err(_) if attempt < ATTEMPTS => {
+                    attempt += 1;
+                    thread::sleep(RETRY_DELAY);
+                }
+                Err(e) => {
+                    log::warn!("webhook: {}", e);
+                    break;
+                }
+            }
+        }
+        if let Some(done) = job.done {
+            let _ = done.send(());
+        }
+    }
+}
+
+// POST the body through curl
+fn post(url: &str, body: &str) -> io::Result<()> {
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "10",
+            "--config",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(curl_config(url, body).as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(())
+}
+
+// Request for `curl --config -`
+fn curl_config(url: &str, body: &str) -> String {
+    format!(
+        "url = {}\nrequest = \"POST\"\nheader = \"Content-Type: application/json; charset=utf-8\"\ndata-raw = {}\n",
+        curl_quote(url),
+        curl_quote(body)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn payload_has_phase_and_timestamps() {
+        let mut session = PomodoroSession::new(1500, 300, 4);
+        session.task = Some("report".to_string());
+        session.phase_started_at = Some(Local::now());
+        session.elapsed = 1500;
+        session.pauses = 1;
+        let mut payload = Payload::new("phase_end", Some(&session));
+        payload.outcome = Some(Outcome::Completed);
+
+        let json: Value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event"], "phase_end");
+        assert_eq!(json["phase"], "focus");
+        assert_eq!(json["cycle"], 1);
+        assert_eq!(json["cycles"], 4);
+        assert_eq!(json["task"], "report");
+        assert_eq!(json["outcome"], "completed");
+        assert!(json["started_at"].is_string());
+        assert!(json.get("ends_at").is_none());
+        assert_eq!(json["planned_secs"], 1500);
+        assert_eq!(json["actual_secs"], 1500);
+        assert_eq!(json["interruptions"], 1);
+
+        let end: Value = serde_json::to_value(Payload::new("session_end", None)).unwrap();
+        assert_eq!(end["event"], "session_end");
+        assert!(end.get("phase").is_none());
+        assert!(end.get("actual_secs").is_none());
+    }
+
+    #[test]
+    fn curl_config_keeps_url_off_the_command_line() {
+        let config = curl_config("https://example.com/hook?k=1", "{\"a\":1}");
+        assert!(config.starts_with("url = \"https://example.com/hook?k=1\"\n"));
+        assert!(config.contains("data-raw = \"{\\\"a\\\":1}\"\n"));
+    }
+}
@@ -0,0 +1,76 @@
+//! Library side of `pomo`, a Pomodoro-style focus timer that turns Wi-Fi (and
+//! other distractions) off while you focus.
+//!
+//! - [`session`]: the `PomodoroSession` / `Phase` state machine
+//! - [`timer`]: phase time accounting with pauses
+//! - [`clock`]: the time source the runner waits on (real, sped up or manual)
+//! - [`runner`]: runs a session in real time
+//! - [`battery`]: battery level and power source
+//! - [`blockers`]: the `DistractionBlocker` trait and its implementations
+//! - [`notify`]: notification settings and the `Notifier` trait
+//! - [`speech`]: spoken announcements (`--speak`)
+//! - [`integrations`]: outside services such as Slack status
+//! - [`output`]: JSON events for scripts and status bars
+//! - [`follow`]: the running session's events streamed to `pomo follow`
+//! - [`plan`]: the planning queue of tasks with estimates
+//! - [`inbox`]: notes taken during focus without stopping the timer
+//! - [`input`]: commands typed at the terminal during a session
+//! - [`logging`]: `-v` / `-q` diagnostics through the `log` crate
+//! - [`theme`]: progress bar themes and templates
+//! - [`tui`]: the full-screen `--tui` dashboard and break overlay
+//! - [`tray`]: the `--tray` icon (with the `tray` feature)
+//! - [`enforce`]: break enforcement (screen lock or overlay)
+//! - [`error`]: the errors that end `pomo`, and their exit statuses
+//! - [`completions`]: shell completion, with profiles, tasks and tags
+//! - [`crypt`]: encryption at rest for the session log
+//! - [`dry_run`]: stand-ins that print side effects instead of performing them
+//! - [`report`]: the end-of-day report from the session log
+//! - [`heatmap`]: focus time by day and by hour, for `pomo stats --heatmap`
+//!
+//! The `pomo` binary is a thin command-line layer on top of these.
+
+pub mod battery;
+pub mod blockers;
+pub mod cleanup;
+pub mod clock;
+pub mod completions;
+pub mod config;
+pub mod crypt;
+pub mod daemon;
+pub mod dry_run;
+pub mod duration;
+pub mod enforce;
+pub mod error;
+pub mod export;
+pub mod follow;
+pub mod git;
+pub mod goal;
+pub mod heatmap;
+pub mod history;
+pub mod hooks;
+pub mod inbox;
+pub mod input;
+pub mod integrations;
+pub mod logging;
+pub mod mqtt;
+pub mod notify;
+pub mod output;
+pub mod plan;
+pub mod power;
+pub mod profile;
+pub mod report;
+pub mod runner;
+pub mod schedule;
+pub mod server;
+pub mod session;
+#[cfg(unix)]
+pub mod signals;
+pub mod sound;
+pub mod speech;
+pub mod state;
+pub mod stats;
+pub mod statusline;
+pub mod theme;
+pub mod timer;
+pub mod tray;
+pub mod tui;
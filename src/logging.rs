@@ -0,0 +1,64 @@
+// Diagnostics through the `log` crate, next to the `say!` messages meant for
+// the user (phase changes, pause toggles, ...):
+//
+//   pomo            say! messages, warnings and errors
+//   pomo -v         also what pomo does behind the scenes: the Wi-Fi and other
+//                   blocker commands, hook commands and their output, pauses
+//   pomo -vv        also every control request (socket, HTTP, signals)
+//   pomo -q         errors only; no messages (the progress bar stays)
+//
+// Records go to stderr (above the progress bar), or onto the `--tui`
+// dashboard while it is up.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = match record.level() {
+            Level::Error | Level::Warn | Level::Info => record.args().to_string(),
+            Level::Debug => format!("debug: {}", record.args()),
+            Level::Trace => format!("trace: {}", record.args()),
+        };
+        if crate::tui::is_active() {
+            crate::tui::log(message);
+        } else {
+            crate::output::print_line(&message, true);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the logger, with `-v` given `verbose` times (or `-q`)
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    QUIET.store(quiet, Ordering::SeqCst);
+    // Only fails if a logger is already installed, which leaves that one
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Whether `say!` messages are turned off (`-q`)
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::SeqCst)
+}
@@ -1,186 +1,1223 @@
 // A CLI tool to manage focus and break time. This uses Clap (with derive), Indicatif,
-// ctrlc to handle arguments, progress display, and SIGINT signals. Now includes a
-// pause feature that toggles Wi-Fi on/off during pauses.
+// ctrlc to handle arguments, progress display, and SIGINT signals. The timer itself
+// lives in the rust_pomo library (lib.rs); this file is the command-line layer, and
+// README.md describes every feature.
 //
 // How to use pause:
 //   During focus or break, type 'p' (and press ENTER) in the console to pause.
 //   If in focus mode (Wi-Fi off), pause will turn Wi-Fi on. When you unpause,
 //   Wi-Fi turns off again. Similarly, if in break mode (Wi-Fi on), pause won't
 //   change Wi-Fi state (it remains on), but the timer is paused until 'p' is pressed again.
+//   The other commands typed at the console are listed in input.rs.
 //
 // Note:
 //  1. This is a simple blocking approach that checks stdin in a separate thread.
 //  2. The user must press ENTER after typing 'p' for the toggle to pick up.
-//  3. This approach sleeps for 1 second per loop tick, so pause may take up to 1 second
-//     to register or unpause.
+//  3. Remaining time is computed from a monotonic clock (timer.rs, clock.rs), so the timer
+//     doesn't drift; time spent paused (including Wi-Fi toggling) is not counted.
 
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
+use chrono::{Local, NaiveDate, NaiveTime};
+use clap::{
+    ArgAction, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand,
+    parser::ValueSource,
+};
+use clap_complete::{ArgValueCompleter, CompleteEnv, Shell};
+#[cfg(unix)]
+use rust_pomo::signals;
+use rust_pomo::{
+    battery,
+    blockers::{self, AppAction, BlockerKind, Blockers, FailurePolicies, FailurePolicy, claims},
+    cleanup::{self, CleanupGuard},
+    clock, completions,
+    config::{self, Config},
+    daemon,
+    dry_run::{self, DryRunNotifier},
+    duration::{format_duration, parse_duration, parse_signed_duration, parse_time},
+    enforce::BreakEnforcement,
+    error::{EXIT_STOPPED, Error, Result},
+    export::{self, ExportFormat},
+    follow, git,
+    heatmap::Heatmap,
+    history,
+    inbox::{self, Note},
+    input::{self, Action, Prompt},
+    integrations::{Integrations, MediaMode, ShortcutSettings},
+    logging, mqtt,
+    notify::{self, Notifier, NotifyEvent},
+    output::{self, OutputFormat, Status},
+    plan::{self, Plan},
+    power::{self, IdleAction, IdleSettings},
+    profile::{self, Profile},
+    report::{self, DayReport},
+    runner::{self, AppState},
+    say,
+    schedule::Schedule,
+    server,
+    session::{PomodoroSession, cycle_of},
+    sound::Sound,
+    state, stats, statusline, timer, tray,
+    tui::{self, Dashboard},
+};
 use std::{
-    io::{BufRead, BufReader},
-    process::Command,
-    sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
-    },
+    io::{IsTerminal, Read},
+    net::SocketAddr,
+    sync::{Arc, atomic::Ordering},
     thread,
     time::Duration,
 };
 
 /// A simple Pomodoro-style focus timer
 #[derive(Debug, Parser)]
-#[command(name = "focus-timer")]
+#[command(name = "pomo")]
 struct Cli {
-    /// Focus time in seconds
-    #[arg(long, default_value_t = 1500)]
-    focus: u64,
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    timer: TimerArgs,
+
+    /// Output format: text, or one JSON event per line on stdout for scripts
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    output: OutputFormat,
+
+    /// Show a full-screen dashboard instead of the progress bar
+    #[arg(long, global = true, conflicts_with = "output")]
+    tui: bool,
+
+    /// No progress bar or keyboard input: a line of progress every minute
+    /// instead (the default when stdin or stdout isn't a terminal)
+    #[arg(long, global = true)]
+    non_interactive: bool,
 
-    /// Break time in seconds
-    #[arg(long, default_value_t = 300)]
-    break_time: u64,
+    /// Also show the remaining time and a menu in the system tray (needs pomo
+    /// built with the `tray` feature)
+    #[arg(long, global = true)]
+    tray: bool,
 
-    /// Number of focus/break cycles
-    #[arg(long, default_value_t = 1)]
-    cycles: u32,
+    /// Also show blocker commands, hook output and pauses (-vv: control requests too)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only show errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Run or control the session of this name, alongside the others (see `pomo list`)
+    #[arg(long, global = true, value_parser = state::parse_name)]
+    name: Option<String>,
 }
 
-/// Global application state
-struct AppState {
-    paused: AtomicBool,
+/// Lengths used when neither flags nor a schedule give them
+const DEFAULT_FOCUS: u64 = 25 * 60;
+const DEFAULT_BREAK: u64 = 5 * 60;
+
+/// Settings for a new session
+#[derive(Debug, Args)]
+struct TimerArgs {
+    /// Settings from a `[profiles.NAME]` table of the config file; flags given
+    /// here still take precedence
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(completions::profiles))]
+    profile: Option<String>,
+
+    /// Focus time (e.g. 25m, 1h30m, 90s; a bare number is seconds) [default: 25m]
+    #[arg(long, value_parser = parse_duration)]
+    focus: Option<u64>,
+
+    /// Break time (e.g. 5m, 90s; a bare number is seconds) [default: 5m]
+    #[arg(long, value_parser = parse_duration)]
+    break_time: Option<u64>,
+
+    /// Number of focus/break cycles; 0 keeps going until stopped [default: 1]
+    #[arg(long)]
+    cycles: Option<u32>,
+
+    /// Keep going (like --cycles 0) until this time of day (e.g. 18:00): no
+    /// phase starts that would end after it
+    #[arg(long, value_name = "HH:MM", value_parser = parse_time,
+          conflicts_with_all = ["cycles", "schedule", "until"])]
+    repeat_until: Option<NaiveTime>,
+
+    /// Make every Nth break this long (see --long-break-every, default 4), e.g. 15m
+    #[arg(long, value_parser = parse_duration)]
+    long_break: Option<u64>,
+
+    /// Which breaks are long: every Nth [default: 4]
+    #[arg(long, value_name = "N")]
+    long_break_every: Option<u32>,
+
+    /// Focus/break lengths per cycle, e.g. "50m/10m,50m/10m,25m/30m" (`3x50m/10m`
+    /// repeats an entry); replaces --focus, --break-time and --cycles
+    #[arg(long, conflicts_with_all = ["focus", "break_time", "cycles"])]
+    schedule: Option<Schedule>,
+
+    /// Focus until this time of day (e.g. 14:30, before a meeting), then end the
+    /// session; replaces --focus, --break-time, --cycles and --schedule
+    #[arg(long, value_name = "HH:MM", value_parser = parse_time,
+          conflicts_with_all = ["focus", "break_time", "cycles", "schedule"])]
+    until: Option<NaiveTime>,
+
+    /// Count down this long (e.g. 10m) before focus starts, to wrap up what
+    /// you're doing first; ENTER / `pomo resume` starts it sooner
+    #[arg(long = "in", value_name = "DURATION", value_parser = parse_duration)]
+    start_in: Option<u64>,
+
+    /// Distraction blockers to engage during focus (comma-separated) [default: wifi]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    block: Option<Vec<BlockerKind>>,
+
+    /// Never turn Wi-Fi off, even if `wifi` is in --block (for staying online)
+    #[arg(long)]
+    no_wifi: bool,
+
+    /// Also turn on macOS Do Not Disturb during focus (same as adding `dnd` to --block)
+    #[arg(long)]
+    dnd: bool,
+
+    /// Keep the system from sleeping during focus (same as adding `awake` to --block)
+    #[arg(long)]
+    keep_awake: bool,
+
+    /// What to do when a blocker fails: end the session (abort), carry on
+    /// without it (warn), or try again first (retry) [default: abort]
+    #[arg(long, value_enum)]
+    on_failure: Option<FailurePolicy>,
+
+    /// Sites to block with the hosts blocker (comma-separated; defaults to twitter.com,
+    /// youtube.com, reddit.com and a few others)
+    #[arg(long, value_delimiter = ',')]
+    sites: Vec<String>,
+
+    /// Apps for the apps blocker (comma-separated; defaults to Slack, Mail and Discord)
+    #[arg(long, value_delimiter = ',')]
+    apps: Vec<String>,
+
+    /// What the apps blocker does to the apps: quit (default) or hide
+    #[arg(long, value_enum)]
+    app_action: Option<AppAction>,
+
+    /// Start apps quit by the apps blocker again for breaks and at the end
+    #[arg(long)]
+    relaunch_apps: bool,
+
+    /// Network interface to turn off for the wifi blocker (e.g. en1, wlan0;
+    /// detected by default)
+    #[arg(long)]
+    wifi_interface: Option<String>,
+
+    /// Hosts, addresses or networks the firewall blocker keeps reachable, on
+    /// top of `[firewall] allow` (comma-separated, e.g. vpn.example.com,10.0.0.0/8)
+    #[arg(long, value_delimiter = ',')]
+    allow: Vec<String>,
+
+    /// Events that trigger a desktop notification (comma-separated; default: all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    notify: Option<Vec<NotifyEvent>>,
+
+    /// Turn off all notifications
+    #[arg(long, conflicts_with = "notify")]
+    no_notify: bool,
+
+    /// How long before the end of a phase to send the warning (e.g. 2m; 0 disables it)
+    #[arg(long, value_parser = parse_duration)]
+    warn_before: Option<u64>,
+
+    /// Play a sound with notifications
+    #[arg(long)]
+    notify_sound: bool,
+
+    /// Also announce phase starts, ends and warnings aloud (text-to-speech)
+    #[arg(long)]
+    speak: bool,
+
+    /// Sound played when a phase starts: none, bell, chime or a path to a sound file
+    #[arg(long)]
+    sound: Option<Sound>,
+
+    /// What you're working on (shown while running and recorded in the session log)
+    #[arg(long, add = ArgValueCompleter::new(completions::tasks))]
+    task: Option<String>,
+
+    /// Work through the planning queue (`pomo plan add`): each focus phase takes the
+    /// next task not done yet
+    #[arg(long, conflicts_with = "task")]
+    plan: bool,
+
+    /// Tag for the session; repeat or comma-separate for several
+    #[arg(long = "tag", value_delimiter = ',', add = ArgValueCompleter::new(completions::tags))]
+    tags: Vec<String>,
+
+    /// Record the git repository and branch of the current directory with each phase
+    /// (see `pomo stats --by-repo`)
+    #[arg(long)]
+    git: bool,
+
+    /// macOS Shortcut to run when focus starts (e.g. "Deep Work")
+    #[arg(long, value_name = "NAME")]
+    shortcut_on_focus: Option<String>,
+
+    /// macOS Shortcut to run when focus ends (break or end of session)
+    #[arg(long, value_name = "NAME")]
+    shortcut_after_focus: Option<String>,
+
+    /// Longest total pause per phase (e.g. 5m); a phase paused for longer is
+    /// abandoned and the session moves on
+    #[arg(long, value_parser = parse_duration)]
+    max_pause: Option<u64>,
+
+    /// Pause while the screen is locked and resume when it is unlocked
+    #[arg(long)]
+    pause_on_lock: bool,
+
+    /// Serve status and controls over HTTP on this address (e.g. 127.0.0.1:7878)
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<SocketAddr>,
+
+    /// Kill a hook command still running after this long (e.g. 30s)
+    #[arg(long, value_parser = parse_duration)]
+    hook_timeout: Option<u64>,
+
+    /// POST a JSON payload to this URL at every phase transition
+    #[arg(long, value_name = "URL")]
+    webhook_url: Option<String>,
+
+    /// Media player at phase transitions: pause it for focus, or play during focus
+    #[arg(long, value_enum)]
+    media: Option<MediaMode>,
+
+    /// Enforce breaks: lock the screen, or cover the terminal with the countdown
+    #[arg(long, value_enum)]
+    enforce_break: Option<BreakEnforcement>,
+
+    /// Count no keyboard/mouse input for this long during focus (e.g. 5m) as
+    /// being away: that time doesn't count as focus
+    #[arg(long, value_parser = parse_duration)]
+    idle_after: Option<u64>,
+
+    /// What to do once idle: pause until there is input again, or abandon the phase
+    #[arg(long, value_enum)]
+    on_idle: Option<IdleAction>,
+
+    /// Start each next phase immediately instead of waiting for ENTER / `pomo resume`
+    #[arg(long)]
+    auto_advance: bool,
+
+    /// When focus ends, keep counting overtime until ENTER / `pomo resume`
+    #[arg(long)]
+    overtime: bool,
+
+    /// Strict Pomodoro: a pause voids the pomodoro and focus starts over; a
+    /// skipped one is logged as void
+    #[arg(long)]
+    strict: bool,
+
+    /// Print what would be done (blockers, hooks, notifications, ...) instead
+    /// of doing it, and leave the session log alone
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Run the clock this many times faster than real time (dry runs only)
+    #[arg(long, value_name = "N", requires = "dry_run", value_parser = clap::value_parser!(u32).range(1..))]
+    time_scale: Option<u32>,
 }
 
-fn main() -> std::io::Result<()> {
-    // Set up SIGINT handler
-    ctrlc::set_handler(|| {
-        eprintln!("SIGINT received. Turning WiFi on and exiting.");
-        let _ = set_wifi_power(true);
-        std::process::exit(0);
-    })
-    .expect("Failed to set SIGINT handler.");
+impl TimerArgs {
+    // Build a fresh session from these flags, falling back to the profile and
+    // then the rest of the config file
+    fn new_session(&self, config: &Config) -> Result<PomodoroSession> {
+        let default_profile = Profile::default();
+        let profile = match &self.profile {
+            Some(name) => profile::find(&config.profiles, name)?,
+            None => &default_profile,
+        };
 
-    // Parse CLI args
-    let cli = Cli::parse();
+        // A schedule from the profile or config file applies unless lengths
+        // are given at a higher level
+        let uniform = self.focus.is_some()
+            || self.break_time.is_some()
+            || self.cycles.is_some()
+            || self.repeat_until.is_some();
+        let profile_uniform =
+            profile.focus.is_some() || profile.break_time.is_some() || profile.cycles.is_some();
+        let schedule = self
+            .schedule
+            .clone()
+            .or_else(|| profile.schedule.clone().filter(|_| !uniform))
+            .or_else(|| {
+                config
+                    .schedule
+                    .clone()
+                    .filter(|_| !uniform && !profile_uniform)
+            });
+        let mut session = match (self.until, schedule) {
+            (Some(time), _) => {
+                let now = Local::now();
+                let deadline = timer::deadline_today(time, now).ok_or(Error::TimePassed(time))?;
+                let length = timer::length_until(deadline, now, Duration::ZERO);
+                let mut session = PomodoroSession::new(length.as_secs(), 0, 1);
+                session.until = Some(deadline);
+                session
+            }
+            (None, Some(schedule)) => PomodoroSession::with_schedule(schedule),
+            (None, None) => PomodoroSession::new(
+                self.focus.or(profile.focus).unwrap_or(DEFAULT_FOCUS),
+                self.break_time
+                    .or(profile.break_time)
+                    .unwrap_or(DEFAULT_BREAK),
+                self.cycles.or(profile.cycles).unwrap_or(1),
+            ),
+        };
+        if let Some(time) = self.repeat_until {
+            let deadline =
+                timer::deadline_today(time, Local::now()).ok_or(Error::TimePassed(time))?;
+            session.cycles = 0;
+            session.repeat_until = Some(deadline);
+        }
+        session.long_break = self.long_break.or(config.long_break);
+        session.long_break_every = self
+            .long_break_every
+            .or(config.long_break_every)
+            .unwrap_or(session.long_break_every);
 
-    // Shared state for pause toggling
-    let app_state = Arc::new(AppState {
-        paused: AtomicBool::new(false),
-    });
+        session.block = self
+            .block
+            .clone()
+            .or_else(|| profile.block.clone())
+            .unwrap_or_else(|| vec![BlockerKind::Wifi]);
+        if self.dnd && !session.block.contains(&BlockerKind::Dnd) {
+            session.block.push(BlockerKind::Dnd);
+        }
+        if self.keep_awake && !session.block.contains(&BlockerKind::Awake) {
+            session.block.push(BlockerKind::Awake);
+        }
+        if self.no_wifi || config.no_wifi {
+            session.block.retain(|&kind| kind != BlockerKind::Wifi);
+        }
+        session.blocker_options.sites = first_given(&[&self.sites, &profile.sites]);
+        session.blocker_options.wifi_interface = self.wifi_interface.clone();
+        session.blocker_options.apps =
+            first_given(&[&self.apps, &profile.apps, &config.apps.names]);
+        session.blocker_options.app_action =
+            self.app_action.or(config.apps.action).unwrap_or_default();
+        session.blocker_options.relaunch_apps = self.relaunch_apps || config.apps.relaunch;
+        session.blocker_options.firewall = config.firewall.clone();
+        session.blocker_options.awake_on_battery = config.battery.keep_awake;
+        session.blocker_options.on_failure = match self.on_failure {
+            Some(policy) => FailurePolicies::all(policy),
+            None => config.on_failure.clone(),
+        };
+        session
+            .blocker_options
+            .firewall
+            .allow
+            .extend(self.allow.iter().cloned());
 
-    // Spawn a thread to listen for 'p' to toggle pause
-    {
+        session.notify = config.notifications.clone();
+        if let Some(events) = &self.notify {
+            session.notify.events = events.clone();
+        }
+        if self.no_notify {
+            session.notify.events.clear();
+        }
+        if let Some(warn_before) = self.warn_before {
+            session.notify.warn_before = warn_before;
+        }
+        if self.notify_sound {
+            session.notify.sound = true;
+        }
+        if self.speak {
+            session.notify.speak = true;
+        }
+
+        session.sound = self
+            .sound
+            .clone()
+            .or_else(|| profile.sound.clone())
+            .or_else(|| config.sound.clone())
+            .unwrap_or_default();
+
+        session.task = self.task.clone().or_else(|| profile.task.clone());
+        session.plan = self.plan;
+        session.tags = first_given(&[&self.tags, &profile.tags]);
+        if self.git || config.git {
+            session.git = git::current();
+        }
+        session.theme = config.theme.clone();
+        session.progress = config.progress.clone();
+        session.auto_advance = self.auto_advance || config.auto_advance;
+        session.overtime = self.overtime || config.overtime;
+        session.strict = self.strict || config.strict;
+        session.daily_goal = config.daily_goal;
+        session.max_pause = self.max_pause;
+        session.pause_on_lock = self.pause_on_lock;
+        session.serve = self.serve.or(config.serve);
+        session.webhook_url = self
+            .webhook_url
+            .clone()
+            .or_else(|| config.webhook.url.clone());
+        session.media = self.media.or(config.media).unwrap_or_default();
+        session.enforce_break = self.enforce_break.or(config.enforce_break);
+        session.idle = IdleSettings {
+            after: self.idle_after.unwrap_or(config.idle.after),
+            action: self.on_idle.unwrap_or(config.idle.action),
+        };
+        session.hooks = profile.hooks_over(&config.hooks);
+        session.hooks.timeout = self.hook_timeout.or(session.hooks.timeout);
+        session.shortcuts = ShortcutSettings {
+            on_focus: self
+                .shortcut_on_focus
+                .clone()
+                .or_else(|| config.shortcuts.on_focus.clone()),
+            after_focus: self
+                .shortcut_after_focus
+                .clone()
+                .or_else(|| config.shortcuts.after_focus.clone()),
+        };
+        session.start_in = self.start_in.filter(|&seconds| seconds > 0);
+        session.dry_run = self.dry_run;
+        session.time_scale = self.time_scale.unwrap_or(1);
+        Ok(session)
+    }
+}
+
+// The first list that isn't empty
+fn first_given(lists: &[&Vec<String>]) -> Vec<String> {
+    lists
+        .iter()
+        .find(|list| !list.is_empty())
+        .map(|list| list.to_vec())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Start a session in the background, detached from the terminal
+    Start(TimerArgs),
+    /// Pause the running session
+    Pause {
+        /// Why (e.g. "phone call"), kept with the pause in the session log;
+        /// for a session already paused, only notes the reason
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Unpause the running session, or resume the last interrupted session
+    /// from the state file if none is running
+    Resume,
+    /// Skip to the next phase of the running session
+    Skip,
+    /// Stop the running session and release all blockers
+    Stop,
+    /// Lengthen the running phase (e.g. 5m), or shorten it (e.g. -5m)
+    Extend {
+        #[arg(allow_hyphen_values = true, value_parser = parse_signed_duration)]
+        by: i64,
+    },
+    /// Put off the phase waiting to start (default 5m), with a countdown
+    Snooze {
+        #[arg(value_parser = parse_duration)]
+        by: Option<u64>,
+    },
+    /// Show the state of the running session
+    Status {
+        /// Print the state as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a one-line summary (e.g. `🍅 12:34 focus (2/4)`) for tmux or a shell prompt
+    Statusline,
+    /// Stream the running session's events as JSON lines (a tick every second) until it ends
+    Follow {
+        /// Wait for a session to start, and keep following from one session to the next
+        #[arg(long)]
+        wait: bool,
+    },
+    /// List the running sessions (named with --name) and where each one is
+    List,
+    /// Show focus time from the session log, by task and tag
+    Stats {
+        /// Also break it down by git repository and branch (sessions run with --git)
+        #[arg(long)]
+        by_repo: bool,
+        /// Show a calendar of focus time per day and a histogram per hour of the day
+        #[arg(long, conflicts_with = "by_repo")]
+        heatmap: bool,
+    },
+    /// Print the end-of-day report: pomodoros, focus time, streak, top tasks
+    Report {
+        /// The day to report on (YYYY-MM-DD, today or yesterday)
+        #[arg(long, value_parser = export::parse_date)]
+        date: Option<NaiveDate>,
+    },
+    /// Write the session log as CSV or JSON (one row per phase) to stdout
+    Export {
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        /// Completed focus phases as calendar events (same as --format ics)
+        #[arg(long, conflicts_with = "format")]
+        ics: bool,
+        /// First day to include (YYYY-MM-DD, today or yesterday)
+        #[arg(long, value_parser = export::parse_date)]
+        from: Option<NaiveDate>,
+        /// Last day to include (YYYY-MM-DD, today or yesterday)
+        #[arg(long, value_parser = export::parse_date)]
+        to: Option<NaiveDate>,
+    },
+    /// List the profiles in the config file
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Note a thought for later in the inbox, without stopping the timer
+    Note {
+        #[arg(required = true, trailing_var_arg = true)]
+        text: Vec<String>,
+    },
+    /// Show the notes in the inbox (`i` or `pomo note` during a session)
+    Inbox {
+        /// Empty the inbox
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Queue tasks with estimates, for `pomo --plan`
+    Plan {
+        #[command(subcommand)]
+        command: PlanCommand,
+    },
+    /// Print the completion script for a shell (bash, zsh, fish, ...)
+    Completions { shell: Shell },
+    /// Encrypt the session log, inbox and plan written before `[encryption]` was set up
+    EncryptLog,
+    /// Show the running session in the system tray until it ends (what --tray starts)
+    Tray,
+    /// Run a session as the background daemon (used by `start`)
+    #[command(hide = true)]
+    Daemon(TimerArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum PlanCommand {
+    /// Add a task to the end of the queue
+    Add {
+        task: String,
+        /// Pomodoros it should take
+        #[arg(long, default_value_t = 1)]
+        estimate: u32,
+    },
+    /// Show the queue, with estimated and actual pomodoros
+    List,
+    /// Mark a task done: the next one, or the one with this number in `pomo plan list`
+    Done { number: Option<usize> },
+    /// Take the task with this number in `pomo plan list` off the queue
+    Remove { number: usize },
+    /// Empty the queue
+    Clear,
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCommand {
+    /// Show each profile's name and settings
+    List,
+}
+
+fn main() {
+    // Answer the completion script (COMPLETE=<shell> pomo ...) and exit
+    CompleteEnv::with_factory(Cli::command)
+        .var(completions::COMPLETE_VAR)
+        .complete();
+
+    // Parse CLI args, keeping what was given for `pomo start` to pass on
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = run(cli, &matches) {
+        eprintln!("pomo: {}", e);
+        if let Some(hint) = e.hint() {
+            eprintln!("  hint: {}", hint);
+        }
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(cli: Cli, matches: &ArgMatches) -> Result<()> {
+    output::set_format(cli.output);
+    logging::init(cli.verbose, cli.quiet);
+    state::set_name(cli.name.clone());
+
+    let (session, interactive, config) = match cli.command {
+        Some(Commands::Start(timer)) => {
+            // Catch an unknown profile (or a log that can't be unlocked) here
+            // rather than in the daemon's log
+            let config = config::load()?;
+            let session = timer.new_session(&config)?;
+            history::unlock()?;
+            battery::check(&session, &config.battery);
+            // A dry run blocks nothing, so there is nothing to check
+            if !session.dry_run {
+                preflight(
+                    &session,
+                    &Blockers::new(&session.block, &session.blocker_options)?,
+                )?;
+            }
+            if cli.tray {
+                tray::supported()?;
+            }
+            // Hand the flags to a detached copy of ourselves
+            let pid = daemon::spawn(&daemon_args(matches))?;
+            println!(
+                "Started {} in the background (pid {}). Log: {}",
+                match &cli.name {
+                    Some(name) => format!("session '{}'", name),
+                    None => "session".to_string(),
+                },
+                pid,
+                daemon::log_path().display()
+            );
+            return Ok(());
+        }
+        Some(Commands::Pause { reason }) => {
+            return match reason.as_deref().map(str::trim) {
+                Some(reason) if !reason.is_empty() => send_command(&format!("pause {}", reason)),
+                _ => send_command("pause"),
+            };
+        }
+        Some(Commands::Skip) => return send_command("skip"),
+        Some(Commands::Stop) => return send_command("stop"),
+        Some(Commands::Extend { by }) => return send_command(&format!("extend {}", by)),
+        Some(Commands::Snooze { by }) => {
+            return match by {
+                Some(by) => send_command(&format!("snooze {}s", by)),
+                None => send_command("snooze"),
+            };
+        }
+        Some(Commands::Status { json: true }) => return print_status_json(),
+        Some(Commands::Status { json: false }) => return send_command("status"),
+        Some(Commands::Statusline) => return print_statusline(),
+        Some(Commands::Follow { wait }) => return follow::run(wait),
+        Some(Commands::Tray) => return tray::run(),
+        Some(Commands::List) => return list_sessions(),
+        Some(Commands::Stats { heatmap: true, .. }) => {
+            let records = history::load()?;
+            let heatmap = Heatmap::of(&records, Local::now().date_naive());
+            if heatmap.is_empty() {
+                println!("No focus sessions recorded yet.");
+            } else {
+                println!("{}", heatmap);
+            }
+            return Ok(());
+        }
+        Some(Commands::Stats { by_repo, .. }) => {
+            stats::print_stats(&history::load()?, config::load()?.daily_goal, by_repo);
+            return Ok(());
+        }
+        Some(Commands::Report { date }) => {
+            let date = date.unwrap_or_else(|| Local::now().date_naive());
+            println!("{}", DayReport::of(&history::load()?, date));
+            return Ok(());
+        }
+        Some(Commands::Export {
+            format,
+            ics,
+            from,
+            to,
+        }) => {
+            let format = if ics { ExportFormat::Ics } else { format };
+            let records = history::load()?;
+            let selected = export::filter(&records, from, to);
+            export::write(&mut std::io::stdout().lock(), &selected, format)?;
+            return Ok(());
+        }
+        Some(Commands::Profile {
+            command: ProfileCommand::List,
+        }) => return list_profiles(),
+        Some(Commands::Plan { command }) => return edit_plan(command),
+        Some(Commands::Note { text }) => return add_note(&text.join(" ")),
+        Some(Commands::Inbox { clear: true }) => {
+            inbox::clear()?;
+            println!("The inbox is empty.");
+            return Ok(());
+        }
+        Some(Commands::Inbox { clear: false }) => {
+            let notes = inbox::load()?;
+            if notes.is_empty() {
+                println!("The inbox is empty. Note things down with `i` during a session.");
+            }
+            for note in notes {
+                println!("{}", note);
+            }
+            return Ok(());
+        }
+        Some(Commands::EncryptLog) => {
+            let lines = history::encrypt_all()?;
+            let notes = inbox::encrypt()?;
+            let queue = Plan::encrypt()?;
+            if lines > 0 {
+                println!(
+                    "Encrypted {} line{} of {}.",
+                    lines,
+                    if lines == 1 { "" } else { "s" },
+                    history::history_path().display()
+                );
+            }
+            if notes > 0 {
+                println!(
+                    "Encrypted {} note{} in {}.",
+                    notes,
+                    if notes == 1 { "" } else { "s" },
+                    inbox::inbox_path().display()
+                );
+            }
+            if queue {
+                println!("Encrypted {}.", plan::plan_path().display());
+            }
+            if lines == 0 && notes == 0 && !queue {
+                println!("The session log, inbox and plan have nothing left to encrypt.");
+            }
+            return Ok(());
+        }
+        Some(Commands::Completions { shell }) => {
+            completions::write_script(shell, &mut std::io::stdout().lock())?;
+            return Ok(());
+        }
+        Some(Commands::Resume) if daemon::is_running() => return send_command("resume"),
+        Some(Commands::Resume) => match state::load()? {
+            Some(mut session) => {
+                let config = config::load()?;
+                if config.no_wifi {
+                    session.block.retain(|&kind| kind != BlockerKind::Wifi);
+                }
+                let away = state::reconcile(&mut session);
+                let away = if away > 0 {
+                    format!(", {} after it stopped", format_duration(away))
+                } else {
+                    String::new()
+                };
+                say!(
+                    "Resuming cycle {} ({:?}) at {}s{}",
+                    cycle_of(session.cycle, session.cycles),
+                    session.phase,
+                    session.elapsed,
+                    away
+                );
+                (session, true, config)
+            }
+            None => {
+                eprintln!("No interrupted session found.");
+                return Ok(());
+            }
+        },
+        Some(Commands::Daemon(timer)) => {
+            let config = config::load()?;
+            (timer.new_session(&config)?, false, config)
+        }
+        None => {
+            let config = config::load()?;
+            (cli.timer.new_session(&config)?, true, config)
+        }
+    };
+
+    // Cron jobs, scripts and pipes get lines of progress instead of a bar,
+    // and nothing reads the keyboard
+    output::set_interactive(
+        interactive
+            && !cli.non_interactive
+            && std::io::stdin().is_terminal()
+            && std::io::stdout().is_terminal(),
+    );
+    if cli.tui && interactive && !output::is_interactive() {
+        log::warn!("--tui needs a terminal; showing progress in lines instead");
+    }
+    // `pomo start` checked the battery before going to the background
+    if interactive {
+        battery::check(&session, &config.battery);
+    }
+
+    // A resumed dry run stays dry
+    if session.dry_run {
+        dry_run::enable();
+        say!("Dry run: nothing is blocked, run or logged; actions are printed instead.");
+    } else {
+        history::unlock()?;
+    }
+    let notifier: Box<dyn Notifier> = if session.dry_run {
+        Box::new(DryRunNotifier)
+    } else {
+        notify::system_notifier()
+    };
+
+    let blockers = Blockers::new(&session.block, &session.blocker_options)?;
+    // `pomo start` checked them before going to the background
+    if interactive {
+        preflight(&session, &blockers)?;
+    }
+
+    // Shared state for pause toggling and remote control
+    let app_state = Arc::new(
+        AppState::new(blockers, notifier, Integrations::new(&config, &session)?)
+            .with_clock(clock::with_scale(session.time_scale)),
+    );
+
+    // Set up SIGINT/SIGTERM/SIGHUP handler (Ctrl+C and console close on Windows)
+    cleanup::handle_signals(Arc::clone(&app_state)).expect("Failed to set signal handler.");
+
+    // SIGUSR1 toggles pause, SIGUSR2 skips
+    #[cfg(unix)]
+    signals::handle_control_signals(Arc::clone(&app_state))
+        .expect("Failed to set SIGUSR1/SIGUSR2 handlers.");
+
+    // Accept commands from `pomo pause/resume/skip/stop/status`
+    daemon::listen(Arc::clone(&app_state))?;
+    if cli.tray {
+        tray::spawn()?;
+    }
+    if let Some(addr) = session.serve {
+        server::serve(addr, config.serve_token.clone(), Arc::clone(&app_state))?;
+    }
+    if let Some(mqtt) = &config.mqtt {
+        if session.dry_run {
+            dry_run::action(format!("publish state to {}", mqtt.broker));
+        } else {
+            mqtt::publish_state(mqtt, Arc::clone(&app_state))?;
+        }
+    }
+
+    // From here on, blockers are released and the socket removed on any way
+    // out of main, including errors and panics
+    let _cleanup = CleanupGuard::new(Arc::clone(&app_state));
+
+    // Unless another session has the hosts blocker engaged, any blocked sites
+    // left in the hosts file are from one that crashed
+    if session.dry_run {
+        dry_run::action("remove stale entries from the hosts file");
+    } else if !claims::others("hosts").is_empty() {
+        log::debug!("the hosts file is in use by another session");
+    } else if let Err(e) = blockers::remove_stale_entries() {
+        if aborts(&session, BlockerKind::Hosts) {
+            return Err(Error::blocker("hosts")(e));
+        }
+        log::warn!("Could not clean up the hosts file: {}", e);
+    }
+    // Likewise firewall rules, only looked for when the blocker is used (it
+    // needs root)
+    if session.block.contains(&BlockerKind::Firewall) {
+        if session.dry_run {
+            dry_run::action("remove stale firewall rules");
+        } else if claims::others("firewall").is_empty()
+            && let Err(e) = blockers::remove_stale_rules()
+        {
+            if aborts(&session, BlockerKind::Firewall) {
+                return Err(Error::blocker("firewall")(e));
+            }
+            log::warn!("Could not remove old firewall rules: {}", e);
+        }
+    }
+
+    if session.pause_on_lock {
+        power::watch_screen_lock(Arc::clone(&app_state));
+    }
+    if let Some(at) = config.report_at {
+        report::deliver_at(at, session.clone(), Arc::clone(&app_state));
+    }
+
+    // The dashboard reads keys itself; it is dropped (restoring the terminal)
+    // before the cleanup guard runs, so cleanup messages land on the terminal
+    let _dashboard = if cli.tui && output::is_interactive() {
+        Some(Dashboard::start(Arc::clone(&app_state))?)
+    } else {
+        None
+    };
+
+    // Spawn a thread to listen for 'p' to toggle pause, or ENTER to start the next phase
+    if output::is_interactive() && !cli.tui {
         let app_state_clone = Arc::clone(&app_state);
         thread::spawn(move || {
-            let stdin = std::io::stdin();
-            let reader = BufReader::new(stdin);
-
-            for line in reader.lines() {
-                match line {
-                    Ok(cmd) => {
-                        if cmd.trim() == "p" {
-                            // Toggle paused
-                            let currently_paused = app_state_clone.paused.load(Ordering::SeqCst);
-                            let new_state = !currently_paused;
-                            app_state_clone.paused.store(new_state, Ordering::SeqCst);
-
-                            println!(
-                                "Pause toggled to {}",
-                                if new_state { "PAUSED" } else { "RUNNING" }
+            // Read byte by byte: the break overlay takes keys one at a time
+            let mut line = Vec::new();
+            // The question (after a bare 'p' or 'i') the next line answers
+            let mut prompt = None;
+            for byte in std::io::stdin().lock().bytes() {
+                let byte = match byte {
+                    Ok(byte) => byte,
+                    Err(e) => {
+                        log::warn!("Error reading input: {}", e);
+                        break;
+                    }
+                };
+                if tui::overlay_key(&app_state_clone, byte) {
+                    line.clear();
+                    continue;
+                }
+                if byte != b'\n' {
+                    line.push(byte);
+                    continue;
+                }
+                let cmd = String::from_utf8_lossy(&std::mem::take(&mut line)).into_owned();
+                let waiting = app_state_clone.waiting.load(Ordering::SeqCst);
+                match input::action(&cmd, prompt.take(), waiting) {
+                    Action::Reason(reason) => {
+                        if app_state_clone.paused.load(Ordering::SeqCst) {
+                            *app_state_clone.pause_reason.lock().unwrap() = Some(reason);
+                        }
+                    }
+                    Action::Note(note) => take_note(&app_state_clone, &note),
+                    Action::AskNote => {
+                        say!("Note: (type it and press ENTER; the timer keeps running)");
+                        prompt = Some(Prompt::Note);
+                    }
+                    Action::TogglePause(reason) => {
+                        let asked = reason.is_none();
+                        if app_state_clone.paused.swap(false, Ordering::SeqCst) {
+                            log::debug!("pause toggled to RUNNING");
+                        } else if !app_state_clone.pause(reason) {
+                            say!("Nothing to pause: the next phase is waiting to start.");
+                        } else if asked {
+                            say!("Paused. Why? (type a reason and press ENTER, or just ENTER)");
+                            prompt = Some(Prompt::Reason);
+                        }
+                    }
+                    Action::Stop => {
+                        say!("Stopping the session.");
+                        app_state_clone.stop.store(true, Ordering::SeqCst);
+                    }
+                    Action::Done => finish_planned_task(&app_state_clone),
+                    Action::Snooze(by) => {
+                        if !app_state_clone.snooze(by) {
+                            say!(
+                                "Nothing to snooze: snooze while the next phase is waiting to start."
                             );
                         }
                     }
-                    Err(e) => {
-                        eprintln!("Error reading input: {}", e);
+                    Action::StartNext => {
+                        app_state_clone.waiting.store(false, Ordering::SeqCst);
+                    }
+                    Action::Extend(by) => {
+                        app_state_clone.extend(by);
                     }
+                    Action::Ignore => {}
                 }
             }
         });
     }
 
-    for cycle in 1..=cli.cycles {
-        println!("=== Cycle {}/{}: Focus time ===", cycle, cli.cycles);
+    runner::run(session, &app_state)?;
 
-        // Turn WiFi off for focus
-        set_wifi_power(false)?;
+    // Stopped on purpose (`pomo stop` or q): say so in the exit status, once
+    // the terminal and blockers are back to normal
+    if app_state.stop.load(Ordering::SeqCst) && !app_state.interrupted.load(Ordering::SeqCst) {
+        drop(_dashboard);
+        drop(_cleanup);
+        std::process::exit(EXIT_STOPPED);
+    }
+    Ok(())
+}
 
-        // Run focus timer
-        run_timer(cli.focus, true, Arc::clone(&app_state));
+// `i ...` typed at the terminal: a note for the inbox
+fn take_note(app_state: &AppState, text: &str) {
+    match app_state.note(text) {
+        Ok(()) => say!("Noted; it will be shown at the break."),
+        Err(e) => log::warn!("Failed to write to the inbox: {}", e),
+    }
+}
 
-        println!("=== Break time ===");
+// `done` typed at the terminal: the task of a --plan session is finished
+fn finish_planned_task(app_state: &AppState) {
+    let session = app_state.session.lock().unwrap().clone();
+    let Some(task) = session
+        .filter(|session| session.plan)
+        .and_then(|session| session.task)
+    else {
+        return;
+    };
+    match plan::finish_task(&task) {
+        Ok(Some(next)) => say!("\"{}\" is done. Next up: \"{}\".", task, next),
+        Ok(None) => say!("\"{}\" is done, and so is the whole plan.", task),
+        Err(e) => log::warn!("Failed to update the plan: {}", e),
+    }
+}
 
-        // Turn WiFi on for break
-        set_wifi_power(true)?;
+// `pomo note ...`: through the running session, which adds its task and
+// shows the note again at the break, or straight into the inbox
+fn add_note(text: &str) -> Result<()> {
+    if daemon::is_running() {
+        return send_command(&format!("note {}", text));
+    }
+    inbox::add(&Note::new(text, None))?;
+    println!("Noted.");
+    Ok(())
+}
 
-        // Run break timer
-        run_timer(cli.break_time, false, Arc::clone(&app_state));
+// `pomo plan ...`: change or show the planning queue
+fn edit_plan(command: PlanCommand) -> Result<()> {
+    let mut queue = Plan::load()?;
+    match command {
+        PlanCommand::Add { task, estimate } => {
+            queue.add(task, estimate);
+            queue.save()?;
+            println!("{}", queue);
+        }
+        PlanCommand::List if queue.tasks.is_empty() => {
+            println!("The plan is empty. Add tasks with `pomo plan add \"task\" --estimate 2`.");
+        }
+        PlanCommand::List => println!("{}", queue),
+        PlanCommand::Done { number } => {
+            let number = number.or(queue.next().map(|i| i + 1));
+            match number {
+                Some(number) if queue.finish(number) => {}
+                Some(number) => return Err(Error::NotInPlan(number)),
+                None => {
+                    println!("Everything in the plan is done already.");
+                    return Ok(());
+                }
+            }
+            queue.save()?;
+            println!("{}", queue);
+        }
+        PlanCommand::Remove { number } => {
+            if queue.remove(number).is_none() {
+                return Err(Error::NotInPlan(number));
+            }
+            queue.save()?;
+            println!("{}", queue);
+        }
+        PlanCommand::Clear => {
+            Plan::default().save()?;
+            println!("The plan is empty.");
+        }
+    }
+    Ok(())
+}
 
-        // Send notification at cycle end
-        send_notification("Focus Timer", &format!("Cycle {} finished!", cycle))?;
+// The flags of `pomo start` for the background session: every one given on
+// the command line, before `start` (`pomo -v --name emails start`) or after
+// it, as parsed
+fn daemon_args(matches: &ArgMatches) -> Vec<String> {
+    let Some(("start", start)) = matches.subcommand() else {
+        return Vec::new();
+    };
+    let mut command = Cli::command();
+    command.build();
+    let Some(start_command) = command.find_subcommand("start") else {
+        return Vec::new();
+    };
+    let mut args = Vec::new();
+    for arg in start_command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        if start.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        match arg.get_action() {
+            ArgAction::SetTrue => args.push(format!("--{}", long)),
+            ArgAction::Count => {
+                for _ in 0..start.get_count(id) {
+                    args.push(format!("--{}", long));
+                }
+            }
+            _ => {
+                for value in start.get_raw(id).into_iter().flatten() {
+                    args.push(format!("--{}={}", long, value.to_string_lossy()));
+                }
+            }
+        }
     }
+    args
+}
 
-    // Ensure WiFi is on at the end
-    set_wifi_power(true)?;
-    println!("All cycles finished!");
+// Forward a control command to the running session and print its reply
+fn send_command(command: &str) -> Result<()> {
+    let reply = daemon::send(command)?;
+    match reply.strip_prefix("error: ") {
+        Some(message) => Err(Error::Refused(message.to_string())),
+        None => {
+            println!("{}", reply);
+            Ok(())
+        }
+    }
+}
 
+// `pomo status --json`: scripts polling for the state get `{"running":false}`
+// rather than an error when no session is running
+fn print_status_json() -> Result<()> {
+    match daemon::send("status-json") {
+        Ok(reply) => println!("{}", reply),
+        Err(_) => println!("{}", Status::not_running().to_json()),
+    }
     Ok(())
 }
 
-// Turn WiFi on/off on macOS
-fn set_wifi_power(on: bool) -> std::io::Result<()> {
-    let status = if on { "on" } else { "off" };
-    println!("Setting WiFi {}", status);
-
-    Command::new("networksetup")
-        .args(["-setairportpower", "en0", status])
-        .status()?;
+// `pomo list`: each running session with its status line
+fn list_sessions() -> Result<()> {
+    let names = daemon::running_sessions();
+    if names.is_empty() {
+        println!("No sessions running.");
+        return Ok(());
+    }
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for name in &names {
+        let status = daemon::send_to(name, "status").unwrap_or_else(|e| e.to_string());
+        println!("{:width$}  {}", name, status, width = width);
+    }
     Ok(())
 }
 
-// Show a countdown in the console using indicatif, checking for pause state
-fn run_timer(seconds: u64, focus_mode: bool, app_state: Arc<AppState>) {
-    // focus_mode = true => WiFi should be off when not paused
-    // focus_mode = false => WiFi should be on when not paused
-
-    println!("Starting timer for {seconds} seconds... (Type 'p' + ENTER to pause)");
-
-    let pb = ProgressBar::new(seconds);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{bar:40.cyan/blue}] {pos}s / {len}s")
-            .unwrap()
-            .progress_chars("##-"),
-    );
+// `pomo profile list`
+fn list_profiles() -> Result<()> {
+    let config = config::load()?;
+    if config.profiles.is_empty() {
+        println!("No profiles in {}", config::config_path().display());
+        return Ok(());
+    }
+    let width = config.profiles.keys().map(String::len).max().unwrap_or(0);
+    for (name, profile) in &config.profiles {
+        println!("{:width$}  {}", name, profile.summary(), width = width);
+    }
+    Ok(())
+}
 
-    let mut elapsed = 0;
-    while elapsed < seconds {
-        // If paused, keep WiFi ON if we are in focus mode
-        if app_state.paused.load(Ordering::SeqCst) {
-            if focus_mode {
-                let _ = set_wifi_power(true);
-            }
-            // Wait in paused state until unpaused
-            while app_state.paused.load(Ordering::SeqCst) {
-                thread::sleep(Duration::from_millis(500));
-            }
-            // Once unpaused, if focus_mode, turn WiFi off again
-            if focus_mode {
-                let _ = set_wifi_power(false);
-            }
-        }
+// Whether the session uses the blocker and ends when it fails
+fn aborts(session: &PomodoroSession, kind: BlockerKind) -> bool {
+    session.block.contains(&kind)
+        && session.blocker_options.on_failure.of(kind) == FailurePolicy::Abort
+}
 
-        pb.set_position(elapsed);
-        thread::sleep(Duration::from_secs(1));
-        elapsed += 1;
+// Check the blockers, notifications and sound before the first focus starts,
+// rather than finding out halfway (a dry run has nothing to check)
+fn preflight(session: &PomodoroSession, blockers: &Blockers) -> Result<()> {
+    if session.dry_run {
+        return Ok(());
+    }
+    blockers.preflight()?;
+    if !session.notify.events.is_empty()
+        && let Err(e) = notify::system_notifier().check()
+    {
+        warn_of(Error::Notification(e));
     }
-    pb.finish_with_message("Done!");
+    if let Err(e) = session.sound.check() {
+        warn_of(Error::Sound(e));
+    }
+    Ok(())
 }
 
-// Show notification on macOS
-fn send_notification(title: &str, message: &str) -> std::io::Result<()> {
-    let script = format!(
-        "display notification \"{}\" with title \"{}\"",
-        message, title
-    );
+// Report a problem that doesn't end the session, with its hint
+fn warn_of(error: Error) {
+    log::warn!("{}", error);
+    if let Some(hint) = error.hint() {
+        log::warn!("  hint: {}", hint);
+    }
+}
 
-    Command::new("osascript").arg("-e").arg(script).status()?;
+// `pomo statusline`: the running session if there is one, else an interrupted
+// one from the state file, else nothing. Errors print nothing too, so a status
+// bar never shows an error message.
+fn print_statusline() -> Result<()> {
+    let line = match daemon::send("statusline") {
+        Ok(line) => line,
+        Err(_) => match state::load() {
+            Ok(Some(session)) => statusline::render(&session, Some("interrupted")),
+            _ => String::new(),
+        },
+    };
+    if !line.is_empty() {
+        println!("{}", line);
+    }
     Ok(())
 }
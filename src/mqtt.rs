@@ -0,0 +1,326 @@
+// Timer state published to an MQTT broker, for Stream Deck buttons and home
+// automation dashboards:
+//
+//   [mqtt]
+//   broker = "mqtt://192.168.1.10:1883"
+//   username = "pomo"
+//   password = "..."          # or the POMO_MQTT_PASSWORD environment variable
+//   topic_prefix = "pomo"
+//
+// Retained messages under the prefix, each published when its value changes:
+//
+//   pomo/state        the session as JSON (as `pomo status --json`)
+//   pomo/phase        focus | break
+//   pomo/remaining    seconds left in the phase
+//   pomo/paused       true | false
+//   pomo/statusline   the compact line of `pomo statusline` ("🍅 12:34 focus (1/4)")
+//
+// The connection's last will sets pomo/state to {"running":false}, so the
+// broker clears it however the session ends. This is a minimal MQTT 3.1.1
+// client (QoS 0 only, no TLS); when the broker can't be reached the timer
+// carries on and it tries again a little later.
+
+use crate::{daemon, runner::AppState};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{Arc, atomic::Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Environment variable that can hold the password instead of the config file
+const PASSWORD_ENV: &str = "POMO_MQTT_PASSWORD";
+
+const DEFAULT_PORT: u16 = 1883;
+
+/// How often the state is checked for changes
+const POLL: Duration = Duration::from_millis(500);
+
+/// Keep-alive interval announced to the broker; a ping goes out if nothing
+/// else has been sent for half of it
+const KEEP_ALIVE: u16 = 60;
+
+/// Wait before reconnecting after the connection failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(15);
+
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `[mqtt]` table of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttSettings {
+    /// mqtt://host:port (the port defaults to 1883)
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        MqttSettings {
+            broker: String::new(),
+            username: None,
+            password: None,
+            topic_prefix: "pomo".to_string(),
+        }
+    }
+}
+
+// Start publishing the session's state in the background
+pub fn publish_state(settings: &MqttSettings, app_state: Arc<AppState>) -> io::Result<()> {
+    let address = broker_address(&settings.broker)?;
+    let mut settings = settings.clone();
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        settings.password = Some(password);
+    }
+    let prefix = settings.topic_prefix.trim_end_matches('/').to_string();
+
+    thread::spawn(move || {
+        loop {
+            if let Err(e) = run(&address, &settings, &prefix, &app_state) {
+                log::warn!("MQTT ({}): {}", address, e);
+            }
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+    Ok(())
+}
+
+// Connect and publish changes until the connection fails
+fn run(
+    address: &str,
+    settings: &MqttSettings,
+    prefix: &str,
+    app_state: &AppState,
+) -> io::Result<()> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(NETWORK_TIMEOUT))?;
+    stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+
+    let client_id = format!("rust-pomo-{}", std::process::id());
+    let will_topic = format!("{}/state", prefix);
+    stream.write_all(&connect_packet(
+        &client_id,
+        settings.username.as_deref(),
+        settings.password.as_deref(),
+        (&will_topic, br#"{"running":false}"#),
+    ))?;
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack)?;
+    check_connack(&connack)?;
+
+    // Last value sent per topic
+    let mut sent: HashMap<String, String> = HashMap::new();
+    let mut last_write = Instant::now();
+    loop {
+        for (topic, value) in current_values(prefix, app_state) {
+            if sent.get(&topic) != Some(&value) {
+                stream.write_all(&publish_packet(&topic, value.as_bytes(), true))?;
+                last_write = Instant::now();
+                sent.insert(topic, value);
+            }
+        }
+        if last_write.elapsed() >= Duration::from_secs(KEEP_ALIVE as u64 / 2) {
+            stream.write_all(&[0xC0, 0x00])?;
+            last_write = Instant::now();
+        }
+        thread::sleep(POLL);
+    }
+}
+
+// Topic and value for everything published
+fn current_values(prefix: &str, app_state: &AppState) -> Vec<(String, String)> {
+    let mut values = vec![(
+        format!("{}/state", prefix),
+        daemon::status_json(app_state).to_json(),
+    )];
+    let session = app_state.session.lock().unwrap().clone();
+    if let Some(session) = session {
+        let phase = serde_json::to_value(session.phase)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        values.push((format!("{}/phase", prefix), phase));
+        values.push((
+            format!("{}/remaining", prefix),
+            session.remaining().to_string(),
+        ));
+        values.push((
+            format!("{}/paused", prefix),
+            app_state.paused.load(Ordering::SeqCst).to_string(),
+        ));
+        values.push((
+            format!("{}/statusline", prefix),
+            daemon::statusline_text(app_state),
+        ));
+    }
+    values
+}
+
+// host:port from mqtt://host[:port]
+fn broker_address(broker: &str) -> io::Result<String> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let rest = match broker.split_once("://") {
+        Some(("mqtt" | "tcp", rest)) => rest,
+        Some((scheme, _)) => {
+            return Err(invalid(format!(
+                "[mqtt] broker: {}:// is not supported (only plain mqtt://)",
+                scheme
+            )));
+        }
+        None => broker,
+    };
+    let host = rest.trim_end_matches('/');
+    if host.is_empty() {
+        return Err(invalid("[mqtt] broker is not set".to_string()));
+    }
+    // A port is there if the part after the last colon is a number (and it
+    // isn't part of a bare IPv6 address)
+    let has_port = match host.rsplit_once(':') {
+        Some((name, port)) => {
+            port.parse::<u16>().is_ok() && (!name.contains(':') || name.ends_with(']'))
+        }
+        None => false,
+    };
+    Ok(if has_port {
+        host.to_string()
+    } else {
+        format!("{}:{}", host, DEFAULT_PORT)
+    })
+}
+
+fn check_connack(packet: &[u8; 4]) -> io::Result<()> {
+    if packet[0] != 0x20 || packet[1] != 0x02 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected reply to CONNECT",
+        ));
+    }
+    match packet[3] {
+        0 => Ok(()),
+        4 | 5 => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the broker refused the username or password",
+        )),
+        code => Err(io::Error::other(format!(
+            "the broker refused the connection (code {})",
+            code
+        ))),
+    }
+}
+
+// CONNECT with a clean session and a retained last will
+fn connect_packet(
+    client_id: &str,
+    username: Option<&str>,
+    password: Option<&str>,
+    (will_topic, will_message): (&str, &[u8]),
+) -> Vec<u8> {
+    // Clean session, will flag, will retain
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = Vec::new();
+    put_bytes(&mut body, b"MQTT");
+    body.push(4); // protocol level 3.1.1
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE.to_be_bytes());
+    put_bytes(&mut body, client_id.as_bytes());
+    put_bytes(&mut body, will_topic.as_bytes());
+    put_bytes(&mut body, will_message);
+    if let Some(username) = username {
+        put_bytes(&mut body, username.as_bytes());
+    }
+    if let Some(password) = password {
+        put_bytes(&mut body, password.as_bytes());
+    }
+    packet(0x10, &body)
+}
+
+// PUBLISH at QoS 0
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_bytes(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(0x30 | retain as u8, &body)
+}
+
+// Fixed header (type and flags, remaining length) and body
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+// A length-prefixed string or binary field
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broker_urls_default_the_port() {
+        assert_eq!(
+            broker_address("mqtt://broker.local").unwrap(),
+            "broker.local:1883"
+        );
+        assert_eq!(
+            broker_address("mqtt://10.0.0.2:1884/").unwrap(),
+            "10.0.0.2:1884"
+        );
+        assert_eq!(broker_address("[::1]:1883").unwrap(), "[::1]:1883");
+        assert!(broker_address("mqtts://broker.local").is_err());
+        assert!(broker_address("").is_err());
+    }
+
+    #[test]
+    fn publish_packets_are_encoded() {
+        assert_eq!(
+            publish_packet("a/b", b"42", true),
+            [0x31, 7, 0, 3, b'a', b'/', b'b', b'4', b'2']
+        );
+        // Remaining lengths over 127 take more than one byte
+        let long = publish_packet("t", &[0; 200], false);
+        assert_eq!(&long[..3], &[0x30, 0xCB, 0x01]);
+        assert_eq!(long.len(), 3 + 203);
+    }
+
+    #[test]
+    fn connect_packet_carries_will_and_credentials() {
+        let packet = connect_packet("id", Some("u"), Some("p"), ("t", b"w"));
+        assert_eq!(packet[0], 0x10);
+        assert_eq!(&packet[2..10], &[0, 4, b'M', b'Q', b'T', b'T', 4, 0xE6]);
+        assert_eq!(&packet[10..12], &KEEP_ALIVE.to_be_bytes());
+        assert_eq!(
+            &packet[12..],
+            &[
+                0, 2, b'i', b'd', 0, 1, b't', 0, 1, b'w', 0, 1, b'u', 0, 1, b'p'
+            ]
+        );
+        assert_eq!(packet[1] as usize, packet.len() - 2);
+    }
+}
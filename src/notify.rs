@@ -0,0 +1,467 @@
+// Desktop notifications for phase transitions. `NotifySettings` decides what
+// to say and when; a `Notifier` backend shows it (macOS `osascript`, Windows
+// toast notifications, `notify-send` elsewhere).
+//
+// Which events produce a notification is configurable:
+//   start   … a focus or break phase begins
+//   end     … a focus or break phase finishes (the end of a break is the end of a cycle)
+//   warning … a phase is about to finish (`warn_before`, 2 minutes by default)
+//
+// With `speak` (`--speak`) the same events are also announced aloud
+// (speech.rs).
+
+use crate::{
+    duration::{deserialize_duration, format_duration},
+    goal::Progress,
+    report::DayReport,
+    session::{Phase, PomodoroSession, cycle_of},
+    speech::{self, spoken_duration},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+/// Notification title
+const TITLE: &str = "Focus Timer";
+
+/// macOS notification sound used when `sound` is enabled
+const SOUND_NAME: &str = "Glass";
+
+/// Sound (from the freedesktop sound theme) asked of `notify-send` when
+/// `sound` is enabled; servers that don't play sounds ignore it
+const SOUND_THEME_NAME: &str = "message-new-instant";
+
+/// Windows toasts need a registered app ID; PowerShell's is always there
+#[cfg_attr(not(windows), allow(dead_code))]
+const TOAST_APP_ID: &str =
+    "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+/// Events that can trigger a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyEvent {
+    /// A phase starts
+    Start,
+    /// A phase ends
+    End,
+    /// A phase is about to end
+    Warning,
+}
+
+/// Notification preferences, from the config file and command line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifySettings {
+    pub events: Vec<NotifyEvent>,
+    /// How long before the end of a phase the warning is sent
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub warn_before: u64,
+    /// Play a sound with each notification
+    pub sound: bool,
+    /// Announce phase starts, ends and warnings aloud
+    pub speak: bool,
+}
+
+impl Default for NotifySettings {
+    fn default() -> Self {
+        NotifySettings {
+            events: vec![NotifyEvent::Start, NotifyEvent::End, NotifyEvent::Warning],
+            warn_before: 120,
+            sound: false,
+            speak: false,
+        }
+    }
+}
+
+impl NotifySettings {
+    fn enabled(&self, event: NotifyEvent) -> bool {
+        self.events.contains(&event)
+    }
+
+    /// Seconds before the end of a phase of this length to warn, if at all
+    pub fn warning_at(&self, phase_len: u64) -> Option<u64> {
+        (self.enabled(NotifyEvent::Warning) && self.warn_before > 0 && self.warn_before < phase_len)
+            .then_some(self.warn_before)
+    }
+
+    pub fn phase_start(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+    ) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::Start) {
+            return Ok(());
+        }
+        self.say(&format!(
+            "{} starts now, {}.",
+            phase_name(session.phase),
+            spoken_duration(session.phase_len())
+        ));
+        let len = format_duration(session.phase_len());
+        let message = match session.phase {
+            Phase::Focus => format!(
+                "Focus started ({}) - cycle {}",
+                len,
+                cycle_of(session.cycle, session.cycles)
+            ),
+            Phase::Break => format!("Break started ({})", len),
+        };
+        self.send(notifier, session, &message)
+    }
+
+    pub fn phase_end(&self, notifier: &dyn Notifier, session: &PomodoroSession) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::End) {
+            return Ok(());
+        }
+        self.say(match session.phase {
+            Phase::Focus => "Focus is over. Time for a break.",
+            Phase::Break => "Break is over.",
+        });
+        let message = match session.phase {
+            Phase::Focus => "Focus finished! Time for a break.".to_string(),
+            Phase::Break => format!("Cycle {} finished!", session.cycle),
+        };
+        self.send(notifier, session, &message)
+    }
+
+    /// The previous phase is over and the next one (`session.phase`) is
+    /// waiting for the user; sent with the `end` event
+    pub fn waiting(&self, notifier: &dyn Notifier, session: &PomodoroSession) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::End) {
+            return Ok(());
+        }
+        let message = match session.phase {
+            Phase::Focus => "Ready for the next pomodoro? Press ENTER to start.",
+            Phase::Break => "Take a break! Press ENTER to start it.",
+        };
+        self.send(notifier, session, message)
+    }
+
+    /// Today's completed pomodoros just reached the daily goal; sent with the
+    /// `end` event
+    pub fn goal_reached(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+        progress: &Progress,
+    ) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::End) {
+            return Ok(());
+        }
+        self.send(
+            notifier,
+            session,
+            &format!("Daily goal reached! {}", progress),
+        )
+    }
+
+    /// The session was stopped before the end (`pomo stop` or q), with
+    /// where the day stands if there is a daily goal; sent with the `end` event
+    pub fn stopped(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+        progress: Option<&Progress>,
+    ) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::End) {
+            return Ok(());
+        }
+        let mut message = format!(
+            "Session stopped in cycle {}.",
+            cycle_of(session.cycle, session.cycles)
+        );
+        if let Some(progress) = progress {
+            message.push_str(&format!(" {}", progress));
+        }
+        self.send(notifier, session, &message)
+    }
+
+    /// The day so far, when a session ends or at `report_at`, with where
+    /// the daily goal stands if there is one; sent with the `end` event
+    pub fn day_report(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+        report: &DayReport,
+        progress: Option<&Progress>,
+    ) -> io::Result<()> {
+        if !self.enabled(NotifyEvent::End) {
+            return Ok(());
+        }
+        let mut message = format!("Today: {}.", report.summary());
+        if let Some(progress) = progress {
+            message.push_str(&format!(" {}", progress));
+        }
+        self.send(notifier, session, &message)
+    }
+
+    pub fn warning(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+        remaining: u64,
+    ) -> io::Result<()> {
+        let name = phase_name(session.phase);
+        self.say(&format!("{} ends in {}.", name, spoken_duration(remaining)));
+        self.send(
+            notifier,
+            session,
+            &format!("{} ends in {}", name, format_duration(remaining)),
+        )
+    }
+
+    fn say(&self, text: &str) {
+        if self.speak {
+            speech::speak(text);
+        }
+    }
+
+    // The task and tags, if any, go in the subtitle
+    fn send(
+        &self,
+        notifier: &dyn Notifier,
+        session: &PomodoroSession,
+        message: &str,
+    ) -> io::Result<()> {
+        notifier.notify(TITLE, session.label().as_deref(), message, self.sound)
+    }
+}
+
+fn phase_name(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Focus => "Focus",
+        Phase::Break => "Break",
+    }
+}
+
+/// A way of showing desktop notifications
+pub trait Notifier: Send + Sync {
+    fn notify(
+        &self,
+        title: &str,
+        subtitle: Option<&str>,
+        message: &str,
+        sound: bool,
+    ) -> io::Result<()>;
+
+    /// Whether notifications can be shown here, checked before the session
+    /// starts
+    fn check(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Notifications through AppleScript's `display notification` (macOS)
+pub struct OsascriptNotifier;
+
+impl Notifier for OsascriptNotifier {
+    fn notify(
+        &self,
+        title: &str,
+        subtitle: Option<&str>,
+        message: &str,
+        sound: bool,
+    ) -> io::Result<()> {
+        send_notification(title, subtitle, message, sound.then_some(SOUND_NAME))
+    }
+
+    fn check(&self) -> io::Result<()> {
+        probe("osascript", &["-e", "return"])
+    }
+}
+
+/// Toast notifications through WinRT's `ToastNotificationManager`, driven
+/// from PowerShell (Windows)
+pub struct ToastNotifier;
+
+impl Notifier for ToastNotifier {
+    fn notify(
+        &self,
+        title: &str,
+        subtitle: Option<&str>,
+        message: &str,
+        sound: bool,
+    ) -> io::Result<()> {
+        let script = format!(
+            "$null = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$null = [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime]
+$xml = New-Object Windows.Data.Xml.Dom.XmlDocument
+$xml.LoadXml('{}')
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{}').Show([Windows.UI.Notifications.ToastNotification]::new($xml))",
+            toast_xml(title, subtitle, message, sound).replace('\'', "''"),
+            TOAST_APP_ID
+        );
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .status()?;
+        Ok(())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        probe(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", "exit"],
+        )
+    }
+}
+
+/// Notifications through `notify-send` (libnotify), for Linux and other
+/// freedesktop desktops
+pub struct NotifySendNotifier;
+
+impl Notifier for NotifySendNotifier {
+    fn notify(
+        &self,
+        title: &str,
+        subtitle: Option<&str>,
+        message: &str,
+        sound: bool,
+    ) -> io::Result<()> {
+        Command::new("notify-send")
+            .args(notify_send_args(title, subtitle, message, sound))
+            .status()?;
+        Ok(())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        probe("notify-send", &["--version"])
+    }
+}
+
+// `notify-send` arguments: the task and tags go on a line below the message,
+// which servers may read as markup
+fn notify_send_args(
+    title: &str,
+    subtitle: Option<&str>,
+    message: &str,
+    sound: bool,
+) -> Vec<String> {
+    let mut body = escape_xml(message);
+    if let Some(subtitle) = subtitle {
+        body.push('\n');
+        body.push_str(&escape_xml(subtitle));
+    }
+    let mut args = vec![format!("--app-name={}", TITLE)];
+    if sound {
+        args.push(format!("--hint=string:sound-name:{}", SOUND_THEME_NAME));
+    }
+    args.extend(["--".to_string(), title.to_string(), body]);
+    args
+}
+
+// Run a program that does nothing, to see that it is there and works
+fn probe(program: &str, args: &[&str]) -> io::Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{} failed ({})", program, status)));
+    }
+    Ok(())
+}
+
+// The notifier for this platform
+pub fn system_notifier() -> Box<dyn Notifier> {
+    if cfg!(windows) {
+        Box::new(ToastNotifier)
+    } else if cfg!(target_os = "macos") {
+        Box::new(OsascriptNotifier)
+    } else {
+        Box::new(NotifySendNotifier)
+    }
+}
+
+// Toast content: the title, then the message, then the task and tags
+fn toast_xml(title: &str, subtitle: Option<&str>, message: &str, sound: bool) -> String {
+    let mut lines = vec![title, message];
+    lines.extend(subtitle);
+    let text: String = lines
+        .iter()
+        .map(|line| format!("<text>{}</text>", escape_xml(line)))
+        .collect();
+    let audio = if sound {
+        "<audio src=\"ms-winsoundevent:Notification.Default\"/>"
+    } else {
+        "<audio silent=\"true\"/>"
+    };
+    format!(
+        "<toast><visual><binding template=\"ToastGeneric\">{}</binding></visual>{}</toast>",
+        text, audio
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Show notification on macOS
+fn send_notification(
+    title: &str,
+    subtitle: Option<&str>,
+    message: &str,
+    sound: Option<&str>,
+) -> io::Result<()> {
+    let mut script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        escape(message),
+        escape(title)
+    );
+    if let Some(subtitle) = subtitle {
+        script.push_str(&format!(" subtitle \"{}\"", escape(subtitle)));
+    }
+    if let Some(sound) = sound {
+        script.push_str(&format!(" sound name \"{}\"", sound));
+    }
+
+    Command::new("osascript").arg("-e").arg(script).status()?;
+    Ok(())
+}
+
+// Quote user-supplied text (task names) for an AppleScript string literal
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toast_xml_escapes_task_names() {
+        assert_eq!(
+            toast_xml(
+                "Focus Timer",
+                Some("Fix <b> & \"c\""),
+                "Focus started",
+                false
+            ),
+            "<toast><visual><binding template=\"ToastGeneric\">\
+             <text>Focus Timer</text><text>Focus started</text>\
+             <text>Fix &lt;b&gt; &amp; &quot;c&quot;</text>\
+             </binding></visual><audio silent=\"true\"/></toast>"
+        );
+    }
+
+    #[test]
+    fn notify_send_gets_task_below_message() {
+        assert_eq!(
+            notify_send_args("Focus Timer", Some("Fix <b> #work"), "Focus started", true),
+            [
+                "--app-name=Focus Timer",
+                "--hint=string:sound-name:message-new-instant",
+                "--",
+                "Focus Timer",
+                "Focus started\nFix &lt;b&gt; #work",
+            ]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// Machine-readable output for status bars and scripts.
+//
+// With `--output json` every event in a session is written to stdout as one
+// JSON object per line, e.g.
+//   {"time":"2024-05-01T10:00:00+09:00","event":"phase_start","phase":"focus","cycle":1,...}
+//   {"time":"2024-05-01T10:00:01+09:00","event":"tick","phase":"focus","cycle":1,"elapsed":1,...}
+//
+// Events: phase_start, tick (once a second while running), pause, resume,
+// extend (the phase was lengthened or shortened, by `by` seconds), phase_end (with its outcome: completed, skipped, stopped or abandoned),
+// waiting (for the user to start the next phase) and finished.
+// Human-readable messages move to stderr (see `say!`) so stdout stays parseable.
+// `pomo follow` gets the same events from a session running elsewhere
+// (follow.rs).
+//
+// Without a terminal (cron, scripts, `pomo | tee`), or with `--non-interactive`,
+// there is no progress bar or keyboard input: progress is reported as a line
+// every minute instead, e.g.
+//   Focus 2/4: 12:00 left, ends 14:25 write report
+
+use crate::{
+    follow,
+    history::Outcome,
+    session::{Phase, PomodoroSession},
+};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use indicatif::ProgressBar;
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Output format of the timer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Progress bar and messages for people
+    #[default]
+    Text,
+    /// One JSON event per line on stdout
+    Json,
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Select the output format for the rest of the process
+pub fn set_format(format: OutputFormat) {
+    JSON.store(format == OutputFormat::Json, Ordering::SeqCst);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Whether someone is at a terminal: a progress bar and keyboard input, or
+/// lines of progress and no input
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::SeqCst);
+}
+
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::SeqCst)
+}
+
+/// Print a human-readable message: to stdout normally, to stderr in JSON mode,
+/// or onto the `--tui` dashboard; nowhere with `-q`
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::output::say(format!($($arg)*))
+    };
+}
+
+// What `say!` does with a message
+pub fn say(message: String) {
+    if crate::logging::is_quiet() {
+        return;
+    }
+    if is_json() {
+        print_line(&message, true);
+    } else if crate::tui::is_active() {
+        crate::tui::log(message);
+    } else {
+        print_line(&message, false);
+    }
+}
+
+/// The progress bar on the terminal, if one is being drawn
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Print messages above this progress bar (rather than through it) until
+/// `detach_bar`
+pub fn attach_bar(pb: &ProgressBar) {
+    *BAR.lock().unwrap() = Some(pb.clone());
+}
+
+pub fn detach_bar() {
+    BAR.lock().unwrap().take();
+}
+
+// Write a line to stdout (or stderr), taking the progress bar off the screen
+// meanwhile so the line doesn't end up in the middle of it
+pub fn print_line(line: &str, stderr: bool) {
+    let print = || {
+        if stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+    let bar = BAR.lock().unwrap().clone();
+    match bar {
+        Some(bar) if !bar.is_finished() => bar.suspend(print),
+        _ => print(),
+    }
+}
+
+/// Where a session is, as reported in events and `pomo status --json`
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub phase: Phase,
+    pub cycle: u32,
+    pub cycles: u32,
+    /// Seconds run in the current phase
+    pub elapsed: u64,
+    /// Seconds left in the current phase
+    pub remaining: u64,
+    /// Length of the current phase in seconds
+    pub duration: u64,
+    /// Seconds the current phase has been paused
+    pub paused: u64,
+    /// Seconds counted past the end of focus (`--overtime`)
+    pub overtime: u64,
+    pub task: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn of(session: &PomodoroSession) -> Self {
+        Snapshot {
+            phase: session.phase,
+            cycle: session.cycle,
+            cycles: session.cycles,
+            elapsed: session.elapsed,
+            remaining: session.remaining(),
+            duration: session.phase_len(),
+            paused: session.paused,
+            overtime: session.overtime_elapsed,
+            task: session.task.clone(),
+            tags: session.tags.clone(),
+        }
+    }
+}
+
+/// Something that happened in a session
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    PhaseStart(Snapshot),
+    Tick(Snapshot),
+    Pause(Snapshot),
+    Resume(Snapshot),
+    /// The current phase was lengthened (or, negative, shortened) by `by`
+    /// seconds
+    Extend {
+        #[serde(flatten)]
+        snapshot: Snapshot,
+        by: i64,
+    },
+    PhaseEnd {
+        #[serde(flatten)]
+        snapshot: Snapshot,
+        outcome: Outcome,
+    },
+    /// The next phase (in the snapshot) is waiting for the user
+    Waiting(Snapshot),
+    /// The session is over, either done or stopped
+    Finished {
+        stopped: bool,
+    },
+}
+
+/// Reply to `pomo status --json`
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub running: bool,
+    /// "running", "paused", "overtime", "waiting" (for the next phase),
+    /// "snoozed" (counting down to it) or "starting"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<&'static str>,
+    #[serde(flatten)]
+    pub snapshot: Option<Snapshot>,
+}
+
+impl Status {
+    pub fn not_running() -> Self {
+        Status {
+            running: false,
+            state: None,
+            snapshot: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: DateTime<Local>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// Write the event to stdout if JSON output is on, and to `pomo follow`
+pub fn emit(event: &Event) {
+    let followed = follow::is_followed();
+    if !is_json() && !followed {
+        return;
+    }
+    let line = Line {
+        time: Local::now(),
+        event,
+    };
+    let Ok(json) = serde_json::to_string(&line) else {
+        return;
+    };
+    if is_json() {
+        // A closed pipe (e.g. `| head`) shouldn't end the session
+        let mut stdout = io::stdout().lock();
+        let _ = writeln!(stdout, "{}", json).and_then(|_| stdout.flush());
+    }
+    if followed {
+        follow::send(&json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn to_value(event: &Event) -> Value {
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn events_are_tagged_and_flattened() {
+        let mut session = PomodoroSession::new(60, 30, 2);
+        session.elapsed = 15;
+        session.task = Some("report".to_string());
+
+        let tick = to_value(&Event::Tick(Snapshot::of(&session)));
+        assert_eq!(tick["event"], "tick");
+        assert_eq!(tick["phase"], "focus");
+        assert_eq!(tick["elapsed"], 15);
+        assert_eq!(tick["remaining"], 45);
+        assert_eq!(tick["task"], "report");
+
+        let end = to_value(&Event::PhaseEnd {
+            snapshot: Snapshot::of(&session),
+            outcome: Outcome::Skipped,
+        });
+        assert_eq!(end["event"], "phase_end");
+        assert_eq!(end["outcome"], "skipped");
+        assert_eq!(end["cycle"], 1);
+    }
+
+    #[test]
+    fn status_without_session_is_just_not_running() {
+        let status: Value = serde_json::from_str(&Status::not_running().to_json()).unwrap();
+        assert_eq!(status, json!({ "running": false }));
+    }
+}
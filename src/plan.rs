@@ -0,0 +1,280 @@
+// The planning queue: tasks lined up before the day starts, each with an
+// estimate in pomodoros, worked through one after the other.
+//
+//   pomo plan add "fix bug #42" --estimate 3
+//   pomo plan add "write release notes"
+//   pomo plan list
+//   pomo --plan --cycles 4
+//
+// With --plan each focus phase takes the first task not yet done as its task
+// (the session log records the task and its estimate). Completed pomodoros
+// count toward the task. Between phases pomo says what is next; typing `done`
+// there (or running `pomo plan done` anywhere) marks the current task finished
+// so the next focus phase moves on. `pomo plan list` shows estimates against
+// the pomodoros actually spent.
+//
+// The queue is kept in plan.json next to the session log, and encrypted like
+// it with `[encryption]` in the config file.
+
+use crate::{
+    dry_run,
+    session::{PlanPosition, PomodoroSession},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// One queued task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedTask {
+    pub task: String,
+    /// Pomodoros it is expected to take
+    pub estimate: u32,
+    /// Pomodoros completed on it so far
+    #[serde(default)]
+    pub pomodoros: u32,
+    #[serde(default)]
+    pub done: bool,
+}
+
+/// The queue, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub tasks: Vec<PlannedTask>,
+}
+
+// Location of the queue
+pub fn plan_path() -> PathBuf {
+    crate::history::history_path().with_file_name("plan.json")
+}
+
+impl Plan {
+    /// Read the queue; a missing file is an empty queue
+    pub fn load() -> io::Result<Plan> {
+        match fs::read_to_string(plan_path()) {
+            Ok(text) => {
+                serde_json::from_str(&crate::history::open(&text)?).map_err(io::Error::other)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Plan::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Seal a queue saved before encryption was turned on (`pomo
+    /// encrypt-log`); false if there was nothing to seal
+    pub fn encrypt() -> io::Result<bool> {
+        match fs::read_to_string(plan_path()) {
+            Ok(text) if !crate::crypt::is_sealed(&text) => {
+                Plan::load()?.save()?;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = plan_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = crate::history::seal(&serde_json::to_string_pretty(self)?)?;
+        crate::history::open_private(
+            fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true),
+            &path,
+        )?
+        .write_all(text.as_bytes())
+    }
+
+    pub fn add(&mut self, task: String, estimate: u32) {
+        self.tasks.push(PlannedTask {
+            task,
+            estimate: estimate.max(1),
+            pomodoros: 0,
+            done: false,
+        });
+    }
+
+    /// Index of the first task not done yet
+    pub fn next(&self) -> Option<usize> {
+        self.tasks.iter().position(|task| !task.done)
+    }
+
+    /// Where the task at `index` stands, for the session
+    pub fn position(&self, index: usize) -> PlanPosition {
+        let task = &self.tasks[index];
+        PlanPosition {
+            number: index as u32 + 1,
+            total: self.tasks.len() as u32,
+            pomodoro: task.pomodoros + 1,
+            estimate: task.estimate,
+        }
+    }
+
+    /// Mark the task at `index` (1-based, as listed) done; false if there is
+    /// no such task
+    pub fn finish(&mut self, number: usize) -> bool {
+        match number.checked_sub(1).and_then(|i| self.tasks.get_mut(i)) {
+            Some(task) => {
+                task.done = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Take the task at `number` (1-based) off the queue
+    pub fn remove(&mut self, number: usize) -> Option<PlannedTask> {
+        let index = number.checked_sub(1).filter(|&i| i < self.tasks.len())?;
+        Some(self.tasks.remove(index))
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let next = self.next();
+        let width = self
+            .tasks
+            .iter()
+            .map(|task| task.task.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (i, task) in self.tasks.iter().enumerate() {
+            let mark = match (task.done, Some(i) == next) {
+                (true, _) => "✓",
+                (false, true) => "→",
+                (false, false) => " ",
+            };
+            write!(
+                f,
+                "{:>3}. {} {:<width$}  {}/{} pomodoros",
+                i + 1,
+                mark,
+                task.task,
+                task.pomodoros,
+                task.estimate,
+                width = width
+            )?;
+            if task.pomodoros > task.estimate {
+                write!(f, " (over by {})", task.pomodoros - task.estimate)?;
+            }
+            if i + 1 < self.tasks.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Give the session's next focus phase the first task not done yet (or no task
+// once the queue is worked through)
+pub fn pick_next(session: &mut PomodoroSession) {
+    let plan = match Plan::load() {
+        Ok(plan) => plan,
+        Err(e) => {
+            log::warn!("Failed to read the plan: {}", e);
+            return;
+        }
+    };
+    match plan.next() {
+        Some(index) => {
+            let position = plan.position(index);
+            crate::say!(
+                "Task {}/{}: {} (pomodoro {} of {} estimated)",
+                position.number,
+                position.total,
+                plan.tasks[index].task,
+                position.pomodoro,
+                position.estimate
+            );
+            session.task = Some(plan.tasks[index].task.clone());
+            session.planned = Some(position);
+        }
+        None => {
+            if session.planned.is_some() || session.task.is_none() {
+                crate::say!("Everything in the plan is done; add more with `pomo plan add`.");
+            }
+            session.task = None;
+            session.planned = None;
+        }
+    }
+}
+
+// A pomodoro was completed on the session's planned task
+pub fn count_pomodoro(session: &PomodoroSession) {
+    let (Some(position), Some(task)) = (&session.planned, &session.task) else {
+        return;
+    };
+    if session.dry_run {
+        dry_run::action(format!("count a pomodoro for planned task \"{}\"", task));
+        return;
+    }
+    let result = Plan::load().and_then(|mut plan| {
+        let index = position.number as usize - 1;
+        match plan.tasks.get_mut(index) {
+            // The queue may have been edited since the task was picked
+            Some(planned) if planned.task == *task => planned.pomodoros += 1,
+            _ => match plan.tasks.iter_mut().find(|p| p.task == *task && !p.done) {
+                Some(planned) => planned.pomodoros += 1,
+                None => return Ok(()),
+            },
+        }
+        plan.save()
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to update the plan: {}", e);
+    }
+}
+
+// Mark `task` done (typed `done` during a session); returns the task up next
+pub fn finish_task(task: &str) -> io::Result<Option<String>> {
+    let mut plan = Plan::load()?;
+    if let Some(planned) = plan.tasks.iter_mut().find(|p| p.task == task && !p.done) {
+        planned.done = true;
+        plan.save()?;
+    }
+    Ok(plan.next().map(|i| plan.tasks[i].task.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_is_worked_through_in_order() {
+        let mut plan = Plan::default();
+        plan.add("fix bug #42".to_string(), 3);
+        plan.add("write docs".to_string(), 0);
+        assert_eq!(plan.tasks[1].estimate, 1);
+        assert_eq!(plan.next(), Some(0));
+
+        plan.tasks[0].pomodoros = 4;
+        assert_eq!(
+            plan.position(0),
+            PlanPosition {
+                number: 1,
+                total: 2,
+                pomodoro: 5,
+                estimate: 3
+            }
+        );
+        assert!(plan.finish(1));
+        assert!(!plan.finish(3));
+        assert_eq!(plan.next(), Some(1));
+        assert_eq!(
+            plan.to_string(),
+            "  1. ✓ fix bug #42  4/3 pomodoros (over by 1)\n  2. → write docs   0/1 pomodoros"
+        );
+
+        assert_eq!(plan.remove(1).unwrap().task, "fix bug #42");
+        assert_eq!(plan.remove(2), None);
+        assert_eq!(plan.next(), Some(0));
+    }
+}
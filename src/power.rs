@@ -0,0 +1,462 @@
+// System sleep and screen lock.
+//
+// The timer runs on a monotonic clock that stops while the machine sleeps, so
+// sleep never counts as focus time, but nothing else noticed it either: Wi-Fi
+// could come back on at wake and the session log showed no gap. Sleep is
+// detected on wake by comparing how far the clock since boot moved with how
+// far the awake-only one did (clock.rs); the runner then treats the sleep as a
+// pause (pause and resume events, pause time, pause budget) and re-engages
+// the blockers. The wall clock being set (NTP, by hand) is told apart from
+// sleep and doesn't change the timing; where there are no clocks since boot,
+// the wall clock against the monotonic one is all there is to go on.
+//
+// With --pause-on-lock the session is also paused while the screen is locked
+// and resumed when it is unlocked. Lock state is polled:
+//   macOS   … `ioreg -n Root -d1` (CGSSessionScreenIsLocked)
+//   Linux   … `loginctl show-session` (LockedHint)
+//   Windows … whether the lock screen (LogonUI.exe) is running
+//
+// With --idle-after 5m, no keyboard or mouse input for that long during focus
+// means the user walked away: the idle stretch is taken back off the focus
+// time and the phase is paused until there is input again (or, with
+// --on-idle abandon, the phase is abandoned). Idle time is logged with the
+// phase. It is read from:
+//   macOS … `ioreg -c IOHIDSystem` (HIDIdleTime)
+//   Linux … `xprintidle` on X11, otherwise `loginctl` (IdleHint, which GNOME
+//           and KDE set on Wayland too)
+// (not on Windows, where --idle-after has no effect)
+
+use crate::{clock::Readings, duration::deserialize_duration, runner::AppState};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    process::Command,
+    sync::{Arc, atomic::Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Smallest gap between the clocks that counts as sleep. Blocking calls
+/// (networksetup, curl) delay both clocks alike, so anything this large
+/// means the machine was suspended.
+const MIN_SLEEP: Duration = Duration::from_secs(5);
+
+/// How often the screen lock state is checked
+const LOCK_POLL: Duration = Duration::from_secs(2);
+
+/// How often idle time is checked
+const IDLE_POLL: Duration = Duration::from_secs(2);
+
+/// What happens to a focus phase when the user is idle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleAction {
+    /// Pause until there is input again
+    #[default]
+    Pause,
+    /// End the phase as abandoned
+    Abandon,
+}
+
+/// Idle detection, from the flags or the `[idle]` table of the config file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleSettings {
+    /// Seconds without input that count as idle; 0 turns detection off
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub after: u64,
+    pub action: IdleAction,
+}
+
+/// Checks the user's idle time every few seconds
+#[derive(Debug, Clone)]
+pub struct IdleWatch {
+    after: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl IdleWatch {
+    pub fn new(settings: &IdleSettings) -> Option<Self> {
+        (settings.after > 0).then(|| IdleWatch {
+            after: Duration::from_secs(settings.after),
+            last_poll: None,
+        })
+    }
+
+    /// How long the user has been idle, once that reaches the threshold
+    pub fn idle(&mut self, now: Instant) -> Option<Duration> {
+        self.poll(now)?.filter(|&idle| idle >= self.after)
+    }
+
+    /// Whether there has been input since the user went idle at `since`
+    pub fn back(&mut self, since: Instant, now: Instant) -> bool {
+        // Still idle means idle for (about) as long as it's been since then
+        matches!(
+            self.poll(now),
+            Some(Some(idle)) if idle + IDLE_POLL < now.saturating_duration_since(since)
+        )
+    }
+
+    // The idle time, if it's time to check again and it can be told
+    fn poll(&mut self, now: Instant) -> Option<Option<Duration>> {
+        if self
+            .last_poll
+            .is_some_and(|last| now.saturating_duration_since(last) < IDLE_POLL)
+        {
+            return None;
+        }
+        self.last_poll = Some(now);
+        Some(idle_time())
+    }
+}
+
+/// What happened between two checks besides time passing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// How long the system was asleep
+    pub slept: Duration,
+    /// How much of that the monotonic clock counted anyway (Windows), so the
+    /// timer has to leave it out
+    pub counted: Duration,
+    /// Seconds the wall clock was set forward (negative: back)
+    pub clock_set: i64,
+}
+
+/// Notices when the system has been asleep, or the wall clock was set, since
+/// the last check
+#[derive(Debug, Clone)]
+pub struct SleepDetector {
+    last: Readings,
+}
+
+impl SleepDetector {
+    pub fn new(readings: Readings) -> Self {
+        SleepDetector { last: readings }
+    }
+
+    /// What happened since the previous check, if anything
+    pub fn check(&mut self, now: Readings) -> Option<Gap> {
+        let last = std::mem::replace(&mut self.last, now);
+        let monotonic = now.instant.saturating_duration_since(last.instant);
+        let wall = match now.wall.duration_since(last.wall) {
+            Ok(passed) => passed.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let (slept, counted, passed) = match (last.boot.zip(now.boot), last.awake.zip(now.awake)) {
+            // Sleep is what the clock since boot counted and the awake one didn't
+            (Some((boot0, boot1)), Some((awake0, awake1))) => {
+                let boot = boot1.saturating_sub(boot0);
+                let awake = awake1.saturating_sub(awake0);
+                (
+                    boot.saturating_sub(awake),
+                    monotonic.saturating_sub(awake),
+                    boot,
+                )
+            }
+            // Otherwise only the wall clock running ahead of the monotonic one
+            // tells (so a clock set forward looks like sleep too)
+            _ => {
+                let slept = Duration::from_secs(wall.max(0) as u64).saturating_sub(monotonic);
+                (slept, Duration::ZERO, monotonic + slept)
+            }
+        };
+
+        let mut gap = Gap::default();
+        if slept >= MIN_SLEEP {
+            gap.slept = slept;
+            gap.counted = counted;
+        }
+        let clock_set = wall - passed.as_secs() as i64;
+        if clock_set.unsigned_abs() >= MIN_SLEEP.as_secs() {
+            gap.clock_set = clock_set;
+        }
+        (gap != Gap::default()).then_some(gap)
+    }
+}
+
+// Pause the session while the screen is locked. Only pauses made here are
+// undone on unlock; a pause the user asked for stays.
+pub fn watch_screen_lock(app_state: Arc<AppState>) {
+    thread::spawn(move || {
+        let mut paused_by_lock = false;
+        loop {
+            match screen_locked() {
+                Some(true) if !app_state.paused.load(Ordering::SeqCst) => {
+                    crate::say!("Screen locked; pausing.");
+                    app_state.pause(Some("screen locked".to_string()));
+                    paused_by_lock = true;
+                }
+                Some(false) if paused_by_lock => {
+                    crate::say!("Screen unlocked; resuming.");
+                    app_state.paused.store(false, Ordering::SeqCst);
+                    paused_by_lock = false;
+                }
+                _ => {}
+            }
+            thread::sleep(LOCK_POLL);
+        }
+    });
+}
+
+// Whether the screen is locked, or `None` if that can't be told
+#[cfg(target_os = "macos")]
+pub(crate) fn screen_locked() -> Option<bool> {
+    let output = Command::new("ioreg")
+        .args(["-n", "Root", "-d1"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(text.contains("\"CGSSessionScreenIsLocked\"=Yes"))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn screen_locked() -> Option<bool> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "LockedHint=yes" => Some(true),
+        "LockedHint=no" => Some(false),
+        _ => None,
+    }
+}
+
+// The lock screen is LogonUI.exe, which only runs while the session is locked
+#[cfg(windows)]
+pub(crate) fn screen_locked() -> Option<bool> {
+    let output = Command::new("tasklist")
+        .args(["/FI", "IMAGENAME eq LogonUI.exe", "/NH"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).contains("LogonUI.exe"))
+}
+
+// Lock the screen now
+#[cfg(target_os = "macos")]
+pub(crate) fn lock_screen() -> io::Result<()> {
+    // The Lock Screen shortcut (needs Accessibility access for the terminal)
+    let status = Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to keystroke \"q\" using {control down, command down}",
+        ])
+        .status()?;
+    check(status, "osascript")
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn lock_screen() -> io::Result<()> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_default();
+    let mut command = Command::new("loginctl");
+    command.arg("lock-session");
+    if !session.is_empty() {
+        command.arg(session);
+    }
+    check(command.status()?, "loginctl lock-session")
+}
+
+#[cfg(windows)]
+pub(crate) fn lock_screen() -> io::Result<()> {
+    let status = Command::new("rundll32.exe")
+        .arg("user32.dll,LockWorkStation")
+        .status()?;
+    check(status, "LockWorkStation")
+}
+
+fn check(status: std::process::ExitStatus, program: &str) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} failed ({})", program, status)))
+    }
+}
+
+// How long since the last keyboard or mouse input, or `None` if that can't
+// be told
+#[cfg(target_os = "macos")]
+fn idle_time() -> Option<Duration> {
+    let output = Command::new("ioreg")
+        .args(["-c", "IOHIDSystem", "-d", "4"])
+        .output()
+        .ok()?;
+    parse_hid_idle(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn idle_time() -> Option<Duration> {
+    if let Ok(output) = Command::new("xprintidle").output()
+        && output.status.success()
+    {
+        let ms = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .ok()?;
+        return Some(Duration::from_millis(ms));
+    }
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".to_string());
+    let output = Command::new("loginctl")
+        .args([
+            "show-session",
+            &session,
+            "--property=IdleHint",
+            "--property=IdleSinceHint",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_loginctl_idle(&String::from_utf8_lossy(&output.stdout), SystemTime::now())
+}
+
+// Not told on Windows (there is no command for GetLastInputInfo)
+#[cfg(windows)]
+fn idle_time() -> Option<Duration> {
+    None
+}
+
+// The "HIDIdleTime" = <nanoseconds> line of `ioreg -c IOHIDSystem`
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_hid_idle(text: &str) -> Option<Duration> {
+    let line = text.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+    let nanos = line.split('=').nth(1)?.trim().parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+// IdleHint=yes|no and IdleSinceHint=<microseconds since the epoch>
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn parse_loginctl_idle(text: &str, now: SystemTime) -> Option<Duration> {
+    let value = |key: &str| {
+        text.lines()
+            .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+    };
+    match value("IdleHint")? {
+        "yes" => {
+            let since = SystemTime::UNIX_EPOCH
+                + Duration::from_micros(value("IdleSinceHint")?.parse().ok()?);
+            Some(now.duration_since(since).unwrap_or_default())
+        }
+        _ => Some(Duration::ZERO),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    // Readings `secs` seconds on, the system awake for `awake` of them and the
+    // wall clock moved by `wall`
+    fn later(start: Readings, secs_on: u64, awake: u64, wall: i64) -> Readings {
+        Readings {
+            instant: start.instant + secs(awake),
+            wall: if wall >= 0 {
+                start.wall + secs(wall as u64)
+            } else {
+                start.wall - secs(wall.unsigned_abs())
+            },
+            boot: start.boot.map(|boot| boot + secs(secs_on)),
+            awake: start.awake.map(|since| since + secs(awake)),
+        }
+    }
+
+    fn start(with_boot_clocks: bool) -> Readings {
+        Readings {
+            instant: Instant::now(),
+            wall: SystemTime::now(),
+            boot: with_boot_clocks.then_some(secs(1000)),
+            awake: with_boot_clocks.then_some(secs(900)),
+        }
+    }
+
+    #[test]
+    fn clocks_moving_together_is_not_sleep() {
+        for boot_clocks in [true, false] {
+            let t0 = start(boot_clocks);
+            let mut detector = SleepDetector::new(t0);
+            assert_eq!(detector.check(later(t0, 1, 1, 1)), None);
+            // A slow blocking call delays all clocks alike
+            assert_eq!(detector.check(later(t0, 31, 31, 31)), None);
+        }
+    }
+
+    #[test]
+    fn sleep_is_told_from_the_clock_being_set() {
+        let t0 = start(true);
+        let mut detector = SleepDetector::new(t0);
+        let slept = Gap {
+            slept: secs(600),
+            ..Gap::default()
+        };
+        assert_eq!(detector.check(later(t0, 601, 1, 601)), Some(slept));
+        // Measured from the last check
+        assert_eq!(detector.check(later(t0, 602, 2, 602)), None);
+
+        // NTP or the user moving the wall clock isn't sleep
+        let mut detector = SleepDetector::new(t0);
+        let set = |clock_set| Gap {
+            clock_set,
+            ..Gap::default()
+        };
+        assert_eq!(detector.check(later(t0, 1, 1, 3601)), Some(set(3600)));
+        assert_eq!(detector.check(later(t0, 2, 2, 2)), Some(set(-3600)));
+    }
+
+    #[test]
+    fn without_boot_clocks_the_wall_clock_tells_sleep() {
+        let t0 = start(false);
+        let mut detector = SleepDetector::new(t0);
+        let slept = Gap {
+            slept: secs(600),
+            ..Gap::default()
+        };
+        assert_eq!(detector.check(later(t0, 601, 1, 601)), Some(slept));
+        // Set back: not sleep
+        let mut detector = SleepDetector::new(t0);
+        let set_back = Gap {
+            clock_set: -3601,
+            ..Gap::default()
+        };
+        assert_eq!(detector.check(later(t0, 1, 1, -3600)), Some(set_back));
+    }
+
+    #[test]
+    fn sleep_counted_by_the_monotonic_clock_is_reported() {
+        // As on Windows, where Instant keeps counting in sleep
+        let t0 = start(true);
+        let mut detector = SleepDetector::new(t0);
+        let mut woke = later(t0, 601, 1, 601);
+        woke.instant = t0.instant + secs(601);
+        let gap = detector.check(woke).unwrap();
+        assert_eq!((gap.slept, gap.counted), (secs(600), secs(600)));
+    }
+
+    #[test]
+    fn reads_hid_idle_time() {
+        let text = "    |   \"HIDIdleTime\" = 125000000000\n    |   \"HIDKeyboardModifierMappingPairs\" = ()\n";
+        assert_eq!(parse_hid_idle(text), Some(secs(125)));
+        assert_eq!(parse_hid_idle(""), None);
+    }
+
+    #[test]
+    fn reads_loginctl_idle_hint() {
+        let now = SystemTime::UNIX_EPOCH + secs(1_700_000_300);
+        let idle = "IdleHint=yes\nIdleSinceHint=1700000000000000\n";
+        assert_eq!(parse_loginctl_idle(idle, now), Some(secs(300)));
+        let active = "IdleHint=no\nIdleSinceHint=0\n";
+        assert_eq!(parse_loginctl_idle(active, now), Some(Duration::ZERO));
+        assert_eq!(parse_loginctl_idle("", now), None);
+    }
+}
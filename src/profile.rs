@@ -0,0 +1,188 @@
+// Named profiles: sets of durations, blockers, sounds and hooks kept in the
+// config file and picked per invocation.
+//
+//   [profiles.writing]
+//   description = "Long stretches, no web"
+//   focus = "50m"
+//   break_time = "10m"
+//   cycles = 3
+//   block = ["wifi", "apps"]
+//   sound = "chime"
+//
+//   [profiles.coding]
+//   schedule = "3x25m/5m,25m/15m"
+//   block = ["hosts"]
+//   sites = ["news.ycombinator.com", "reddit.com"]
+//   tags = ["code"]
+//   [profiles.coding.hooks]
+//   on_focus_start = "~/bin/slack-status coding"
+//
+//   pomo --profile writing
+//   pomo profile list
+//
+// Command-line flags still win over the profile, and the profile over the
+// rest of the config file. A profile's hooks replace [hooks] event by event;
+// events it doesn't name keep their [hooks] command.
+
+use crate::{
+    blockers::BlockerKind,
+    duration::{deserialize_optional_duration, format_duration},
+    error::{Error, Result},
+    hooks::Hooks,
+    schedule::Schedule,
+    sound::Sound,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// A `[profiles.<name>]` table of the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Shown by `pomo profile list`
+    pub description: Option<String>,
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub focus: Option<u64>,
+    #[serde(deserialize_with = "deserialize_optional_duration")]
+    pub break_time: Option<u64>,
+    pub cycles: Option<u32>,
+    pub schedule: Option<Schedule>,
+    pub block: Option<Vec<BlockerKind>>,
+    pub sites: Vec<String>,
+    pub apps: Vec<String>,
+    pub sound: Option<Sound>,
+    pub hooks: Hooks,
+    pub task: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Profile {
+    // [hooks] with this profile's commands in place of the ones it sets
+    pub fn hooks_over(&self, base: &Hooks) -> Hooks {
+        let pick = |own: &Option<String>, base: &Option<String>| own.clone().or(base.clone());
+        Hooks {
+            on_focus_start: pick(&self.hooks.on_focus_start, &base.on_focus_start),
+            on_focus_end: pick(&self.hooks.on_focus_end, &base.on_focus_end),
+            on_break_start: pick(&self.hooks.on_break_start, &base.on_break_start),
+            on_break_end: pick(&self.hooks.on_break_end, &base.on_break_end),
+            on_pause: pick(&self.hooks.on_pause, &base.on_pause),
+            on_resume: pick(&self.hooks.on_resume, &base.on_resume),
+            on_complete: pick(&self.hooks.on_complete, &base.on_complete),
+            timeout: self.hooks.timeout.or(base.timeout),
+        }
+    }
+
+    // One line of what the profile sets, for `pomo profile list`
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(schedule) = &self.schedule {
+            parts.push(schedule.to_string());
+        }
+        if let Some(focus) = self.focus {
+            parts.push(format!("focus {}", format_duration(focus)));
+        }
+        if let Some(break_time) = self.break_time {
+            parts.push(format!("break {}", format_duration(break_time)));
+        }
+        match self.cycles {
+            Some(0) => parts.push("cycles until stopped".to_string()),
+            Some(1) => parts.push("1 cycle".to_string()),
+            Some(cycles) => parts.push(format!("{} cycles", cycles)),
+            None => {}
+        }
+        if let Some(block) = &self.block {
+            let names: Vec<&str> = block.iter().map(|kind| kind.name()).collect();
+            parts.push(format!("block {}", names.join(",")));
+        }
+        if let Some(sound) = &self.sound {
+            parts.push(format!("sound {}", sound));
+        }
+        if let Some(task) = &self.task {
+            parts.push(format!("task \"{}\"", task));
+        }
+        if !self.tags.is_empty() {
+            parts.push(format!("tags {}", self.tags.join(",")));
+        }
+        let settings = parts.join(", ");
+        match &self.description {
+            Some(description) if settings.is_empty() => description.clone(),
+            Some(description) => format!("{} ({})", description, settings),
+            None => settings,
+        }
+    }
+}
+
+// The profile with this name, or an error listing the ones there are
+pub fn find<'a>(profiles: &'a BTreeMap<String, Profile>, name: &str) -> Result<&'a Profile> {
+    profiles.get(name).ok_or_else(|| Error::UnknownProfile {
+        name: name.to_string(),
+        known: profiles.keys().cloned().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(toml: &str) -> BTreeMap<String, Profile> {
+        #[derive(Deserialize)]
+        struct File {
+            profiles: BTreeMap<String, Profile>,
+        }
+        toml::from_str::<File>(toml).unwrap().profiles
+    }
+
+    #[test]
+    fn profiles_parse_and_summarize() {
+        let profiles = profiles(
+            r#"
+            [profiles.writing]
+            description = "Long stretches"
+            focus = "50m"
+            break_time = 600
+            cycles = 3
+            block = ["wifi", "apps"]
+            sound = "chime"
+
+            [profiles.coding]
+            schedule = "2x25m/5m"
+            tags = ["code"]
+            "#,
+        );
+        let writing = find(&profiles, "writing").unwrap();
+        assert_eq!(writing.focus, Some(3000));
+        assert_eq!(writing.break_time, Some(600));
+        assert_eq!(
+            writing.summary(),
+            "Long stretches (focus 50m, break 10m, 3 cycles, block wifi,apps, sound chime)"
+        );
+        assert_eq!(
+            find(&profiles, "coding").unwrap().summary(),
+            "25m/5m,25m/5m, tags code"
+        );
+
+        let error = find(&profiles, "reading").unwrap_err();
+        assert!(
+            matches!(error, Error::UnknownProfile { known, .. } if known == ["coding", "writing"])
+        );
+    }
+
+    #[test]
+    fn profile_hooks_replace_by_event() {
+        let base = Hooks {
+            on_focus_start: Some("base start".to_string()),
+            on_break_start: Some("base break".to_string()),
+            ..Hooks::default()
+        };
+        let profile = Profile {
+            hooks: Hooks {
+                on_focus_start: Some("profile start".to_string()),
+                ..Hooks::default()
+            },
+            ..Profile::default()
+        };
+        let hooks = profile.hooks_over(&base);
+        assert_eq!(hooks.on_focus_start.as_deref(), Some("profile start"));
+        assert_eq!(hooks.on_break_start.as_deref(), Some("base break"));
+    }
+}
@@ -0,0 +1,255 @@
+// End-of-day report from the session log: pomodoros completed, focus time,
+// the longest streak and the tasks that took the most time.
+//
+//   report_at = "18:00"      (config file)
+//   pomo report              today's report
+//   pomo report --date yesterday
+//
+// A running session prints the report and sends it as a notification (with
+// the `end` event) at `report_at`, and when its last cycle ends. The streak is
+// the longest run of pomodoros completed back to back that day; a skipped,
+// stopped, abandoned or voided one ends it.
+
+use crate::{
+    duration::format_duration,
+    goal::Progress,
+    history::{self, Outcome, PhaseRecord},
+    runner::AppState,
+    say,
+    session::{Phase, PomodoroSession},
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use std::{collections::HashMap, fmt, sync::Arc, thread, time::Duration};
+
+/// Tasks listed in the report
+const TOP_TASKS: usize = 3;
+
+/// How often the report time is checked for; the wall clock is compared each
+/// time, so system sleep doesn't throw it off
+const CHECK_EVERY: Duration = Duration::from_secs(20);
+
+/// One day of the session log, summed up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayReport {
+    pub date: NaiveDate,
+    pub pomodoros: u32,
+    /// Focus time including overtime, in seconds
+    pub focus: u64,
+    /// Most pomodoros completed back to back
+    pub streak: u32,
+    /// Tasks with the most focus time (seconds), most first
+    pub top_tasks: Vec<(String, u64)>,
+}
+
+impl DayReport {
+    pub fn of(records: &[PhaseRecord], date: NaiveDate) -> Self {
+        let mut focus: Vec<&PhaseRecord> = records
+            .iter()
+            .filter(|r| r.phase == Phase::Focus && r.started_at.date_naive() == date)
+            .collect();
+        focus.sort_by_key(|r| r.started_at);
+
+        let mut report = DayReport {
+            date,
+            pomodoros: 0,
+            focus: 0,
+            streak: 0,
+            top_tasks: Vec::new(),
+        };
+        let mut run = 0;
+        let mut by_task: HashMap<&str, u64> = HashMap::new();
+        for record in focus {
+            if record.outcome == Outcome::Completed {
+                report.pomodoros += 1;
+                run += 1;
+                report.streak = report.streak.max(run);
+            } else {
+                run = 0;
+            }
+            let seconds = record.actual + record.overtime;
+            report.focus += seconds;
+            if let Some(task) = &record.task {
+                *by_task.entry(task).or_default() += seconds;
+            }
+        }
+        let mut tasks: Vec<(String, u64)> = by_task
+            .into_iter()
+            .map(|(task, seconds)| (task.to_string(), seconds))
+            .collect();
+        tasks.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        tasks.truncate(TOP_TASKS);
+        report.top_tasks = tasks;
+        report
+    }
+
+    /// The report in one line, for a notification
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} pomodoro{}, {} of focus",
+            self.pomodoros,
+            if self.pomodoros == 1 { "" } else { "s" },
+            format_duration(self.focus)
+        );
+        if self.streak > 1 {
+            summary.push_str(&format!(", longest streak {}", self.streak));
+        }
+        if let Some((task, _)) = self.top_tasks.first() {
+            summary.push_str(&format!(". Top task: {}", task));
+        }
+        summary
+    }
+}
+
+impl fmt::Display for DayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Report for {}", self.date.format("%a %Y-%m-%d"))?;
+        writeln!(f, "  Pomodoros completed: {}", self.pomodoros)?;
+        writeln!(f, "  Focus time:          {}", format_duration(self.focus))?;
+        write!(f, "  Longest streak:      {}", self.streak)?;
+        if !self.top_tasks.is_empty() {
+            write!(f, "\n  Top tasks:")?;
+            let width = self
+                .top_tasks
+                .iter()
+                .map(|(task, _)| task.chars().count())
+                .max()
+                .unwrap_or(0);
+            for (task, seconds) in &self.top_tasks {
+                write!(
+                    f,
+                    "\n    {:<width$}  {:>8}",
+                    task,
+                    format_duration(*seconds),
+                    width = width
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Print today's report and send its summary as a notification (with where
+// the daily goal stands, if there is one)
+pub fn deliver(app_state: &AppState, session: &PomodoroSession) {
+    let records = match history::load() {
+        Ok(records) => records,
+        Err(e) => {
+            log::warn!("Failed to read session log: {}", e);
+            return;
+        }
+    };
+    let today = Local::now().date_naive();
+    let report = DayReport::of(&records, today);
+    for line in report.to_string().lines() {
+        say!("{}", line);
+    }
+    let progress = session
+        .daily_goal
+        .map(|goal| Progress::of(&records, goal, today));
+    if let Err(e) = session.notify.day_report(
+        app_state.notifier.as_ref(),
+        session,
+        &report,
+        progress.as_ref(),
+    ) {
+        log::warn!("Failed to send the day report: {}", e);
+    }
+}
+
+// Deliver the report each day at `at` while the session runs
+pub fn deliver_at(at: NaiveTime, session: PomodoroSession, app_state: Arc<AppState>) {
+    thread::spawn(move || {
+        let mut last_check = Local::now().naive_local();
+        loop {
+            thread::sleep(CHECK_EVERY);
+            let now = Local::now().naive_local();
+            if is_due(at, last_check, now) {
+                // The latest snapshot has the task and tags for the subtitle
+                let latest = app_state.session.lock().unwrap().clone();
+                deliver(&app_state, latest.as_ref().unwrap_or(&session));
+            }
+            last_check = now;
+        }
+    });
+}
+
+// Whether `at` (on any day) falls after `from` and no later than `to`
+fn is_due(at: NaiveTime, from: NaiveDateTime, to: NaiveDateTime) -> bool {
+    let mut day = from.date();
+    while day <= to.date() {
+        let time = day.and_time(at);
+        if time > from && time <= to {
+            return true;
+        }
+        match day.succ_opt() {
+            Some(next) => day = next,
+            None => break,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duration::parse_time;
+    use chrono::TimeZone;
+
+    fn focus(hour: u32, task: Option<&str>, outcome: Outcome) -> PhaseRecord {
+        let started_at = Local.with_ymd_and_hms(2024, 3, 5, hour, 0, 0).unwrap();
+        let mut session = PomodoroSession::new(1500, 300, 4);
+        session.task = task.map(str::to_string);
+        session.elapsed = 1500;
+        session.phase_started_at = Some(started_at);
+        PhaseRecord::from_session(&session, outcome)
+    }
+
+    #[test]
+    fn report_sums_up_the_day() {
+        let records = vec![
+            focus(9, Some("write"), Outcome::Completed),
+            focus(10, Some("write"), Outcome::Completed),
+            focus(11, Some("mail"), Outcome::Skipped),
+            focus(13, None, Outcome::Completed),
+            focus(14, Some("review"), Outcome::Completed),
+            focus(15, Some("write"), Outcome::Completed),
+        ];
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let report = DayReport::of(&records, date);
+        assert_eq!(report.pomodoros, 5);
+        assert_eq!(report.focus, 6 * 1500);
+        assert_eq!(report.streak, 3);
+        assert_eq!(
+            report.top_tasks,
+            [
+                ("write".to_string(), 4500),
+                ("mail".to_string(), 1500),
+                ("review".to_string(), 1500)
+            ]
+        );
+        assert_eq!(
+            report.summary(),
+            "5 pomodoros, 2h30m of focus, longest streak 3. Top task: write"
+        );
+
+        let other_day = DayReport::of(&records, date.succ_opt().unwrap());
+        assert_eq!((other_day.pomodoros, other_day.focus), (0, 0));
+    }
+
+    #[test]
+    fn report_time_is_due_once_passed() {
+        let at = parse_time("18:00").unwrap();
+        let time = |day, h, m| {
+            NaiveDate::from_ymd_opt(2024, 3, day)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        assert!(is_due(at, time(5, 17, 59), time(5, 18, 0)));
+        assert!(!is_due(at, time(5, 18, 0), time(5, 18, 1)));
+        assert!(!is_due(at, time(5, 9, 0), time(5, 17, 0)));
+        // Asleep over the report time
+        assert!(is_due(at, time(5, 17, 0), time(6, 8, 0)));
+        assert!(parse_time("6pm").is_err());
+    }
+}
@@ -0,0 +1,1282 @@
+// Runs a session in real time: drives the PomodoroSession state machine and a
+// PhaseTimer per phase, engages/releases blockers at phase transitions, shows
+// the progress bar, sends notifications, writes the session log and keeps the
+// state file up to date. Control requests (pause, skip, ...) arrive through the
+// shared AppState from the stdin thread or the control socket. Time comes from
+// the AppState's Clock (clock.rs), so tests can run phases without waiting.
+
+use crate::{
+    blockers::Blockers,
+    clock::{Clock, SystemClock},
+    dry_run,
+    duration::format_duration,
+    enforce,
+    error::{Error, Result},
+    goal::Progress,
+    history::{self, Outcome, PhaseRecord},
+    hooks::HookEvent,
+    inbox::{self, Note},
+    integrations::Integrations,
+    notify::Notifier,
+    output::{self, Event, Snapshot},
+    plan,
+    power::{IdleAction, IdleWatch, SleepDetector},
+    say,
+    session::{Interruption, Phase, PomodoroSession, cycle_of},
+    sound::Sound,
+    speech, state,
+    statusline::clock,
+    theme,
+    timer::{self, PhaseTimer},
+    tui,
+};
+use chrono::Local;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{
+    io,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// How often (in seconds) the session state is written to disk
+const SAVE_INTERVAL: u64 = 5;
+
+/// Seconds between lines of progress when there is no progress bar
+/// (`--non-interactive`)
+const PROGRESS_LINE_INTERVAL: u64 = 60;
+
+/// How long the end of a session waits for announcements still being spoken
+const SPEECH_GRACE: Duration = Duration::from_secs(10);
+
+/// Longest sleep between timer checks
+const TICK: Duration = Duration::from_millis(250);
+
+/// Seconds the end of a `--until` phase may drift from the wall clock before
+/// its length is worked out again
+const DEADLINE_SLACK: u64 = 2;
+
+/// Seconds the + and - keys add to or take off the running phase
+pub const EXTEND_STEP: i64 = 5 * 60;
+
+/// Seconds a phase waiting to start is put off by `z` or `pomo snooze`
+pub const SNOOZE_STEP: u64 = 5 * 60;
+
+/// Global application state
+pub struct AppState {
+    pub paused: AtomicBool,
+    /// Why the session was paused, until the runner notes it down with the
+    /// pause
+    pub pause_reason: Mutex<Option<String>>,
+    /// Between phases, waiting for the user to start the next one
+    pub waiting: AtomicBool,
+    /// Counting overtime after focus (`waiting` is set too: the same
+    /// ENTER / `pomo resume` ends it)
+    pub overtime: AtomicBool,
+    /// Counting down to the start of the next phase (`--in`, or snoozed;
+    /// `waiting` is set too: ENTER / `pomo resume` starts it right away)
+    pub snoozing: AtomicBool,
+    /// Seconds to put off the phase waiting to start, taken up by the runner
+    pub snooze: AtomicU64,
+    pub skip: AtomicBool,
+    pub stop: AtomicBool,
+    /// Seconds to add to the running phase (`pomo extend`), taken up by the
+    /// runner
+    pub extend: AtomicI64,
+    /// Stopped by a signal: keep the state file so the session can be resumed
+    pub interrupted: AtomicBool,
+    /// A blocker failed mid-session and its policy is to abort: the session
+    /// stops as if interrupted, and ends with this error
+    failure: Mutex<Option<Error>>,
+    /// Latest snapshot of the running session, for `pomo status`
+    pub session: Mutex<Option<PomodoroSession>>,
+    /// Notes for the inbox taken since the last break started
+    pub inbox: Mutex<Vec<Note>>,
+    pub blockers: Blockers,
+    pub notifier: Box<dyn Notifier>,
+    pub integrations: Integrations,
+    /// Time phases are measured in (real time unless replaced)
+    pub clock: Box<dyn Clock>,
+}
+
+impl AppState {
+    pub fn new(
+        blockers: Blockers,
+        notifier: Box<dyn Notifier>,
+        integrations: Integrations,
+    ) -> Self {
+        AppState {
+            paused: AtomicBool::new(false),
+            pause_reason: Mutex::new(None),
+            waiting: AtomicBool::new(false),
+            overtime: AtomicBool::new(false),
+            snoozing: AtomicBool::new(false),
+            snooze: AtomicU64::new(0),
+            skip: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+            extend: AtomicI64::new(0),
+            interrupted: AtomicBool::new(false),
+            failure: Mutex::new(None),
+            session: Mutex::new(None),
+            inbox: Mutex::new(Vec::new()),
+            blockers,
+            notifier,
+            integrations,
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Measure phases on another clock (sped up, or driven by a test)
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// "overtime", "snoozed", "waiting", "paused" or "running"
+    pub fn run_state(&self) -> &'static str {
+        if self.overtime.load(Ordering::SeqCst) {
+            "overtime"
+        } else if self.snoozing.load(Ordering::SeqCst) {
+            "snoozed"
+        } else if self.waiting.load(Ordering::SeqCst) {
+            "waiting"
+        } else if self.paused.load(Ordering::SeqCst) {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+
+    /// Pause the session, saying why if known. A reason given while already
+    /// paused goes with the current pause. False between phases, when there
+    /// is nothing running to pause.
+    pub fn pause(&self, reason: Option<String>) -> bool {
+        if self.waiting.load(Ordering::SeqCst) {
+            return false;
+        }
+        if reason.is_some() {
+            *self.pause_reason.lock().unwrap() = reason;
+        }
+        self.paused.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Lengthen (or shorten) the running phase; false between phases, when
+    /// there is nothing to extend
+    pub fn extend(&self, seconds: i64) -> bool {
+        if self.waiting.load(Ordering::SeqCst) || self.session.lock().unwrap().is_none() {
+            return false;
+        }
+        self.extend.fetch_add(seconds, Ordering::SeqCst);
+        true
+    }
+
+    /// Put off the phase waiting to start (or further off, while counting
+    /// down to it); false when no phase is waiting
+    pub fn snooze(&self, seconds: u64) -> bool {
+        if !self.waiting.load(Ordering::SeqCst) || self.overtime.load(Ordering::SeqCst) {
+            return false;
+        }
+        self.snooze.fetch_add(seconds, Ordering::SeqCst);
+        true
+    }
+
+    /// Add a note to the inbox, with the task in focus, without stopping
+    /// the timer; it is shown again when the next break starts
+    pub fn note(&self, text: &str) -> io::Result<()> {
+        let task = self
+            .session
+            .lock()
+            .unwrap()
+            .as_ref()
+            .and_then(|session| session.task.clone());
+        let note = Note::new(text, task);
+        inbox::add(&note)?;
+        self.inbox.lock().unwrap().push(note);
+        Ok(())
+    }
+
+    // Re-engage the blockers after a pause or sleep; one that fails and
+    // aborts stops the session, keeping its position for `pomo resume`
+    fn reengage(&self) {
+        if let Err(e) = self.blockers.engage() {
+            self.failure.lock().unwrap().get_or_insert(e);
+            self.interrupted.store(true, Ordering::SeqCst);
+            self.stop.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+}
+
+// Run the session to the end (or until stopped), then release all blockers
+// and remove the state file (unless interrupted by a signal)
+pub fn run(session: PomodoroSession, app_state: &AppState) -> Result<()> {
+    let sound = session.sound.clone();
+    let hooks = session.hooks.clone();
+    if let Some(seconds) = session.start_in {
+        *app_state.session.lock().unwrap() = Some(session.clone());
+        count_down(&session, app_state, seconds);
+    }
+    let last = run_session(session, app_state)?;
+
+    // Ensure WiFi is on at the end
+    app_state.blockers.release()?;
+    app_state.integrations.session_end();
+    let stopped = app_state.stopped();
+    if app_state.interrupted() {
+        // The state file stays for `pomo resume`
+        say!("Session interrupted. Run `pomo resume` to continue.");
+    } else if stopped {
+        state::clear()?;
+        say!("Session stopped.");
+    } else {
+        state::clear()?;
+        play(&sound);
+        hook(hooks.run(HookEvent::Complete, &last));
+        match last.repeat_until {
+            Some(stop) => say!(
+                "All done: the next phase would run past {}.",
+                stop.format("%H:%M")
+            ),
+            None => say!("All cycles finished!"),
+        }
+    }
+    if !app_state.interrupted() {
+        if stopped {
+            let progress = goal_progress(&last);
+            report(
+                last.notify
+                    .stopped(app_state.notifier.as_ref(), &last, progress.as_ref()),
+            );
+        } else {
+            // The day so far, now that the last cycle ended
+            crate::report::deliver(app_state, &last);
+        }
+    }
+    output::emit(&Event::Finished { stopped });
+    // Let the last announcement finish
+    speech::finish(SPEECH_GRACE);
+    match app_state.failure.lock().unwrap().take() {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+// Run the remaining cycles of a session, starting from its saved position.
+// Returns the session as it ended.
+fn run_session(mut session: PomodoroSession, app_state: &AppState) -> Result<PomodoroSession> {
+    // The blockers came off when focus ended (a resumed break releases them
+    // when it starts)
+    let mut released = false;
+    while !session.is_finished() && !app_state.stopped() {
+        match session.phase {
+            Phase::Focus => {
+                say!(
+                    "=== Cycle {}: Focus time ===",
+                    cycle_of(session.cycle, session.cycles)
+                );
+                // A resumed phase keeps its task
+                if session.plan && session.elapsed == 0 {
+                    plan::pick_next(&mut session);
+                }
+
+                // Integrations go first, while the network is still up
+                app_state.integrations.phase_start(&session);
+
+                // Turn WiFi (and any other blockers) off for focus
+                app_state.blockers.engage()?;
+                released = false;
+            }
+            Phase::Break => {
+                say!("=== Break time ===");
+
+                // Turn WiFi on for break
+                if !std::mem::take(&mut released) {
+                    app_state.blockers.release()?;
+                }
+                app_state.integrations.phase_start(&session);
+            }
+        }
+        start_phase(&mut session, app_state);
+
+        let outcome = match session.enforce_break {
+            Some(mode) if session.phase == Phase::Break => {
+                enforce::during_break(mode, app_state, || run_timer(&mut session, app_state))
+            }
+            _ => run_timer(&mut session, app_state),
+        };
+        // Focus is over: unblock now, not once the break starts, so overtime,
+        // waiting for the user and snoozing aren't spent blocked
+        if session.phase == Phase::Focus && !app_state.interrupted() {
+            app_state.blockers.release()?;
+            released = true;
+        }
+        let overtime =
+            outcome == Outcome::Completed && session.phase == Phase::Focus && session.overtime;
+        if overtime && !app_state.interrupted() {
+            run_overtime(&mut session, app_state);
+        }
+        if app_state.interrupted() {
+            // Save the exact position for `pomo resume`; the phase is logged
+            // once it is finished there
+            state::save(&session)?;
+            break;
+        }
+        log_phase(&session, outcome);
+        if outcome == Outcome::Completed && session.phase == Phase::Focus {
+            plan::count_pomodoro(&session);
+        }
+        app_state.integrations.phase_end(&session, outcome);
+        if outcome == Outcome::Completed
+            && session.phase == Phase::Focus
+            && let Some(progress) = goal_progress(&session)
+        {
+            say!("{}", progress);
+            // Only the pomodoro that reaches the goal celebrates it
+            if progress.done == progress.goal {
+                report(session.notify.goal_reached(
+                    app_state.notifier.as_ref(),
+                    &session,
+                    &progress,
+                ));
+            }
+        }
+        output::emit(&Event::PhaseEnd {
+            snapshot: Snapshot::of(&session),
+            outcome,
+        });
+        if outcome == Outcome::Stopped {
+            break;
+        }
+
+        // Send notification at phase end (the end of a break is the end of a cycle);
+        // with overtime it went out when the planned time was up
+        if !overtime {
+            report(
+                session
+                    .notify
+                    .phase_end(app_state.notifier.as_ref(), &session),
+            );
+        }
+        hook(session.hooks.run(HookEvent::PhaseEnd, &session));
+
+        let next = session.advance();
+        state::save(&session)?;
+
+        // Ending overtime already meant the user is back
+        if next.is_some() && !session.auto_advance && !overtime {
+            wait_for_next_phase(&session, app_state);
+        }
+    }
+    Ok(session)
+}
+
+// Hold off the next phase until the user is back: ENTER in the terminal, or
+// `pomo resume` / `pomo skip` from anywhere. Snoozing (`z` or `pomo snooze`)
+// starts it after a countdown instead.
+fn wait_for_next_phase(session: &PomodoroSession, app_state: &AppState) {
+    let next = match session.phase {
+        Phase::Focus => "next pomodoro",
+        Phase::Break => "break",
+    };
+    if output::is_interactive() {
+        say!(
+            "Press ENTER (or run `pomo resume`) to start the {}, or type `z` to snooze it {}.",
+            next,
+            format_duration(SNOOZE_STEP)
+        );
+    } else {
+        say!(
+            "Run `pomo resume` to start the {}, or `pomo snooze` to put it off {}.",
+            next,
+            format_duration(SNOOZE_STEP)
+        );
+    }
+    if output::is_interactive()
+        && session.plan
+        && session.phase == Phase::Focus
+        && let Some(task) = &session.task
+    {
+        say!(
+            "Type `done` + ENTER if \"{}\" is finished, to move on to the next task.",
+            task
+        );
+    }
+    report(session.notify.waiting(app_state.notifier.as_ref(), session));
+    output::emit(&Event::Waiting(Snapshot::of(session)));
+
+    *app_state.session.lock().unwrap() = Some(session.clone());
+    app_state.waiting.store(true, Ordering::SeqCst);
+    while app_state.waiting.load(Ordering::SeqCst) && !app_state.stopped() {
+        let snooze = app_state.snooze.swap(0, Ordering::SeqCst);
+        if snooze > 0 {
+            count_down(session, app_state, snooze);
+            break;
+        }
+        app_state.clock.sleep(TICK);
+    }
+    app_state.waiting.store(false, Ordering::SeqCst);
+}
+
+// Count down `seconds` before the next phase starts (`--in`, or snoozed
+// between phases), so there is time to wrap up without the timer running.
+// ENTER / `pomo resume` starts it right away; snoozing again adds to the wait.
+fn count_down(session: &PomodoroSession, app_state: &AppState, seconds: u64) {
+    let phase = match session.phase {
+        Phase::Focus => "Focus",
+        Phase::Break => "Break",
+    };
+    say!(
+        "{} starts in {}. {} to start now.",
+        phase,
+        format_duration(seconds),
+        resume_hint()
+    );
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.blue} {prefix:.blue.bold} {msg}").unwrap(),
+    );
+    hide_bar(&pb);
+    output::attach_bar(&pb);
+    pb.set_message(bar_message(session));
+
+    let start = app_state.clock.now();
+    let mut length = seconds;
+    app_state.snoozing.store(true, Ordering::SeqCst);
+    app_state.waiting.store(true, Ordering::SeqCst);
+    while app_state.waiting.load(Ordering::SeqCst) && !app_state.stopped() {
+        length += app_state.snooze.swap(0, Ordering::SeqCst);
+        let passed = (app_state.clock.now() - start).as_secs();
+        if passed >= length {
+            break;
+        }
+        pb.set_prefix(format!("{} starts in {}", phase, clock(length - passed)));
+        pb.tick();
+        app_state.clock.sleep(TICK);
+    }
+    app_state.snoozing.store(false, Ordering::SeqCst);
+    app_state.waiting.store(false, Ordering::SeqCst);
+    output::detach_bar();
+    pb.finish_and_clear();
+}
+
+// Show a countdown in the console using indicatif, checking for pause state.
+// The session's elapsed time is advanced in place and saved every few seconds.
+fn run_timer(session: &mut PomodoroSession, app_state: &AppState) -> Outcome {
+    // Focus => WiFi should be off when not paused
+    // Break => WiFi should be on when not paused
+    let focus_mode = session.phase == Phase::Focus;
+    let mut seconds = session.phase_len();
+    // Requests made between phases were refused, so none are left over
+    app_state.extend.store(0, Ordering::SeqCst);
+
+    if output::is_interactive() {
+        say!(
+            "Starting timer for {}... (Type 'p' + ENTER to pause, 'q' + ENTER to stop)",
+            format_duration(seconds)
+        );
+    } else {
+        say!("Starting timer for {}...", format_duration(seconds));
+    }
+
+    // The bar counts milliseconds so it moves smoothly between seconds
+    let pb = ProgressBar::new(seconds * 1000);
+    hide_bar(&pb);
+    output::attach_bar(&pb);
+    show_state(&pb, session, false);
+
+    let clock = app_state.clock.as_ref();
+    let mut timer = PhaseTimer::start(
+        Duration::from_secs(seconds),
+        Duration::from_secs(session.elapsed),
+        clock.now(),
+    );
+    let mut last_saved = session.elapsed;
+    let mut last_tick = None;
+    // Pause time from before a resume (and system sleep); the timer only
+    // counts pauses since then
+    let mut paused_before = session.paused;
+    // Not on a sped-up or test clock, whose time the system's doesn't match
+    let mut sleep = clock.readings().map(SleepDetector::new);
+    let mut idle_watch = IdleWatch::new(&session.idle).filter(|_| focus_mode);
+    // When the user went idle, while paused for it
+    let mut idle_since = None;
+    // --strict: the pomodoro was interrupted and starts over
+    let mut restart = false;
+
+    // Warn once when the remaining time drops to `warn_before` (not if a resumed
+    // phase is already past that point)
+    let mut warn_at = warning_at(session);
+
+    let outcome = loop {
+        let now = clock.now();
+
+        if std::mem::take(&mut restart) {
+            void_pomodoro(session, app_state);
+            seconds = session.phase_len();
+            timer = PhaseTimer::start(Duration::from_secs(seconds), Duration::ZERO, now);
+            paused_before = 0;
+            last_saved = 0;
+            last_tick = None;
+            warn_at = warning_at(session);
+            pb.set_length(seconds * 1000);
+            pb.set_position(0);
+            pb.reset_eta();
+            pb.set_message(bar_message(session));
+        }
+
+        let gap = sleep
+            .as_mut()
+            .zip(clock.readings())
+            .and_then(|(sleep, readings)| sleep.check(readings))
+            .unwrap_or_default();
+        // The wall clock was set: timing goes on as before, but the start of the
+        // phase moves with the clock so the session log adds up
+        if gap.clock_set != 0 {
+            say!(
+                "The system clock was set {} by {}; the timer is not affected.",
+                if gap.clock_set > 0 { "forward" } else { "back" },
+                format_duration(gap.clock_set.unsigned_abs())
+            );
+            let by = chrono::Duration::seconds(gap.clock_set);
+            session.phase_started_at = session.phase_started_at.map(|start| start + by);
+            for interruption in &mut session.interruptions {
+                interruption.started_at += by;
+            }
+        }
+
+        // The machine slept (which the timer didn't count): treat it as a pause,
+        // and put the blockers back in case waking up undid them
+        if !gap.slept.is_zero() {
+            let slept = gap.slept;
+            timer.leave_out(gap.counted, now);
+            say!(
+                "System was asleep for {}; counting it as a pause.",
+                format_duration(slept.as_secs())
+            );
+            paused_before += slept.as_secs();
+            session.paused = paused_before + timer.paused(now).as_secs();
+            session.pauses += 1;
+            session.interruptions.push(Interruption {
+                started_at: Local::now() - slept,
+                seconds: slept.as_secs(),
+                reason: Some("system asleep".to_string()),
+            });
+            output::emit(&Event::Pause(Snapshot::of(session)));
+            hook(session.hooks.run(HookEvent::Pause, session));
+            if abandon(session, app_state) {
+                break Outcome::Abandoned;
+            }
+            if focus_mode && !app_state.paused.load(Ordering::SeqCst) {
+                app_state.reengage();
+            }
+            pb.set_message(bar_message(session));
+            output::emit(&Event::Resume(Snapshot::of(session)));
+            hook(session.hooks.run(HookEvent::Resume, session));
+            if session.strict && focus_mode {
+                restart = true;
+                continue;
+            }
+        }
+
+        if let Some(length) = take_extension(session, app_state, &mut timer, &pb) {
+            seconds = length;
+            warn_at = warning_at(session);
+        }
+        if let Some(length) = follow_deadline(session, &mut timer, &pb, now) {
+            seconds = length;
+        }
+
+        if timer.is_finished(now) {
+            session.elapsed = seconds;
+            break Outcome::Completed;
+        }
+        let elapsed = timer.elapsed(now);
+        session.elapsed = elapsed.as_secs();
+        *app_state.session.lock().unwrap() = Some(session.clone());
+
+        // A remote `skip` ends the phase early, `stop` ends the whole session
+        if app_state.skip.swap(false, Ordering::SeqCst) {
+            if session.strict && focus_mode {
+                say!("Skipping to the next phase; this pomodoro doesn't count (--strict).");
+                break Outcome::Void;
+            }
+            say!("Skipping to the next phase.");
+            break Outcome::Skipped;
+        }
+        if app_state.stopped() {
+            break Outcome::Stopped;
+        }
+
+        // Nobody at the keyboard for a while: that stretch wasn't focus
+        if let Some(watch) = idle_watch.as_mut()
+            && let Some(idle) = watch.idle(now)
+        {
+            let since = now.checked_sub(idle).unwrap_or(now);
+            timer.pause_since(since, now);
+            session.elapsed = timer.elapsed(now).as_secs();
+            match session.idle.action {
+                IdleAction::Pause => {
+                    say!(
+                        "Idle for {}; pausing until you're back.",
+                        format_duration(idle.as_secs())
+                    );
+                    idle_since = Some(since);
+                    app_state.pause(Some("idle".to_string()));
+                }
+                IdleAction::Abandon => {
+                    say!(
+                        "Idle for {}; abandoning this phase.",
+                        format_duration(idle.as_secs())
+                    );
+                    session.idle_elapsed += idle.as_secs();
+                    break Outcome::Abandoned;
+                }
+            }
+        }
+
+        // If paused, keep WiFi ON if we are in focus mode
+        if app_state.paused.load(Ordering::SeqCst) {
+            // Freeze the clock before touching Wi-Fi
+            timer.pause(now);
+            session.pauses += 1;
+            let paused_at = now;
+            session.interruptions.push(Interruption {
+                started_at: Local::now(),
+                seconds: 0,
+                reason: app_state.pause_reason.lock().unwrap().take(),
+            });
+            log::debug!(
+                "paused at {} of {} (pause {} in this phase)",
+                format_duration(session.elapsed),
+                format_duration(seconds),
+                session.pauses
+            );
+            show_state(&pb, session, true);
+            output::emit(&Event::Pause(Snapshot::of(session)));
+            hook(session.hooks.run(HookEvent::Pause, session));
+            if focus_mode {
+                let _ = app_state.blockers.release();
+            }
+            // Wait in paused state until unpaused, or until the pause budget runs out
+            while app_state.paused.load(Ordering::SeqCst)
+                && !app_state.stopped()
+                && !app_state.skip.load(Ordering::SeqCst)
+            {
+                let now = clock.now();
+                session.paused = paused_before + timer.paused(now).as_secs();
+                if let Some(interruption) = session.interruptions.last_mut() {
+                    interruption.seconds = now.saturating_duration_since(paused_at).as_secs();
+                    // Given at the prompt after `p`, or another `pomo pause --reason`
+                    if let Some(reason) = app_state.pause_reason.lock().unwrap().take() {
+                        log::debug!("pause reason: {}", reason);
+                        interruption.reason = Some(reason);
+                    }
+                }
+                if let Some(length) = take_extension(session, app_state, &mut timer, &pb) {
+                    seconds = length;
+                    warn_at = warning_at(session);
+                }
+                pb.set_message(bar_message(session));
+                *app_state.session.lock().unwrap() = Some(session.clone());
+                if session.pause_budget_spent() {
+                    break;
+                }
+                if let Some(since) = idle_since
+                    && let Some(watch) = idle_watch.as_mut()
+                    && watch.back(since, now)
+                {
+                    say!("Welcome back; resuming.");
+                    app_state.paused.store(false, Ordering::SeqCst);
+                }
+                clock.sleep(Duration::from_millis(500));
+            }
+            if let Some(since) = idle_since.take() {
+                session.idle_elapsed += clock.now().saturating_duration_since(since).as_secs();
+            }
+            if abandon(session, app_state) {
+                break Outcome::Abandoned;
+            }
+            // A skip or stop ends the pause with the phase: the blockers stay
+            // off, and the next phase doesn't start out paused
+            if app_state.stopped() || app_state.skip.load(Ordering::SeqCst) {
+                app_state.paused.store(false, Ordering::SeqCst);
+                continue;
+            }
+            // Once unpaused, if focus_mode, turn WiFi off again
+            if focus_mode {
+                app_state.reengage();
+            }
+            timer.resume(clock.now());
+            log::debug!(
+                "resumed; paused {} in this phase",
+                format_duration(session.paused)
+            );
+            show_state(&pb, session, false);
+            output::emit(&Event::Resume(Snapshot::of(session)));
+            hook(session.hooks.run(HookEvent::Resume, session));
+            restart = session.strict && focus_mode;
+            continue;
+        }
+
+        pb.set_position(elapsed.as_millis() as u64);
+        if last_tick != Some(session.elapsed) {
+            last_tick = Some(session.elapsed);
+            output::emit(&Event::Tick(Snapshot::of(session)));
+            if !output::is_interactive()
+                && !output::is_json()
+                && session.elapsed > 0
+                && session.elapsed < seconds
+                && session.elapsed.is_multiple_of(PROGRESS_LINE_INTERVAL)
+            {
+                say!("{}", progress_line(session));
+            }
+        }
+
+        if let Some(warn) = warn_at
+            && seconds.saturating_sub(session.elapsed) <= warn
+        {
+            report(
+                session
+                    .notify
+                    .warning(app_state.notifier.as_ref(), session, warn),
+            );
+            warn_at = None;
+        }
+
+        if session.elapsed >= last_saved + SAVE_INTERVAL {
+            last_saved = session.elapsed;
+            if let Err(e) = state::save(session) {
+                log::warn!("Failed to save session state: {}", e);
+            }
+        }
+
+        // Sleep until the next whole second (or the end), but wake up often
+        // enough that pause/skip requests are picked up promptly
+        let until_next_second = Duration::from_secs(session.elapsed + 1) - elapsed;
+        clock.sleep(until_next_second.min(timer.remaining(now)).min(TICK));
+    };
+    pb.set_position(session.elapsed * 1000);
+    output::detach_bar();
+    if outcome == Outcome::Completed {
+        pb.finish_with_message("Done!");
+    } else {
+        // Leave the bar where the phase was cut short
+        pb.abandon();
+    }
+    outcome
+}
+
+// When to send the end-of-phase warning (seconds before the end), unless the
+// phase is already past that point
+fn warning_at(session: &PomodoroSession) -> Option<u64> {
+    session
+        .notify
+        .warning_at(session.phase_len())
+        .filter(|&warn| session.remaining() > warn)
+}
+
+// Apply `pomo extend` requests (and +/- keys) to the phase; returns its new
+// length if it changed
+fn take_extension(
+    session: &mut PomodoroSession,
+    app_state: &AppState,
+    timer: &mut PhaseTimer,
+    pb: &ProgressBar,
+) -> Option<u64> {
+    let by = app_state.extend.swap(0, Ordering::SeqCst);
+    if by == 0 {
+        return None;
+    }
+    let before = session.adjusted;
+    session.extend(by);
+    let by = session.adjusted - before;
+    if by == 0 {
+        say!("The phase can't be shortened any further.");
+        return None;
+    }
+    let seconds = session.phase_len();
+    timer.set_target(Duration::from_secs(seconds));
+    pb.set_length(seconds * 1000);
+    say!(
+        "{} the phase by {}; it is now {}.",
+        if by > 0 { "Extended" } else { "Shortened" },
+        format_duration(by.unsigned_abs()),
+        format_duration(seconds)
+    );
+    output::emit(&Event::Extend {
+        snapshot: Snapshot::of(session),
+        by,
+    });
+    *app_state.session.lock().unwrap() = Some(session.clone());
+    Some(seconds)
+}
+
+// With --overtime, keep counting up past the end of focus until the user is
+// back: ENTER in the terminal, or `pomo resume` / `pomo skip` from anywhere.
+// The extra time goes in `session.overtime_elapsed`, apart from `elapsed`.
+fn run_overtime(session: &mut PomodoroSession, app_state: &AppState) {
+    // Not again when an interrupted overtime is resumed
+    if session.overtime_elapsed == 0 {
+        report(
+            session
+                .notify
+                .phase_end(app_state.notifier.as_ref(), session),
+        );
+    }
+    say!(
+        "Focus time is up; counting overtime. {} to take the break.",
+        resume_hint()
+    );
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.yellow} {prefix:.yellow.bold} {msg}").unwrap(),
+    );
+    hide_bar(&pb);
+    output::attach_bar(&pb);
+    pb.set_message(bar_message(session));
+
+    let counted_before = session.overtime_elapsed;
+    let start = app_state.clock.now();
+    let mut last_saved = counted_before;
+    let mut last_tick = None;
+    app_state.overtime.store(true, Ordering::SeqCst);
+    app_state.waiting.store(true, Ordering::SeqCst);
+    while app_state.waiting.load(Ordering::SeqCst) && !app_state.stopped() {
+        session.overtime_elapsed = counted_before + (app_state.clock.now() - start).as_secs();
+        *app_state.session.lock().unwrap() = Some(session.clone());
+        pb.set_prefix(format!("+{} overtime", clock(session.overtime_elapsed)));
+        pb.tick();
+
+        if last_tick != Some(session.overtime_elapsed) {
+            last_tick = Some(session.overtime_elapsed);
+            output::emit(&Event::Tick(Snapshot::of(session)));
+        }
+        if session.overtime_elapsed >= last_saved + SAVE_INTERVAL {
+            last_saved = session.overtime_elapsed;
+            if let Err(e) = state::save(session) {
+                log::warn!("Failed to save session state: {}", e);
+            }
+        }
+        app_state.clock.sleep(TICK);
+    }
+    app_state.overtime.store(false, Ordering::SeqCst);
+    app_state.waiting.store(false, Ordering::SeqCst);
+    output::detach_bar();
+    pb.finish_with_message("Done!");
+}
+
+// Show the phase, cycle and pause state on the bar, in the session's theme
+// (theme.rs), e.g.
+//   Focus 2/4 [#########---------------] 23:52  38% ends 14:25 write report
+//   ⏸ PAUSED Focus 2/4 [#########---------------] 23:52  38% ends 14:31 write report (paused 6m)
+fn show_state(pb: &ProgressBar, session: &PomodoroSession, paused: bool) {
+    let today = if session.theme.counts_pomodoros() {
+        theme::completed_today()
+    } else {
+        0
+    };
+    pb.set_style(
+        session
+            .theme
+            .style(&session.progress, session.phase, paused, today),
+    );
+    pb.set_prefix(bar_prefix(session, paused));
+    pb.set_message(bar_message(session));
+}
+
+// The --tui dashboard shows progress itself, and without a terminal
+// (--non-interactive) it goes in lines of its own
+fn hide_bar(pb: &ProgressBar) {
+    if tui::is_active() || !output::is_interactive() {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
+}
+
+// Whether the phase has to be abandoned for running over its pause budget.
+// If so, the pause is lifted so the next phase doesn't start out paused.
+fn abandon(session: &PomodoroSession, app_state: &AppState) -> bool {
+    let Some(max) = session.max_pause.filter(|_| session.pause_budget_spent()) else {
+        return false;
+    };
+    say!(
+        "Paused for more than {}; abandoning this phase.",
+        format_duration(max)
+    );
+    app_state.paused.store(false, Ordering::SeqCst);
+    true
+}
+
+// Progress bar prefix: phase and cycle, behind a pause sign while paused
+fn bar_prefix(session: &PomodoroSession, paused: bool) -> String {
+    let phase = match session.phase {
+        Phase::Focus => "Focus",
+        Phase::Break => "Break",
+    };
+    let prefix = format!("{} {}", phase, cycle_of(session.cycle, session.cycles));
+    if paused {
+        format!("⏸ PAUSED {}", prefix)
+    } else {
+        prefix
+    }
+}
+
+// Progress as a line, for --non-interactive, e.g.
+//   Focus 2/4: 12:00 left, ends 14:25 write report
+fn progress_line(session: &PomodoroSession) -> String {
+    let ends = Local::now() + chrono::Duration::seconds(session.remaining() as i64);
+    let line = format!(
+        "{}: {} left, ends {}",
+        bar_prefix(session, false),
+        clock(session.remaining()),
+        ends.format("%H:%M")
+    );
+    match bar_message(session) {
+        message if message.is_empty() => line,
+        message => format!("{} {}", line, message),
+    }
+}
+
+// How to start the next phase: from the terminal or, without one, from
+// another shell
+fn resume_hint() -> &'static str {
+    if output::is_interactive() {
+        "Press ENTER (or run `pomo resume`)"
+    } else {
+        "Run `pomo resume`"
+    }
+}
+
+// Progress bar message: the task label, plus the pause time once there has
+// been a pause (and the budget, with --max-pause)
+fn bar_message(session: &PomodoroSession) -> String {
+    let mut parts: Vec<String> = session.label().into_iter().collect();
+    if let Some(planned) = session.planned.filter(|_| session.phase == Phase::Focus) {
+        parts.push(format!("[{}]", planned));
+    }
+    if session.paused > 0 {
+        let mut pause = format!("paused {}", format_duration(session.paused));
+        if let Some(max) = session.max_pause {
+            pause.push_str(&format!(" / {}", format_duration(max)));
+        }
+        parts.push(format!("({})", pause));
+    }
+    parts.join(" ")
+}
+
+// Note the start time and announce the phase, unless it is being resumed part-way through
+fn start_phase(session: &mut PomodoroSession, app_state: &AppState) {
+    if session.phase_started_at.is_none() {
+        session.phase_started_at = Some(Local::now());
+    }
+    if session.elapsed == 0 {
+        report(
+            session
+                .notify
+                .phase_start(app_state.notifier.as_ref(), session),
+        );
+        play(&session.sound);
+        if session.phase == Phase::Break {
+            show_inbox(app_state);
+        }
+    }
+    // Hooks run on resume too, so whatever they set up matches the phase again
+    hook(session.hooks.run(HookEvent::PhaseStart, session));
+    output::emit(&Event::PhaseStart(Snapshot::of(session)));
+}
+
+// The notes taken since the last break, now that there is time for them
+fn show_inbox(app_state: &AppState) {
+    let notes = std::mem::take(&mut *app_state.inbox.lock().unwrap());
+    if notes.is_empty() {
+        return;
+    }
+    say!(
+        "Noted during focus ({}; `pomo inbox` lists them all):",
+        notes.len()
+    );
+    for note in notes {
+        say!("  - {}", note.text);
+    }
+}
+
+// --strict: an interrupted pomodoro is logged as void and focus starts over
+fn void_pomodoro(session: &mut PomodoroSession, app_state: &AppState) {
+    say!("This pomodoro was interrupted, so it doesn't count (--strict). Focus starts over.");
+    log_phase(session, Outcome::Void);
+    output::emit(&Event::PhaseEnd {
+        snapshot: Snapshot::of(session),
+        outcome: Outcome::Void,
+    });
+    session.restart_phase();
+    start_phase(session, app_state);
+}
+
+// --until: keep focus ending at the time given, whatever happened to the wall
+// clock (set by hand or by NTP, the machine slept, the phase was paused).
+// `pomo extend` moves the end with it. Returns the new length if it changed.
+fn follow_deadline(
+    session: &mut PomodoroSession,
+    timer: &mut PhaseTimer,
+    pb: &ProgressBar,
+    now: Instant,
+) -> Option<u64> {
+    // A sped-up dry run can't keep to the wall clock
+    let until = session
+        .until
+        .filter(|_| session.phase == Phase::Focus && session.time_scale <= 1)?;
+    let deadline = until + chrono::Duration::seconds(session.adjusted);
+    let length = timer::length_until(deadline, Local::now(), timer.elapsed(now)).as_secs();
+    if length.abs_diff(session.phase_len()) <= DEADLINE_SLACK {
+        return None;
+    }
+    log::debug!(
+        "focus ends at {}: {} left",
+        deadline.format("%H:%M"),
+        format_duration(length.saturating_sub(session.elapsed))
+    );
+    session.focus = length.saturating_add_signed(-session.adjusted);
+    timer.set_target(Duration::from_secs(length));
+    pb.set_length(length * 1000);
+    Some(length)
+}
+
+// Add the phase to the session log (a dry run leaves it alone)
+fn log_phase(session: &PomodoroSession, outcome: Outcome) {
+    if session.dry_run {
+        let entry = format!(
+            "cycle {} {:?} as {:?}",
+            session.cycle, session.phase, outcome
+        );
+        dry_run::action(format!("log {}", entry.to_lowercase()));
+        return;
+    }
+    if let Err(e) = history::append(&PhaseRecord::from_session(session, outcome)) {
+        log::warn!("Failed to write session log: {}", e);
+    }
+}
+
+// Today's progress toward the daily goal, if one is set
+fn goal_progress(session: &PomodoroSession) -> Option<Progress> {
+    let goal = session.daily_goal?;
+    match history::load() {
+        Ok(records) => Some(Progress::of(&records, goal, Local::now().date_naive())),
+        Err(e) => {
+            log::warn!("Failed to read session log: {}", e);
+            None
+        }
+    }
+}
+
+// A failed notification shouldn't end the session; just say so
+fn report(result: io::Result<()>) {
+    static HINTED: AtomicBool = AtomicBool::new(false);
+    if let Err(e) = result {
+        let error = Error::Notification(e);
+        log::warn!("{}", error);
+        // Once is enough for the way out
+        if let Some(hint) = error.hint()
+            && !HINTED.swap(true, Ordering::SeqCst)
+        {
+            log::warn!("  hint: {}", hint);
+        }
+    }
+}
+
+// Likewise for hook commands
+fn hook(result: io::Result<()>) {
+    if let Err(e) = result {
+        log::warn!("Failed to run hook: {}", e);
+    }
+}
+
+// Likewise for sounds
+fn play(sound: &Sound) {
+    if let Err(e) = sound.play() {
+        log::warn!("Failed to play sound '{}': {}", sound, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blockers::BlockerOptions, clock::ManualClock, dry_run::DryRunNotifier};
+    use std::{sync::Arc, thread};
+
+    fn app_state(clock: &Arc<ManualClock>) -> AppState {
+        AppState::new(
+            Blockers::new(&[], &BlockerOptions::default()).unwrap(),
+            Box::new(DryRunNotifier),
+            Integrations::default(),
+        )
+        .with_clock(Box::new(Arc::clone(clock)))
+    }
+
+    // Short enough that the state file isn't written
+    fn session() -> PomodoroSession {
+        let mut session = PomodoroSession::new(4, 2, 1);
+        session.notify.events.clear();
+        session
+    }
+
+    #[test]
+    fn bar_shows_phase_cycle_and_pause() {
+        let mut session = PomodoroSession::new(1500, 300, 4);
+        session.task = Some("write report".to_string());
+        assert_eq!(bar_prefix(&session, false), "Focus 1/4");
+        assert_eq!(bar_prefix(&session, true), "⏸ PAUSED Focus 1/4");
+        assert_eq!(bar_message(&session), "write report");
+
+        session.paused = 360;
+        session.max_pause = Some(600);
+        assert_eq!(bar_message(&session), "write report (paused 6m / 10m)");
+    }
+
+    #[test]
+    fn progress_line_has_time_left_and_label() {
+        let mut session = PomodoroSession::new(1500, 300, 4);
+        session.elapsed = 780;
+        let line = progress_line(&session);
+        assert!(line.starts_with("Focus 1/4: 12:00 left, ends "), "{}", line);
+
+        session.task = Some("write report".to_string());
+        assert!(progress_line(&session).ends_with(" write report"));
+    }
+
+    #[test]
+    fn phase_runs_on_the_manual_clock() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        let mut session = session();
+
+        let real = Instant::now();
+        let outcome = run_timer(&mut session, &app_state);
+        assert_eq!(outcome, Outcome::Completed);
+        assert_eq!(session.elapsed, 4);
+        assert_eq!(clock.elapsed(), Duration::from_secs(4));
+        assert!(real.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn pause_is_logged_with_its_reason() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        let mut session = session();
+        session.max_pause = Some(60);
+
+        app_state.pause(Some("phone call".to_string()));
+        let outcome = run_timer(&mut session, &app_state);
+        assert_eq!(outcome, Outcome::Abandoned);
+        assert_eq!(session.pauses, 1);
+        assert_eq!(session.interruptions.len(), 1);
+        let interruption = &session.interruptions[0];
+        assert_eq!(interruption.reason.as_deref(), Some("phone call"));
+        assert_eq!(interruption.seconds, 60);
+    }
+
+    #[test]
+    fn nothing_is_paused_while_waiting() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        app_state.waiting.store(true, Ordering::SeqCst);
+
+        assert!(!app_state.pause(Some("phone call".to_string())));
+        assert!(!app_state.paused.load(Ordering::SeqCst));
+        assert!(app_state.pause_reason.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn until_phase_keeps_to_the_wall_clock() {
+        let mut session = session();
+        session.focus = 600;
+        session.until = Some(Local::now() + chrono::Duration::seconds(60));
+        let now = Instant::now();
+        let mut timer = PhaseTimer::start(Duration::from_secs(600), Duration::ZERO, now);
+        let pb = ProgressBar::hidden();
+
+        let length = follow_deadline(&mut session, &mut timer, &pb, now).unwrap();
+        assert!((58..=60).contains(&length));
+        assert_eq!(session.phase_len(), length);
+        assert_eq!(timer.remaining(now).as_secs(), length);
+        // In step with the clock now
+        assert_eq!(follow_deadline(&mut session, &mut timer, &pb, now), None);
+    }
+
+    #[test]
+    fn countdown_waits_and_snoozing_adds_to_it() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        let session = session();
+
+        app_state.snooze.store(30, Ordering::SeqCst);
+        count_down(&session, &app_state, 60);
+        assert!(clock.elapsed() >= Duration::from_secs(90));
+        assert!(clock.elapsed() < Duration::from_secs(91));
+        assert_eq!(app_state.run_state(), "running");
+        // Nothing is waiting to start now
+        assert!(!app_state.snooze(60));
+    }
+
+    #[test]
+    fn skipping_while_paused_ends_the_pause() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        let mut session = session();
+        session.dry_run = true;
+
+        app_state.pause(None);
+        let outcome = thread::scope(|scope| {
+            scope.spawn(|| {
+                while clock.elapsed() < Duration::from_secs(1) {
+                    thread::yield_now();
+                }
+                app_state.skip.store(true, Ordering::SeqCst);
+            });
+            run_timer(&mut session, &app_state)
+        });
+        assert_eq!(outcome, Outcome::Skipped);
+        assert_eq!(app_state.run_state(), "running");
+
+        session.advance();
+        let before = clock.elapsed();
+        assert_eq!(run_timer(&mut session, &app_state), Outcome::Completed);
+        assert_eq!(session.pauses, 0);
+        assert_eq!(clock.elapsed() - before, Duration::from_secs(2));
+    }
+
+    #[test]
+    fn strict_pause_starts_focus_over() {
+        let clock = Arc::new(ManualClock::default());
+        let app_state = app_state(&clock);
+        let mut session = session();
+        session.strict = true;
+        session.dry_run = true;
+
+        app_state.pause(None);
+        let outcome = thread::scope(|scope| {
+            scope.spawn(|| {
+                while clock.elapsed() < Duration::from_secs(3) {
+                    thread::yield_now();
+                }
+                app_state.paused.store(false, Ordering::SeqCst);
+            });
+            run_timer(&mut session, &app_state)
+        });
+        assert_eq!(outcome, Outcome::Completed);
+        // The pause was dropped with the voided pomodoro
+        assert_eq!(session.pauses, 0);
+        assert!(session.interruptions.is_empty());
+        assert!(clock.elapsed() >= Duration::from_secs(3 + 4));
+    }
+}
@@ -0,0 +1,131 @@
+// Custom schedules: a different focus/break pair for each cycle instead of
+// uniform lengths, e.g.
+//   --schedule "50m/10m,50m/10m,25m/30m"
+//   schedule = "3x50m/10m,25m/30m"        (config file; `3x` repeats an entry)
+//
+// Each entry is one cycle, so the session runs as many cycles as the schedule
+// has entries. Durations use the usual syntax (duration.rs).
+
+use crate::duration::{format_duration, parse_duration};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Focus and break lengths (in seconds) of one cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub focus: u64,
+    pub break_time: u64,
+}
+
+/// The cycles of a session, in order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Schedule {
+    pub steps: Vec<Step>,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for (n, entry) in s.split(',').map(str::trim).enumerate() {
+            let entry_error = |e: String| format!("schedule entry {} ('{}'): {}", n + 1, entry, e);
+
+            // Optional repeat count, e.g. 3x50m/10m
+            let (count, step) = match entry.split_once('x') {
+                Some((count, step)) => {
+                    let count: usize = count.parse().ok().filter(|&c| c > 0).ok_or_else(|| {
+                        entry_error("repeat count must be a number above 0".to_string())
+                    })?;
+                    (count, step)
+                }
+                None => (1, entry),
+            };
+            let (focus, break_time) = step
+                .split_once('/')
+                .ok_or_else(|| entry_error("expected focus/break, e.g. 50m/10m".to_string()))?;
+            let step = Step {
+                focus: parse_duration(focus).map_err(entry_error)?,
+                break_time: parse_duration(break_time).map_err(entry_error)?,
+            };
+            steps.extend(std::iter::repeat_n(step, count));
+        }
+        Ok(Schedule { steps })
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            format_duration(self.focus),
+            format_duration(self.break_time)
+        )
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self.steps.iter().map(Step::to_string).collect();
+        write!(f, "{}", entries.join(","))
+    }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> String {
+        schedule.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(focus: u64, break_time: u64) -> Step {
+        Step { focus, break_time }
+    }
+
+    #[test]
+    fn parses_entries_in_order() {
+        let schedule: Schedule = "50m/10m, 50m/10m,25m/30m".parse().unwrap();
+        assert_eq!(
+            schedule.steps,
+            vec![step(3000, 600), step(3000, 600), step(1500, 1800)]
+        );
+    }
+
+    #[test]
+    fn repeat_count_expands_an_entry() {
+        let schedule: Schedule = "3x25m/5m,25m/15m".parse().unwrap();
+        assert_eq!(schedule.steps.len(), 4);
+        assert_eq!(schedule.steps[2], step(1500, 300));
+        assert_eq!(schedule.steps[3], step(1500, 900));
+    }
+
+    #[test]
+    fn bad_entries_are_reported_by_position() {
+        let err = "50m/10m,50m".parse::<Schedule>().unwrap_err();
+        assert!(err.starts_with("schedule entry 2 ('50m')"), "{}", err);
+
+        assert!("50m/10q".parse::<Schedule>().is_err());
+        assert!("0x50m/10m".parse::<Schedule>().is_err());
+        assert!("".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn round_trips_through_a_string() {
+        let schedule: Schedule = "2x1h/10m,25m/0".parse().unwrap();
+        assert_eq!(schedule.to_string(), "1h/10m,1h/10m,25m/0s");
+        assert_eq!(schedule.to_string().parse::<Schedule>().unwrap(), schedule);
+    }
+}
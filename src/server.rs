@@ -0,0 +1,273 @@
+// A small HTTP server for controlling the timer from browser extensions,
+// Stream Deck plugins or other machines:
+//
+//   --serve 127.0.0.1:7878     (or `serve` in the config file)
+//
+//   GET  /status       the session as JSON (as `pomo status --json`)
+//   GET  /statusline   the compact line of `pomo statusline`, as text
+//   POST /pause, /resume, /skip, /stop
+//   POST /pause?reason=phone%20call pauses and notes why
+//   POST /extend?by=5m (or by=-5m) lengthens or shortens the running phase
+//
+// Commands reply with {"ok":true,"message":"paused"} (or "ok":false and a
+// 409 status). With `serve_token = "..."` in the config file every request
+// needs an `Authorization: Bearer <token>` header (401 otherwise). Without a
+// token anyone who can reach the address can control the timer, so only a
+// loopback address (127.0.0.1, ::1) may be served without one.
+// Requests are handled one at a time on a background thread; each carries a
+// single command, so that is plenty.
+
+use crate::{daemon, runner::AppState};
+use serde_json::json;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// How long a client gets to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request body read (and ignored)
+const MAX_BODY: u64 = 4096;
+
+/// A reply: status code, content type and body
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    fn text(status: u16, body: String) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body,
+        }
+    }
+}
+
+// Bind the address and serve requests on a background thread; requests must
+// carry the token, if there is one
+pub fn serve(addr: SocketAddr, token: Option<String>, app_state: Arc<AppState>) -> io::Result<()> {
+    if token.is_none() && !addr.ip().is_loopback() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "--serve {}: set `serve_token` in the config file to serve beyond this machine",
+                addr
+            ),
+        ));
+    }
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| io::Error::new(e.kind(), format!("--serve {}: {}", addr, e)))?;
+    crate::say!("Serving status and controls on http://{}", addr);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if let Err(e) = handle_client(stream, token.as_deref(), &app_state) {
+                log::warn!("HTTP request failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn handle_client(stream: TcpStream, token: Option<&str>, app_state: &AppState) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, noting the body length so it can be read off, and
+    // the credentials
+    let mut length = 0;
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.trim().parse().unwrap_or(0);
+        } else if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.trim().to_string());
+        }
+    }
+    io::copy(
+        &mut reader.by_ref().take(length.min(MAX_BODY)),
+        &mut io::sink(),
+    )?;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    log::trace!("HTTP {} {}", method, path);
+    let response = if authorized(authorization.as_deref(), token) {
+        route(method, path, |command| {
+            daemon::handle_command(command, app_state)
+        })
+    } else {
+        Response::text(401, "unauthorized\n".to_string())
+    };
+
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+// Answer a request, running control commands through `command`
+fn route(method: &str, path: &str, command: impl Fn(&str) -> String) -> Response {
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    match (method, path) {
+        ("GET", "/status") => Response::json(200, command("status-json")),
+        ("GET", "/statusline") => Response::text(200, command("statusline")),
+        ("POST", "/pause") => match query_value(query, "reason").filter(|r| !r.trim().is_empty()) {
+            Some(reason) => command_reply(command(&format!("pause {}", reason.trim()))),
+            None => command_reply(command("pause")),
+        },
+        ("POST", "/resume" | "/skip" | "/stop") => command_reply(command(&path[1..])),
+        ("POST", "/extend") => {
+            let by = query_value(query, "by").unwrap_or_default();
+            command_reply(command(&format!("extend {}", by)))
+        }
+        (_, "/status" | "/statusline" | "/pause" | "/resume" | "/skip" | "/stop" | "/extend") => {
+            Response::text(405, "method not allowed\n".to_string())
+        }
+        _ => Response::text(404, "not found\n".to_string()),
+    }
+}
+
+// Whether the Authorization header carries the token (anything goes when there
+// is none)
+fn authorized(authorization: Option<&str>, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    authorization
+        .and_then(|value| value.split_once(' '))
+        .is_some_and(|(scheme, given)| {
+            scheme.eq_ignore_ascii_case("bearer") && given.trim() == token
+        })
+}
+
+// A query string parameter, with %XX escapes and `+` decoded
+fn query_value(query: &str, key: &str) -> Option<String> {
+    let raw = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))?;
+    let mut bytes = Vec::new();
+    let mut rest = raw.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => match rest
+                .get(..2)
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+            {
+                Some(decoded) => {
+                    bytes.push(decoded);
+                    rest = &rest[2..];
+                }
+                None => bytes.push(b'%'),
+            },
+            _ => bytes.push(byte),
+        }
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// A control command's "ok: ..." / "error: ..." reply as JSON
+fn command_reply(reply: String) -> Response {
+    let (ok, message) = match reply.split_once(": ") {
+        Some(("ok", message)) => (true, message),
+        Some((_, message)) => (false, message),
+        None => (false, reply.as_str()),
+    };
+    let body = json!({ "ok": ok, "message": message }).to_string();
+    Response::json(if ok { 200 } else { 409 }, body)
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake(command: &str) -> String {
+        match command {
+            "status-json" => "{\"running\":true}".to_string(),
+            "pause" => "ok: paused".to_string(),
+            "pause phone call" => "ok: paused (phone call)".to_string(),
+            "extend -5m" => "ok: shortening the current phase by 5m".to_string(),
+            other => format!("error: unknown command '{}'", other),
+        }
+    }
+
+    #[test]
+    fn routes_status_and_commands() {
+        let status = route("GET", "/status?x=1", fake);
+        assert_eq!(status.status, 200);
+        assert_eq!(status.body, "{\"running\":true}");
+
+        let pause = route("POST", "/pause", fake);
+        assert_eq!(pause.status, 200);
+        assert_eq!(pause.body, r#"{"message":"paused","ok":true}"#);
+
+        let pause = route("POST", "/pause?reason=phone%20call", fake);
+        assert_eq!(pause.body, r#"{"message":"paused (phone call)","ok":true}"#);
+        assert_eq!(
+            query_value("x=1&reason=call+from%2Fboss", "reason").as_deref(),
+            Some("call from/boss")
+        );
+
+        let extend = route("POST", "/extend?by=-5m", fake);
+        assert_eq!(extend.status, 200);
+        assert!(extend.body.contains("shortening"));
+
+        assert_eq!(route("POST", "/skip", fake).status, 409);
+        assert_eq!(route("GET", "/pause", fake).status, 405);
+        assert_eq!(route("GET", "/", fake).status, 404);
+    }
+
+    #[test]
+    fn token_is_needed_when_set() {
+        assert!(authorized(None, None));
+        assert!(authorized(Some("Bearer s3cret"), Some("s3cret")));
+        assert!(authorized(Some("bearer s3cret"), Some("s3cret")));
+        assert!(!authorized(None, Some("s3cret")));
+        assert!(!authorized(Some("Bearer wrong"), Some("s3cret")));
+        assert!(!authorized(Some("Basic s3cret"), Some("s3cret")));
+    }
+}
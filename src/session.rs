@@ -0,0 +1,594 @@
+// The Pomodoro state machine. A session alternates focus and break phases
+// for a number of cycles (or, with `--cycles 0` or `--repeat-until`, until it
+// is stopped); `advance` moves it from one phase to the next.
+// Timing, blockers and notifications live elsewhere (timer.rs, runner.rs).
+
+use crate::{
+    blockers::{BlockerKind, BlockerOptions},
+    enforce::BreakEnforcement,
+    git::GitContext,
+    hooks::Hooks,
+    integrations::{MediaMode, ShortcutSettings},
+    notify::NotifySettings,
+    power::IdleSettings,
+    schedule::{Schedule, Step},
+    sound::Sound,
+    state::SavedAt,
+    theme::{ProgressSettings, Theme},
+};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::SocketAddr};
+
+/// Which half of a cycle the session is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Focus,
+    Break,
+}
+
+/// A Pomodoro session: its settings plus where it is (cycle, phase, elapsed time).
+/// This is also what gets saved to the state file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PomodoroSession {
+    pub focus: u64,
+    pub break_time: u64,
+    /// Cycles to run; 0 keeps going until the session is stopped
+    pub cycles: u32,
+    pub cycle: u32,
+    pub phase: Phase,
+    pub elapsed: u64,
+    /// Per-cycle focus/break lengths; `focus` and `break_time` apply without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+    /// Length of every `long_break_every`th break (`--long-break`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub long_break: Option<u64>,
+    #[serde(default = "default_long_break_every")]
+    pub long_break_every: u32,
+    /// No phase starts that would end after this time (`--repeat-until`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_until: Option<DateTime<Local>>,
+    /// Distraction blockers engaged during focus
+    #[serde(default = "default_block")]
+    pub block: Vec<BlockerKind>,
+    #[serde(default, flatten)]
+    pub blocker_options: BlockerOptions,
+    #[serde(default)]
+    pub notify: NotifySettings,
+    /// Sound played at phase transitions
+    #[serde(default)]
+    pub sound: Sound,
+    /// Progress bar layout, colours and characters (theme.rs)
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub progress: ProgressSettings,
+    /// What the session is being spent on
+    #[serde(default)]
+    pub task: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Take tasks from the planning queue (`--plan`)
+    #[serde(default)]
+    pub plan: bool,
+    /// Where the current task stands in the queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub planned: Option<PlanPosition>,
+    /// Repository and branch the session was started in (`--git`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitContext>,
+    /// Start the next phase without waiting for the user
+    #[serde(default)]
+    pub auto_advance: bool,
+    /// Pomodoros to complete per day, for progress and streaks
+    #[serde(default)]
+    pub daily_goal: Option<u32>,
+    /// Keep counting past the end of focus until the user is back
+    #[serde(default)]
+    pub overtime: bool,
+    /// Interrupted pomodoros don't count (`--strict`)
+    #[serde(default)]
+    pub strict: bool,
+    /// Focus ends at this time of day, and the session with it (`--until`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Local>>,
+    /// Seconds to count down before the first phase starts (`--in`)
+    #[serde(skip)]
+    pub start_in: Option<u64>,
+    /// Seconds counted past the end of the current focus phase
+    #[serde(default)]
+    pub overtime_elapsed: u64,
+    /// Seconds added to the current phase with `pomo extend` (negative when
+    /// it was shortened)
+    #[serde(default)]
+    pub adjusted: i64,
+    /// Seconds the current phase has been paused
+    #[serde(default)]
+    pub paused: u64,
+    /// Times the current phase has been paused (system sleep included)
+    #[serde(default)]
+    pub pauses: u32,
+    /// The pauses of the current phase, with their reasons
+    #[serde(default)]
+    pub interruptions: Vec<Interruption>,
+    /// Longest total pause allowed per phase before it is abandoned
+    #[serde(default)]
+    pub max_pause: Option<u64>,
+    /// Pause while the screen is locked
+    #[serde(default)]
+    pub pause_on_lock: bool,
+    /// Address of the HTTP status server
+    #[serde(default)]
+    pub serve: Option<SocketAddr>,
+    /// URL to POST phase transitions to
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Media player control at phase transitions
+    #[serde(default)]
+    pub media: MediaMode,
+    /// Lock the screen or cover the terminal during breaks
+    #[serde(default)]
+    pub enforce_break: Option<BreakEnforcement>,
+    /// What to do when the user is idle during focus
+    #[serde(default)]
+    pub idle: IdleSettings,
+    /// Seconds the user was idle during the current phase
+    #[serde(default)]
+    pub idle_elapsed: u64,
+    /// Commands to run at phase transitions
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// macOS Shortcuts to run when focus starts and ends
+    #[serde(default)]
+    pub shortcuts: ShortcutSettings,
+    /// When the current phase began (for the session log)
+    #[serde(default)]
+    pub phase_started_at: Option<DateTime<Local>>,
+    /// When the state file was last written, to tell how long the session
+    /// was away when it is resumed (state.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved: Option<SavedAt>,
+    /// Print side effects instead of performing them (`--dry-run`)
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Timer seconds per real second (dry runs only)
+    #[serde(default = "real_time")]
+    pub time_scale: u32,
+}
+
+/// One pause of a phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interruption {
+    pub started_at: DateTime<Local>,
+    /// How long it lasted (so far, while it lasts) in seconds
+    pub seconds: u64,
+    /// Why: given with `pomo pause --reason` or at the prompt after `p`, or
+    /// "idle", "screen locked", "system asleep" or "not running" (before
+    /// `pomo resume`) for automatic pauses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// The current task's place in the planning queue (plan.rs)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanPosition {
+    /// Place of the task in the queue (1-based)
+    pub number: u32,
+    /// Tasks in the queue
+    pub total: u32,
+    /// Which of the task's pomodoros this is (1-based)
+    pub pomodoro: u32,
+    /// Pomodoros the task was estimated at
+    pub estimate: u32,
+}
+
+impl fmt::Display for PlanPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "🍅{}/{} · task {}/{}",
+            self.pomodoro, self.estimate, self.number, self.total
+        )
+    }
+}
+
+fn default_block() -> Vec<BlockerKind> {
+    vec![BlockerKind::Wifi]
+}
+
+fn real_time() -> u32 {
+    1
+}
+
+fn default_long_break_every() -> u32 {
+    4
+}
+
+/// Where a session is in its cycles, e.g. "2/4", or "2" when it runs until
+/// stopped
+pub fn cycle_of(cycle: u32, cycles: u32) -> String {
+    match cycles {
+        0 => cycle.to_string(),
+        cycles => format!("{}/{}", cycle.min(cycles), cycles),
+    }
+}
+
+impl PomodoroSession {
+    /// State at the very beginning of a fresh session
+    pub fn new(focus: u64, break_time: u64, cycles: u32) -> Self {
+        PomodoroSession {
+            focus,
+            break_time,
+            cycles,
+            cycle: 1,
+            phase: Phase::Focus,
+            elapsed: 0,
+            schedule: None,
+            long_break: None,
+            long_break_every: default_long_break_every(),
+            repeat_until: None,
+            block: default_block(),
+            blocker_options: BlockerOptions::default(),
+            notify: NotifySettings::default(),
+            sound: Sound::None,
+            theme: Theme::default(),
+            progress: ProgressSettings::default(),
+            task: None,
+            tags: Vec::new(),
+            plan: false,
+            planned: None,
+            git: None,
+            auto_advance: false,
+            daily_goal: None,
+            overtime: false,
+            strict: false,
+            until: None,
+            start_in: None,
+            overtime_elapsed: 0,
+            adjusted: 0,
+            paused: 0,
+            pauses: 0,
+            interruptions: Vec::new(),
+            max_pause: None,
+            pause_on_lock: false,
+            serve: None,
+            webhook_url: None,
+            media: MediaMode::Off,
+            enforce_break: None,
+            idle: IdleSettings::default(),
+            idle_elapsed: 0,
+            hooks: Hooks::default(),
+            shortcuts: ShortcutSettings::default(),
+            phase_started_at: None,
+            saved: None,
+            dry_run: false,
+            time_scale: real_time(),
+        }
+    }
+
+    /// A session running through a schedule, one cycle per entry
+    pub fn with_schedule(schedule: Schedule) -> Self {
+        let first = schedule.steps.first().copied().unwrap_or(Step {
+            focus: 0,
+            break_time: 0,
+        });
+        let mut session =
+            PomodoroSession::new(first.focus, first.break_time, schedule.steps.len() as u32);
+        session.schedule = Some(schedule);
+        session
+    }
+
+    /// Which schedule entry (1-based) the current cycle comes from, if any
+    pub fn schedule_entry(&self) -> Option<u32> {
+        self.schedule_step().map(|_| self.cycle)
+    }
+
+    fn schedule_step(&self) -> Option<Step> {
+        let index = usize::try_from(self.cycle).ok()?.checked_sub(1)?;
+        self.schedule.as_ref()?.steps.get(index).copied()
+    }
+
+    /// Length of the current phase in seconds, including `pomo extend`
+    /// adjustments
+    pub fn phase_len(&self) -> u64 {
+        self.planned_len().saturating_add_signed(self.adjusted)
+    }
+
+    /// Length of the current phase as planned, before any adjustment
+    pub fn planned_len(&self) -> u64 {
+        let (focus, break_time) = match self.schedule_step() {
+            Some(step) => (step.focus, step.break_time),
+            None => (self.focus, self.break_time),
+        };
+        match self.phase {
+            Phase::Focus => focus,
+            Phase::Break => match self.long_break {
+                Some(long_break) if self.long_break_due() => long_break,
+                _ => break_time,
+            },
+        }
+    }
+
+    // Whether this cycle's break is a long one (schedules set their own)
+    fn long_break_due(&self) -> bool {
+        self.schedule.is_none()
+            && self.long_break_every > 0
+            && self.cycle.is_multiple_of(self.long_break_every)
+    }
+
+    /// Seconds left in the current phase
+    pub fn remaining(&self) -> u64 {
+        self.phase_len().saturating_sub(self.elapsed)
+    }
+
+    /// Seconds actually spent in the current phase: overtime counts, pauses
+    /// don't
+    pub fn actual_len(&self) -> u64 {
+        self.elapsed + self.overtime_elapsed
+    }
+
+    /// Lengthen (or, with negative seconds, shorten) the current phase. It
+    /// can't be cut to less than has already run.
+    pub fn extend(&mut self, seconds: i64) {
+        let floor = self.elapsed.min(self.planned_len()) as i64 - self.planned_len() as i64;
+        self.adjusted = self.adjusted.saturating_add(seconds).max(floor);
+    }
+
+    /// Whether the session is over: every cycle has been completed or, with
+    /// `--repeat-until`, the phase about to start wouldn't be over by the stop
+    /// time. The first focus always runs, and a phase already under way is
+    /// finished.
+    pub fn is_finished(&self) -> bool {
+        if self.cycles > 0 && self.cycle > self.cycles {
+            return true;
+        }
+        let first = self.cycle == 1 && self.phase == Phase::Focus;
+        self.repeat_until.is_some_and(|stop| {
+            !first
+                && self.elapsed == 0
+                && Local::now() + TimeDelta::seconds(self.phase_len() as i64) > stop
+        })
+    }
+
+    /// Move on to the next phase: focus -> break, break -> focus of the next
+    /// cycle. Returns the new phase, or `None` once the last break is over
+    /// (or focus is, for a session run `--until` a time: it has no break).
+    pub fn advance(&mut self) -> Option<Phase> {
+        match self.phase {
+            Phase::Focus if self.until.is_some() => self.cycle += 1,
+            Phase::Focus => self.phase = Phase::Break,
+            Phase::Break => {
+                self.cycle += 1;
+                self.phase = Phase::Focus;
+            }
+        }
+        self.restart_phase();
+        (!self.is_finished()).then_some(self.phase)
+    }
+
+    /// Start the current phase over from the beginning
+    pub fn restart_phase(&mut self) {
+        self.elapsed = 0;
+        self.paused = 0;
+        self.pauses = 0;
+        self.interruptions.clear();
+        self.overtime_elapsed = 0;
+        self.adjusted = 0;
+        self.idle_elapsed = 0;
+        self.phase_started_at = None;
+    }
+
+    /// Count `seconds` up to `now` during which pomo wasn't running (the time
+    /// before `pomo resume`) as a pause of the current phase
+    pub fn add_time_away(&mut self, seconds: u64, now: DateTime<Local>) {
+        self.paused += seconds;
+        self.pauses += 1;
+        self.interruptions.push(Interruption {
+            started_at: now - TimeDelta::seconds(seconds as i64),
+            seconds,
+            reason: Some("not running".to_string()),
+        });
+    }
+
+    /// Whether the current phase has used up its pause budget
+    pub fn pause_budget_spent(&self) -> bool {
+        self.max_pause.is_some_and(|max| self.paused >= max)
+    }
+
+    /// Task and tags for display, e.g. "write report #work"
+    pub fn label(&self) -> Option<String> {
+        let mut parts: Vec<String> = self.task.iter().cloned().collect();
+        parts.extend(self.tags.iter().map(|tag| format!("#{}", tag)));
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_session_starts_with_focus_of_first_cycle() {
+        let session = PomodoroSession::new(1500, 300, 4);
+        assert_eq!(session.cycle, 1);
+        assert_eq!(session.phase, Phase::Focus);
+        assert_eq!(session.elapsed, 0);
+        assert_eq!(session.phase_len(), 1500);
+        assert!(!session.is_finished());
+    }
+
+    #[test]
+    fn focus_is_followed_by_break_of_same_cycle() {
+        let mut session = PomodoroSession::new(1500, 300, 2);
+        assert_eq!(session.advance(), Some(Phase::Break));
+        assert_eq!(session.cycle, 1);
+        assert_eq!(session.phase_len(), 300);
+    }
+
+    #[test]
+    fn break_is_followed_by_focus_of_next_cycle() {
+        let mut session = PomodoroSession::new(1500, 300, 2);
+        session.advance();
+        assert_eq!(session.advance(), Some(Phase::Focus));
+        assert_eq!(session.cycle, 2);
+        assert_eq!(session.phase_len(), 1500);
+    }
+
+    #[test]
+    fn session_until_a_time_ends_with_focus() {
+        let mut session = PomodoroSession::new(1800, 0, 1);
+        session.until = Some(Local::now());
+        assert_eq!(session.advance(), None);
+        assert!(session.is_finished());
+    }
+
+    #[test]
+    fn zero_cycles_run_until_stopped() {
+        let mut session = PomodoroSession::new(10, 5, 0);
+        for _ in 0..100 {
+            assert!(session.advance().is_some());
+        }
+        assert_eq!(session.cycle, 51);
+        assert!(!session.is_finished());
+        assert_eq!(cycle_of(session.cycle, session.cycles), "51");
+        assert_eq!(cycle_of(5, 4), "4/4");
+    }
+
+    #[test]
+    fn repeat_until_starts_no_phase_past_the_stop_time() {
+        let mut session = PomodoroSession::new(1500, 300, 0);
+        session.repeat_until = Some(Local::now() + TimeDelta::minutes(10));
+        assert!(!session.is_finished());
+        // The break still fits, the next pomodoro doesn't
+        assert_eq!(session.advance(), Some(Phase::Break));
+        assert_eq!(session.advance(), None);
+        assert!(session.is_finished());
+    }
+
+    #[test]
+    fn every_fourth_break_is_long() {
+        let mut session = PomodoroSession::new(1500, 300, 0);
+        session.long_break = Some(900);
+        let mut breaks = Vec::new();
+        while session.cycle <= 8 {
+            if session.advance() == Some(Phase::Break) {
+                breaks.push(session.phase_len());
+            }
+        }
+        assert_eq!(breaks, vec![300, 300, 300, 900, 300, 300, 300, 900]);
+    }
+
+    #[test]
+    fn cycles_run_in_order_and_then_finish() {
+        let mut session = PomodoroSession::new(10, 5, 3);
+        let mut sequence = vec![(session.cycle, session.phase)];
+        while session.advance().is_some() {
+            sequence.push((session.cycle, session.phase));
+        }
+
+        assert_eq!(
+            sequence,
+            vec![
+                (1, Phase::Focus),
+                (1, Phase::Break),
+                (2, Phase::Focus),
+                (2, Phase::Break),
+                (3, Phase::Focus),
+                (3, Phase::Break),
+            ]
+        );
+        assert!(session.is_finished());
+    }
+
+    #[test]
+    fn advance_resets_phase_progress() {
+        let mut session = PomodoroSession::new(1500, 300, 1);
+        session.elapsed = 1200;
+        session.paused = 60;
+        session.pauses = 2;
+        session.interruptions.push(Interruption {
+            started_at: Local::now(),
+            seconds: 60,
+            reason: Some("phone call".to_string()),
+        });
+        session.overtime_elapsed = 90;
+        session.idle_elapsed = 120;
+        session.phase_started_at = Some(Local::now());
+
+        session.advance();
+        assert_eq!(session.elapsed, 0);
+        assert_eq!(session.paused, 0);
+        assert_eq!(session.pauses, 0);
+        assert!(session.interruptions.is_empty());
+        assert_eq!(session.overtime_elapsed, 0);
+        assert_eq!(session.idle_elapsed, 0);
+        assert_eq!(session.remaining(), 300);
+        assert!(session.phase_started_at.is_none());
+    }
+
+    #[test]
+    fn pause_budget_is_spent_at_max_pause() {
+        let mut session = PomodoroSession::new(1500, 300, 1);
+        session.paused = 600;
+        assert!(!session.pause_budget_spent());
+
+        session.max_pause = Some(300);
+        session.paused = 299;
+        assert!(!session.pause_budget_spent());
+        session.paused = 300;
+        assert!(session.pause_budget_spent());
+    }
+
+    #[test]
+    fn schedule_sets_lengths_per_cycle() {
+        let mut session = PomodoroSession::with_schedule("50m/10m,25m/30m".parse().unwrap());
+        assert_eq!(session.cycles, 2);
+
+        let mut lengths = vec![(session.schedule_entry(), session.phase_len())];
+        while session.advance().is_some() {
+            lengths.push((session.schedule_entry(), session.phase_len()));
+        }
+        assert_eq!(
+            lengths,
+            vec![
+                (Some(1), 3000),
+                (Some(1), 600),
+                (Some(2), 1500),
+                (Some(2), 1800),
+            ]
+        );
+        assert_eq!(session.schedule_entry(), None);
+    }
+
+    #[test]
+    fn extend_adjusts_only_the_current_phase() {
+        let mut session = PomodoroSession::new(1500, 300, 1);
+        session.extend(300);
+        assert_eq!(session.phase_len(), 1800);
+        assert_eq!(session.planned_len(), 1500);
+
+        // Can't be cut below what has already run
+        session.elapsed = 1000;
+        session.extend(-3600);
+        assert_eq!(session.phase_len(), 1000);
+        assert_eq!(session.adjusted, -500);
+
+        session.advance();
+        assert_eq!(session.adjusted, 0);
+        assert_eq!(session.phase_len(), 300);
+    }
+
+    #[test]
+    fn label_combines_task_and_tags() {
+        let mut session = PomodoroSession::new(1500, 300, 1);
+        assert_eq!(session.label(), None);
+
+        session.tags = vec!["work".to_string(), "deep".to_string()];
+        assert_eq!(session.label().as_deref(), Some("#work #deep"));
+
+        session.task = Some("write report".to_string());
+        assert_eq!(session.label().as_deref(), Some("write report #work #deep"));
+    }
+}
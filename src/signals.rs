@@ -0,0 +1,69 @@
+// Control by Unix signal, for window-manager keybindings and the like:
+//   SIGUSR1 … toggle pause (`pkill -USR1 pomo`)
+//   SIGUSR2 … skip the current phase (`pkill -USR2 pomo`)
+// While the next phase is waiting for the user, either one starts it.
+//
+// The handler only sets a flag; a thread applies it to the AppState, since
+// nothing else is safe to do inside a signal handler.
+
+use crate::{runner::AppState, say};
+use nix::sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction};
+use std::{
+    ffi::c_int,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often pending signals are checked
+const POLL: Duration = Duration::from_millis(100);
+
+static PAUSE: AtomicBool = AtomicBool::new(false);
+static SKIP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(signal: c_int) {
+    if signal == Signal::SIGUSR1 as c_int {
+        PAUSE.store(true, Ordering::SeqCst);
+    } else if signal == Signal::SIGUSR2 as c_int {
+        SKIP.store(true, Ordering::SeqCst);
+    }
+}
+
+// Install the SIGUSR1/SIGUSR2 handlers and start acting on them
+pub fn handle_control_signals(app_state: Arc<AppState>) -> nix::Result<()> {
+    // SA_RESTART, so the stdin thread's read isn't cut short by a signal
+    let action = SigAction::new(
+        SigHandler::Handler(on_signal),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    // Safety: the handler only stores to atomics
+    unsafe {
+        sigaction(Signal::SIGUSR1, &action)?;
+        sigaction(Signal::SIGUSR2, &action)?;
+    }
+
+    thread::spawn(move || {
+        loop {
+            let pause = PAUSE.swap(false, Ordering::SeqCst);
+            let skip = SKIP.swap(false, Ordering::SeqCst);
+            if (pause || skip) && app_state.waiting.swap(false, Ordering::SeqCst) {
+                say!("Signal received; starting the next phase.");
+            } else if pause {
+                let paused = !app_state.paused.fetch_xor(true, Ordering::SeqCst);
+                say!(
+                    "SIGUSR1: pause toggled to {}",
+                    if paused { "PAUSED" } else { "RUNNING" }
+                );
+            } else if skip {
+                say!("SIGUSR2: skipping the current phase.");
+                app_state.skip.store(true, Ordering::SeqCst);
+            }
+            thread::sleep(POLL);
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,242 @@
+// Audible alerts at phase transitions, for when a notification banner is easy
+// to miss (headphones on, screen off).
+//
+//   --sound none            … silent (default)
+//   --sound bell | chime    … built-in sounds
+//   --sound /path/to/a.wav  … any file the system player understands
+//
+// The built-in sounds are synthesized into small WAV files in the state
+// directory the first time they are needed. Playback uses the platform's
+// command-line player (`afplay` on macOS, `paplay` or `aplay` on Linux,
+// PowerShell on Windows), which has to be installed, and runs in the
+// background so it never holds up the timer. A missing player is reported
+// before the session starts (`Sound::check`).
+
+use crate::{dry_run, state};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    thread,
+};
+
+const SAMPLE_RATE: u32 = 44_100;
+
+/// Which sound to play
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Sound {
+    #[default]
+    None,
+    Bell,
+    Chime,
+    File(PathBuf),
+}
+
+impl FromStr for Sound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Sound::None),
+            "bell" => Ok(Sound::Bell),
+            "chime" => Ok(Sound::Chime),
+            "" => Err("expected none, bell, chime or a path to a sound file".to_string()),
+            path => {
+                let path = PathBuf::from(path);
+                if path.is_file() {
+                    Ok(Sound::File(path))
+                } else {
+                    Err(format!(
+                        "'{}' is not none, bell, chime or an existing sound file",
+                        path.display()
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Sound {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Sound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sound::None => write!(f, "none"),
+            Sound::Bell => write!(f, "bell"),
+            Sound::Chime => write!(f, "chime"),
+            Sound::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl From<Sound> for String {
+    fn from(sound: Sound) -> String {
+        sound.to_string()
+    }
+}
+
+impl Sound {
+    // Start playing the sound in the background
+    pub fn play(&self) -> io::Result<()> {
+        let path = match self {
+            Sound::None => return Ok(()),
+            _ if dry_run::is_enabled() => {
+                dry_run::action(format!("play sound {}", self));
+                return Ok(());
+            }
+            Sound::Bell => builtin_file("bell", &[(880.0, 0.0)], 1.2)?,
+            Sound::Chime => builtin_file("chime", &[(659.3, 0.0), (987.8, 0.25)], 1.4)?,
+            Sound::File(path) => path.clone(),
+        };
+
+        let mut child = player_command(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // Reap the player when it finishes
+        thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    /// Whether the sound can be played here, checked before the session
+    /// starts
+    pub fn check(&self) -> io::Result<()> {
+        if *self == Sound::None {
+            return Ok(());
+        }
+        check_player()
+    }
+}
+
+#[cfg(unix)]
+fn check_player() -> io::Result<()> {
+    let command = player_command(Path::new(""));
+    let player = command.get_program().to_string_lossy();
+    if !command_exists(&player) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("`{}` not found", player),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn check_player() -> io::Result<()> {
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", "exit"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("powershell failed ({})", status)));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn player_command(path: &Path) -> Command {
+    let mut command = Command::new("afplay");
+    command.arg(path);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn player_command(path: &Path) -> Command {
+    // PulseAudio/PipeWire if available, plain ALSA otherwise
+    let player = if command_exists("paplay") {
+        "paplay"
+    } else {
+        "aplay"
+    };
+    let mut command = Command::new(player);
+    if player == "aplay" {
+        command.arg("-q");
+    }
+    command.arg(path);
+    command
+}
+
+// The WAV player built into .NET, so nothing needs installing
+#[cfg(windows)]
+fn player_command(path: &Path) -> Command {
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        &format!(
+            "(New-Object Media.SoundPlayer '{}').PlaySync()",
+            path.display().to_string().replace('\'', "''")
+        ),
+    ]);
+    command
+}
+
+#[cfg(unix)]
+pub(crate) fn command_exists(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+        .unwrap_or(false)
+}
+
+// Path of a built-in sound, writing it on first use.
+// `tones` are (frequency in Hz, start offset in seconds) pairs.
+fn builtin_file(name: &str, tones: &[(f32, f32)], seconds: f32) -> io::Result<PathBuf> {
+    let path = state::state_dir()
+        .join("sounds")
+        .join(format!("{}.wav", name));
+    if !path.is_file() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, synthesize(tones, seconds))?;
+    }
+    Ok(path)
+}
+
+// Render decaying sine tones as a 16-bit mono WAV file
+fn synthesize(tones: &[(f32, f32)], seconds: f32) -> Vec<u8> {
+    let samples = (SAMPLE_RATE as f32 * seconds) as u32;
+    let mut pcm = Vec::with_capacity(samples as usize * 2);
+
+    for i in 0..samples {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        let mut value = 0.0;
+        for &(freq, start) in tones {
+            if t >= start {
+                let local = t - start;
+                let envelope = (-4.0 * local).exp();
+                value += (2.0 * std::f32::consts::PI * freq * local).sin() * envelope;
+            }
+        }
+        let sample = (value / tones.len() as f32 * 0.6 * i16::MAX as f32) as i16;
+        pcm.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + pcm.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes()); // fmt chunk size
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // byte rate
+    wav.extend_from_slice(&2u16.to_le_bytes()); // block align
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+    wav.extend_from_slice(&pcm);
+    wav
+}
@@ -0,0 +1,158 @@
+// Spoken announcements (`--speak`), for when the screen is out of sight:
+// "Break starts now, 5 minutes", "Focus ends in 2 minutes". They go with the
+// notification events (notify.rs), using the platform's speech synthesizer:
+//
+//   macOS   … `say`
+//   Linux   … `spd-say` (speech-dispatcher), or `espeak-ng` / `espeak`
+//   Windows … System.Speech through PowerShell
+//
+// Announcements are spoken one after another on a background thread, so they
+// never hold up the timer or talk over each other.
+
+use crate::dry_run;
+use std::{
+    io,
+    process::{Command, Stdio},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Announcements queued or being spoken
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+// Queue an announcement
+pub fn speak(text: &str) {
+    if dry_run::is_enabled() {
+        dry_run::action(format!("say \"{}\"", text));
+        return;
+    }
+    static QUEUE: OnceLock<Mutex<Sender<String>>> = OnceLock::new();
+    let queue = QUEUE.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for text in receiver {
+                if let Err(e) = say(&text) {
+                    warn_once(e);
+                }
+                PENDING.fetch_sub(1, Ordering::SeqCst);
+            }
+        });
+        Mutex::new(sender)
+    });
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    if queue.lock().unwrap().send(text.to_string()).is_err() {
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Give announcements still queued (the end of the last phase) up to `timeout`
+// to be spoken before pomo exits
+pub fn finish(timeout: Duration) {
+    let start = Instant::now();
+    while PENDING.load(Ordering::SeqCst) > 0 && start.elapsed() < timeout {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// A missing synthesizer fails every time; saying so once is enough
+fn warn_once(error: io::Error) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if !WARNED.swap(true, Ordering::SeqCst) {
+        log::warn!("Failed to speak: {}", error);
+    }
+}
+
+// Speak the text and wait until it has been said
+fn say(text: &str) -> io::Result<()> {
+    let mut command = speech_command(text);
+    let program = command.get_program().to_string_lossy().into_owned();
+    log::debug!("speaking \"{}\" with {}", text, program);
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(e.kind(), format!("--speak needs `{}`", program))
+            }
+            _ => e,
+        })?;
+    if !status.success() {
+        return Err(io::Error::other(format!("`{}` {}", program, status)));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn speech_command(text: &str) -> Command {
+    let mut command = Command::new("say");
+    command.arg(text);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn speech_command(text: &str) -> Command {
+    use crate::sound::command_exists;
+    // speech-dispatcher if available (-w: return once it has been said)
+    if command_exists("spd-say") {
+        let mut command = Command::new("spd-say");
+        command.args(["-w", text]);
+        return command;
+    }
+    let mut command = Command::new(if command_exists("espeak-ng") {
+        "espeak-ng"
+    } else {
+        "espeak"
+    });
+    command.arg(text);
+    command
+}
+
+// The synthesizer built into .NET, so nothing needs installing
+#[cfg(windows)]
+fn speech_command(text: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        &format!(
+            "Add-Type -AssemblyName System.Speech; \
+             (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')",
+            text.replace('\'', "''")
+        ),
+    ]);
+    command
+}
+
+// A length as it is said: "25 minutes", "1 hour 30 minutes", "45 seconds"
+pub fn spoken_duration(seconds: u64) -> String {
+    let unit = |n: u64, name: &str| format!("{} {}{}", n, name, if n == 1 { "" } else { "s" });
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => unit(seconds, "second"),
+        (0, _) => unit(minutes, "minute"),
+        (_, 0) => unit(hours, "hour"),
+        _ => format!("{} {}", unit(hours, "hour"), unit(minutes, "minute")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_spelled_out() {
+        assert_eq!(spoken_duration(45), "45 seconds");
+        assert_eq!(spoken_duration(60), "1 minute");
+        assert_eq!(spoken_duration(25 * 60), "25 minutes");
+        assert_eq!(spoken_duration(3600), "1 hour");
+        assert_eq!(spoken_duration(5400), "1 hour 30 minutes");
+    }
+}
@@ -0,0 +1,207 @@
+// Persisted session state so an interrupted run can be picked up again with
+// `pomo resume`. The state is written as JSON to the user's state directory
+// (e.g. ~/.local/state/rust-pomo/session.json) every few seconds while a timer
+// is running, and removed once all cycles have finished.
+//
+// Sessions started with `--name emails` keep their own files next to it
+// (session.emails.json, and likewise the control socket and daemon log), so
+// several can run at once.
+//
+// Each save notes when it was made twice over: on the wall clock and on the
+// clock since boot (with the boot's id on Linux). On `pomo resume` the time
+// since is taken from the clock since boot if the machine hasn't restarted,
+// so NTP, a time zone change or a clock set by hand in between don't matter,
+// and from the wall clock otherwise. It counts as a pause of the phase that
+// was cut short, rather than as focus time or not at all.
+
+use crate::{clock, session::PomodoroSession};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf, sync::Mutex};
+
+/// Name shown for the session started without `--name`
+pub const DEFAULT_NAME: &str = "default";
+
+/// The session this process runs or talks to (`--name`); `None` for the
+/// unnamed one
+static NAME: Mutex<Option<String>> = Mutex::new(None);
+
+/// Select the session for the rest of the process
+pub fn set_name(name: Option<String>) {
+    *NAME.lock().unwrap() = name.filter(|name| name != DEFAULT_NAME);
+}
+
+pub fn name() -> Option<String> {
+    NAME.lock().unwrap().clone()
+}
+
+/// Check a `--name` argument (used as a Clap value parser)
+pub fn parse_name(input: &str) -> Result<String, String> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if input.is_empty() || !input.chars().all(valid) {
+        return Err(format!(
+            "'{}' is not a session name (use letters, digits, - and _)",
+            input
+        ));
+    }
+    Ok(input.to_string())
+}
+
+/// Shortest time away that counts as a pause on resume (the state is saved
+/// every few seconds anyway)
+const MIN_AWAY: u64 = 5;
+
+/// When the state was saved, on the wall clock and the clock since boot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedAt {
+    pub wall: DateTime<Local>,
+    /// Seconds since boot, counting time suspended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_boot: Option<u64>,
+    /// Which boot that was (Linux)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_id: Option<String>,
+}
+
+impl SavedAt {
+    pub fn now() -> Self {
+        SavedAt {
+            wall: Local::now(),
+            since_boot: clock::since_boot().map(|since| since.as_secs()),
+            boot_id: boot_id(),
+        }
+    }
+
+    /// Seconds from this moment until `now`: on the clock since boot when
+    /// both are from the same boot, on the wall clock otherwise
+    pub fn until(&self, now: &SavedAt) -> u64 {
+        let same_boot = match (&self.boot_id, &now.boot_id) {
+            (Some(then), Some(id)) => then == id,
+            _ => true,
+        };
+        match (self.since_boot, now.since_boot) {
+            (Some(then), Some(since_boot)) if same_boot && since_boot >= then => since_boot - then,
+            _ => (now.wall - self.wall).num_seconds().max(0) as u64,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn boot_id() -> Option<String> {
+    static ID: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    ID.get_or_init(|| {
+        fs::read_to_string("/proc/sys/kernel/random/boot_id")
+            .ok()
+            .map(|id| id.trim().to_string())
+    })
+    .clone()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn boot_id() -> Option<String> {
+    None
+}
+
+// Directory holding the state file and other runtime files
+pub fn state_dir() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("rust-pomo")
+}
+
+// A file of the named session in the state directory: `<stem>.<extension>`
+// for the unnamed session, `<stem>.<name>.<extension>` otherwise
+pub fn session_file(name: Option<&str>, stem: &str, extension: &str) -> PathBuf {
+    state_dir().join(match name {
+        Some(name) => format!("{}.{}.{}", stem, name, extension),
+        None => format!("{}.{}", stem, extension),
+    })
+}
+
+// Location of the state file
+pub fn state_path() -> PathBuf {
+    session_file(name().as_deref(), "session", "json")
+}
+
+// Write the state file, creating the parent directory if needed
+pub fn save(state: &PomodoroSession) -> io::Result<()> {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut state = state.clone();
+    state.saved = Some(SavedAt::now());
+    let json = serde_json::to_string_pretty(&state)?;
+
+    // Write to a temporary file first so a crash never leaves a half-written state
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(tmp, path)
+}
+
+// Read the state file, if any
+pub fn load() -> io::Result<Option<PomodoroSession>> {
+    match fs::read_to_string(state_path()) {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Count the time since a resumed session was saved as a pause of the phase
+// it was in; returns that time in seconds
+pub fn reconcile(session: &mut PomodoroSession) -> u64 {
+    let Some(saved) = session.saved.take() else {
+        return 0;
+    };
+    let away = saved.until(&SavedAt::now());
+    if away >= MIN_AWAY && session.phase_started_at.is_some() {
+        session.add_time_away(away, Local::now());
+    }
+    away
+}
+
+// Remove the state file once a session is complete
+pub fn clear() -> io::Result<()> {
+    match fs::remove_file(state_path()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn time_away_follows_the_clock_since_boot() {
+        let then = SavedAt {
+            wall: Local::now(),
+            since_boot: Some(1000),
+            boot_id: Some("a".to_string()),
+        };
+        // The wall clock was set back an hour in between
+        let now = SavedAt {
+            wall: then.wall - TimeDelta::hours(1),
+            since_boot: Some(1600),
+            boot_id: Some("a".to_string()),
+        };
+        assert_eq!(then.until(&now), 600);
+
+        // After a restart only the wall clock can tell
+        let rebooted = SavedAt {
+            wall: then.wall + TimeDelta::minutes(30),
+            since_boot: Some(2000),
+            boot_id: Some("b".to_string()),
+        };
+        assert_eq!(then.until(&rebooted), 1800);
+        let no_boot_clock = SavedAt {
+            since_boot: None,
+            boot_id: None,
+            ..rebooted
+        };
+        assert_eq!(then.until(&no_boot_clock), 1800);
+    }
+}
@@ -0,0 +1,242 @@
+// `pomo stats`: focus time from the session log, broken down by task and tag
+// (and by schedule entry for sessions run with --schedule, and with --by-repo
+// by git repository and branch for sessions run with --git). Overtime after a
+// focus phase (--overtime) counts as focus time. With a `daily_goal` in the
+// config, today's progress and the streak come first.
+//
+// Interruptions are the pauses of focus phases. Fewer interruptions per
+// pomodoro is what the technique is after, so their rate comes next, and the
+// reasons given for them (`pomo pause --reason`, the prompt after `p`) are
+// counted at the end.
+//
+// Pomodoros voided under `--strict` are counted apart from the valid ones and
+// never add to them; their focus time still counts.
+
+use crate::{
+    duration::format_duration,
+    goal::Progress,
+    history::{Outcome, PhaseRecord},
+    session::Phase,
+};
+use chrono::Local;
+use std::collections::HashMap;
+
+const NO_TASK: &str = "(no task)";
+const NO_REPO: &str = "(no repository)";
+const NO_REASON: &str = "(no reason)";
+
+/// Totals for one task or tag
+#[derive(Debug, Default)]
+struct Total {
+    pomodoros: u32,
+    /// Pomodoros voided under `--strict`
+    voided: u32,
+    /// Focus time including overtime
+    seconds: u64,
+    overtime: u64,
+}
+
+impl Total {
+    fn add(&mut self, record: &PhaseRecord) {
+        match record.outcome {
+            Outcome::Completed => self.pomodoros += 1,
+            Outcome::Void => self.voided += 1,
+            _ => {}
+        }
+        self.seconds += record.actual + record.overtime;
+        self.overtime += record.overtime;
+    }
+}
+
+pub fn print_stats(records: &[PhaseRecord], daily_goal: Option<u32>, by_repo: bool) {
+    let focus: Vec<&PhaseRecord> = records.iter().filter(|r| r.phase == Phase::Focus).collect();
+    if focus.is_empty() {
+        println!("No focus sessions recorded yet.");
+        return;
+    }
+
+    let mut overall = Total::default();
+    let mut by_task: HashMap<&str, Total> = HashMap::new();
+    let mut by_tag: HashMap<&str, Total> = HashMap::new();
+    let mut by_entry: HashMap<String, Total> = HashMap::new();
+    let mut by_repository: HashMap<&str, Total> = HashMap::new();
+    let mut by_branch: HashMap<String, Total> = HashMap::new();
+
+    for record in &focus {
+        overall.add(record);
+        by_task
+            .entry(record.task.as_deref().unwrap_or(NO_TASK))
+            .or_default()
+            .add(record);
+        for tag in &record.tags {
+            by_tag.entry(tag).or_default().add(record);
+        }
+        if let Some(entry) = record.schedule_entry {
+            by_entry
+                .entry(format!("entry {}", entry))
+                .or_default()
+                .add(record);
+        }
+        by_repository
+            .entry(record.repo.as_deref().unwrap_or(NO_REPO))
+            .or_default()
+            .add(record);
+        if let (Some(repo), Some(branch)) = (&record.repo, &record.branch) {
+            by_branch
+                .entry(format!("{}:{}", repo, branch))
+                .or_default()
+                .add(record);
+        }
+    }
+
+    print!("Completed pomodoros: {}", overall.pomodoros);
+    if overall.voided > 0 {
+        print!("  Voided: {}", overall.voided);
+    }
+    print!("  Focus time: {}", format_duration(overall.seconds));
+    if overall.overtime > 0 {
+        print!(" (overtime {})", format_duration(overall.overtime));
+    }
+    println!();
+    let interruptions: u32 = focus.iter().map(|r| r.pauses).sum();
+    if interruptions > 0 {
+        print!("Interruptions: {}", interruptions);
+        if overall.pomodoros > 0 {
+            print!(
+                " ({:.1} per pomodoro)",
+                f64::from(interruptions) / f64::from(overall.pomodoros)
+            );
+        }
+        println!();
+    }
+    if let Some(goal) = daily_goal {
+        let progress = Progress::of(records, goal, Local::now().date_naive());
+        println!(
+            "Today: {}/{}  Streak: {} day{}",
+            progress.done,
+            goal,
+            progress.streak,
+            if progress.streak == 1 { "" } else { "s" }
+        );
+    }
+
+    print_table("By task", by_task);
+    if !by_tag.is_empty() {
+        print_table("By tag", by_tag);
+    }
+    if !by_entry.is_empty() {
+        print_table("By schedule entry", by_entry);
+    }
+    if by_repo {
+        print_table("By repository", by_repository);
+        if !by_branch.is_empty() {
+            print_table("By branch", by_branch);
+        }
+    }
+
+    let reasons = by_reason(&focus);
+    if !reasons.is_empty() {
+        let width = reasons
+            .iter()
+            .map(|(reason, _, _)| reason.chars().count())
+            .max()
+            .unwrap_or(0);
+        println!();
+        println!("Interruptions by reason:");
+        for (reason, count, seconds) in reasons {
+            println!(
+                "  {:<width$}  {:>3} times  {:>8}",
+                reason,
+                count,
+                format_duration(seconds),
+                width = width
+            );
+        }
+    }
+}
+
+// Interruptions of the focus phases by reason: how many and how long in
+// total, most frequent first
+fn by_reason<'a>(focus: &[&'a PhaseRecord]) -> Vec<(&'a str, u32, u64)> {
+    let mut totals: HashMap<&str, (u32, u64)> = HashMap::new();
+    for interruption in focus.iter().flat_map(|r| &r.interruptions) {
+        let total = totals
+            .entry(interruption.reason.as_deref().unwrap_or(NO_REASON))
+            .or_default();
+        total.0 += 1;
+        total.1 += interruption.seconds;
+    }
+    let mut rows: Vec<(&str, u32, u64)> = totals
+        .into_iter()
+        .map(|(reason, (count, seconds))| (reason, count, seconds))
+        .collect();
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(b.0)));
+    rows
+}
+
+// Print one breakdown, most time first
+fn print_table<K: AsRef<str> + Ord>(title: &str, totals: HashMap<K, Total>) {
+    let mut rows: Vec<(K, Total)> = totals.into_iter().collect();
+    rows.sort_by(|a, b| b.1.seconds.cmp(&a.1.seconds).then(a.0.cmp(&b.0)));
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.as_ref().chars().count())
+        .max()
+        .unwrap_or(0);
+
+    println!();
+    println!("{}:", title);
+    for (name, total) in rows {
+        print!(
+            "  {:<width$}  {:>3} pomodoros  {:>8}",
+            name.as_ref(),
+            total.pomodoros,
+            format_duration(total.seconds),
+            width = width
+        );
+        if total.voided > 0 {
+            print!("  ({} void)", total.voided);
+        }
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{Interruption, PomodoroSession};
+
+    #[test]
+    fn voided_pomodoros_are_counted_apart() {
+        let session = PomodoroSession::new(1500, 300, 2);
+        let mut total = Total::default();
+        for outcome in [
+            Outcome::Completed,
+            Outcome::Void,
+            Outcome::Void,
+            Outcome::Skipped,
+        ] {
+            total.add(&PhaseRecord::from_session(&session, outcome));
+        }
+        assert_eq!((total.pomodoros, total.voided), (1, 2));
+    }
+
+    #[test]
+    fn interruptions_are_counted_by_reason() {
+        let pause = |reason: Option<&str>, seconds| Interruption {
+            started_at: Local::now(),
+            seconds,
+            reason: reason.map(str::to_string),
+        };
+        let mut session = PomodoroSession::new(1500, 300, 2);
+        session.interruptions = vec![pause(Some("phone call"), 120), pause(None, 30)];
+        let first = PhaseRecord::from_session(&session, Outcome::Completed);
+        session.interruptions = vec![pause(Some("phone call"), 60), pause(Some("idle"), 300)];
+        let second = PhaseRecord::from_session(&session, Outcome::Completed);
+
+        assert_eq!(
+            by_reason(&[&first, &second]),
+            [("phone call", 2, 180), ("idle", 1, 300), (NO_REASON, 1, 30)]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+// `pomo statusline`: a compact one-line summary for tmux, starship or a shell
+// prompt, e.g.
+//   🍅 12:34 focus (2/4)
+//   ☕ 03:10 break (2/4) paused
+//   🍅 +04:10 focus (2/4) overtime
+//
+// The line comes from the running session over the control socket; if none is
+// running, an interrupted session in the state file is shown instead, and
+// with no session at all the output is empty so the status bar stays clean.
+
+use crate::session::{Phase, PomodoroSession, cycle_of};
+
+/// Render the summary line. `note` says what the session is doing if it isn't
+/// simply counting down ("paused", "ready", "overtime", "interrupted").
+pub fn render(session: &PomodoroSession, note: Option<&str>) -> String {
+    let (icon, name) = match session.phase {
+        Phase::Focus => ("🍅", "focus"),
+        Phase::Break => ("☕", "break"),
+    };
+    // Past the end of focus with --overtime the clock counts up instead
+    let time = if session.overtime_elapsed > 0 {
+        format!("+{}", clock(session.overtime_elapsed))
+    } else {
+        clock(session.remaining())
+    };
+    let mut line = format!(
+        "{} {} {} ({})",
+        icon,
+        time,
+        name,
+        cycle_of(session.cycle, session.cycles)
+    );
+    if let Some(note) = note {
+        line.push(' ');
+        line.push_str(note);
+    }
+    line
+}
+
+// Seconds as a countdown clock: mm:ss, or h:mm:ss from an hour up
+pub fn clock(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{:02}:{:02}", m, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shows_remaining_time_phase_and_cycle() {
+        let mut session = PomodoroSession::new(25 * 60, 5 * 60, 4);
+        session.cycle = 2;
+        session.elapsed = 12 * 60 + 26;
+        assert_eq!(render(&session, None), "🍅 12:34 focus (2/4)");
+
+        session.advance();
+        assert_eq!(
+            render(&session, Some("ready")),
+            "☕ 05:00 break (2/4) ready"
+        );
+    }
+
+    #[test]
+    fn long_phases_show_hours() {
+        let session = PomodoroSession::new(90 * 60, 60, 1);
+        assert_eq!(render(&session, None), "🍅 1:30:00 focus (1/1)");
+    }
+
+    #[test]
+    fn overtime_counts_up() {
+        let mut session = PomodoroSession::new(25 * 60, 5 * 60, 1);
+        session.elapsed = 25 * 60;
+        session.overtime_elapsed = 250;
+        assert_eq!(
+            render(&session, Some("overtime")),
+            "🍅 +04:10 focus (1/1) overtime"
+        );
+    }
+}
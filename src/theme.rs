@@ -0,0 +1,254 @@
+// Progress bar themes, picked with `theme` in the config file:
+//
+//   theme = "classic"    Focus 2/4 [#########---------------] 23:52  38% ends 14:25 write report
+//   theme = "minimal"    Focus 2/4 23:52 write report
+//   theme = "tomato"     🍅🍅🍅 Focus 2/4 ████████▓░░░░░░░░░░░ 23:52 ends 14:25 write report
+//
+// or a template of its own, in indicatif's syntax:
+//
+//   theme = "{tomatoes}{prefix:.bold.$color} {bar:30.$color} {countdown} {msg}"
+//
+// Besides indicatif's keys ({prefix}, {bar}, {msg}, {percent}, ...) a template
+// can use {countdown} (the time left), {ends} (the time the phase ends) and
+// {tomatoes} (a 🍅 for each pomodoro completed today, from the session log);
+// `$color` stands for the colour of the phase. Colours and bar characters can
+// be changed for any theme:
+//
+//   [progress]
+//   focus_color = "red"
+//   break_color = "green"
+//   paused_color = "yellow"
+//   chars = "█▓░"            (done, in progress, ..., to go)
+//
+// Colours are the names `console` knows (red, cyan, ...) or 0-255.
+
+use crate::{history, session::Phase, statusline::clock, tui};
+use chrono::Local;
+use indicatif::{ProgressState, ProgressStyle};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Tomatoes shown one by one; more become a count
+const MAX_TOMATOES: usize = 8;
+
+/// Layout of the progress bar
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Theme {
+    #[default]
+    Classic,
+    Minimal,
+    Tomato,
+    /// A template in indicatif's syntax
+    Custom(String),
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Theme::Classic),
+            "minimal" => Ok(Theme::Minimal),
+            "tomato" => Ok(Theme::Tomato),
+            template if template.contains('{') => {
+                ProgressStyle::with_template(&template.replace("$color", "white"))
+                    .map_err(|e| format!("bad progress bar template: {}", e))?;
+                Ok(Theme::Custom(template.to_string()))
+            }
+            other => Err(format!(
+                "'{}' is not classic, minimal, tomato or a template like \"{{prefix}} {{bar}} {{countdown}}\"",
+                other
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Theme {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Theme::Classic => write!(f, "classic"),
+            Theme::Minimal => write!(f, "minimal"),
+            Theme::Tomato => write!(f, "tomato"),
+            Theme::Custom(template) => write!(f, "{}", template),
+        }
+    }
+}
+
+impl From<Theme> for String {
+    fn from(theme: Theme) -> String {
+        theme.to_string()
+    }
+}
+
+/// `[progress]` in the config file: colours and bar characters, over the
+/// theme's
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProgressSettings {
+    pub focus_color: Option<String>,
+    pub break_color: Option<String>,
+    pub paused_color: Option<String>,
+    /// Bar characters: done, in progress (any number), to go
+    #[serde(deserialize_with = "deserialize_chars")]
+    pub chars: Option<String>,
+}
+
+fn deserialize_chars<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // null as written to the state file when not set
+    let Some(chars) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    if chars.chars().count() < 2 {
+        return Err(serde::de::Error::custom(
+            "chars needs at least two characters (done and to go), e.g. \"#>-\"",
+        ));
+    }
+    Ok(Some(chars))
+}
+
+impl ProgressSettings {
+    /// Colour of the bar for the phase
+    pub fn color(&self, phase: Phase, paused: bool) -> &str {
+        match phase {
+            _ if paused => self.paused_color.as_deref().unwrap_or("yellow"),
+            Phase::Focus => self.focus_color.as_deref().unwrap_or("cyan"),
+            Phase::Break => self.break_color.as_deref().unwrap_or("green"),
+        }
+    }
+}
+
+impl Theme {
+    fn template(&self) -> &str {
+        match self {
+            Theme::Classic => {
+                "{prefix:.bold.$color} [{bar:40.$color/blue}] {countdown} {percent:>3}% ends {ends} {msg}"
+            }
+            Theme::Minimal => "{prefix:.$color} {countdown} {msg}",
+            Theme::Tomato => {
+                "{tomatoes}{prefix:.bold.$color} {bar:30.$color} {countdown} ends {ends} {msg}"
+            }
+            Theme::Custom(template) => template,
+        }
+    }
+
+    fn chars(&self) -> &str {
+        match self {
+            Theme::Tomato => "█▓░",
+            _ => "##-",
+        }
+    }
+
+    /// Whether the bar shows today's pomodoros (so the log has to be read)
+    pub fn counts_pomodoros(&self) -> bool {
+        self.template().contains("{tomatoes")
+    }
+
+    // The bar for a phase. The remaining time and the end time are worked
+    // out from the bar's position (in milliseconds), so the end time moves on
+    // while the timer is paused.
+    pub fn style(
+        &self,
+        settings: &ProgressSettings,
+        phase: Phase,
+        paused: bool,
+        today: usize,
+    ) -> ProgressStyle {
+        fn remaining_ms(state: &ProgressState) -> u64 {
+            state.len().unwrap_or(0).saturating_sub(state.pos())
+        }
+        let template = self
+            .template()
+            .replace("$color", settings.color(phase, paused));
+        let style = ProgressStyle::with_template(&template).unwrap_or_else(|e| {
+            log::warn!("Bad progress bar template ({}); using the classic one", e);
+            ProgressStyle::with_template(&Theme::Classic.template().replace("$color", "cyan"))
+                .unwrap()
+        });
+        let tomatoes = tomatoes(today);
+        style
+            .with_key(
+                "countdown",
+                |state: &ProgressState, w: &mut dyn fmt::Write| {
+                    // Round up, so the clock shows 00:00 only once the phase is over
+                    let _ = write!(w, "{}", clock(remaining_ms(state).div_ceil(1000)));
+                },
+            )
+            .with_key("ends", |state: &ProgressState, w: &mut dyn fmt::Write| {
+                let remaining = chrono::Duration::milliseconds(remaining_ms(state) as i64);
+                let _ = write!(w, "{}", (Local::now() + remaining).format("%H:%M"));
+            })
+            .with_key(
+                "tomatoes",
+                move |_: &ProgressState, w: &mut dyn fmt::Write| {
+                    let _ = write!(w, "{}", tomatoes);
+                },
+            )
+            .progress_chars(settings.chars.as_deref().unwrap_or(self.chars()))
+    }
+}
+
+// Pomodoros completed today, for {tomatoes}
+pub fn completed_today() -> usize {
+    match history::load() {
+        Ok(records) => tui::completed_on(&records, Local::now().date_naive()),
+        Err(e) => {
+            log::warn!("Failed to read session log: {}", e);
+            0
+        }
+    }
+}
+
+// A tomato per pomodoro, followed by a space (nothing for none)
+fn tomatoes(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        n if n <= MAX_TOMATOES => format!("{} ", "🍅".repeat(n)),
+        n => format!("🍅×{} ", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_are_named_or_templates() {
+        assert_eq!("minimal".parse(), Ok(Theme::Minimal));
+        assert_eq!("tomato".parse(), Ok(Theme::Tomato));
+        let custom: Theme = "{prefix:.$color} {bar:20} {countdown}".parse().unwrap();
+        assert_eq!(custom.to_string(), "{prefix:.$color} {bar:20} {countdown}");
+        assert!(!custom.counts_pomodoros());
+        assert!(Theme::Tomato.counts_pomodoros());
+        assert!("fancy".parse::<Theme>().is_err());
+        assert!("{bar:x}".parse::<Theme>().is_err());
+
+        assert_eq!(tomatoes(0), "");
+        assert_eq!(tomatoes(3), "🍅🍅🍅 ");
+        assert_eq!(tomatoes(12), "🍅×12 ");
+
+        let settings: ProgressSettings =
+            toml::from_str("focus_color = \"red\"\nchars = \"█░\"").unwrap();
+        assert_eq!(settings.color(Phase::Focus, false), "red");
+        assert_eq!(settings.color(Phase::Focus, true), "yellow");
+        assert_eq!(settings.color(Phase::Break, false), "green");
+        assert!(toml::from_str::<ProgressSettings>("chars = \"#\"").is_err());
+        // As saved in the state file
+        let json = serde_json::to_string(&ProgressSettings::default()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ProgressSettings>(&json).unwrap(),
+            ProgressSettings::default()
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// Timer engine: how much of a phase has run, with pauses taken out.
+//
+// Time is measured against `Instant` (a monotonic clock) rather than by
+// counting sleeps, so slow Wi-Fi commands or late wakeups never stretch a
+// phase. Every method takes the current instant explicitly, which keeps the
+// accounting independent of real time (and easy to test).
+//
+// A phase can also be given an end on the wall clock (`--until 14:30`): its
+// length is worked out from the time of day, and worked out again whenever
+// the wall clock and the timer drift apart (the system clock was set, the
+// machine slept, the phase was paused), so it still ends at 14:30.
+
+use chrono::{DateTime, Local, NaiveTime, TimeDelta};
+use std::time::{Duration, Instant};
+
+/// Elapsed-time accounting for one phase
+#[derive(Debug, Clone)]
+pub struct PhaseTimer {
+    target: Duration,
+    /// Running time accumulated before the current stretch
+    counted: Duration,
+    /// Start of the current stretch; `None` while paused
+    running_since: Option<Instant>,
+    /// Time spent paused in earlier pauses
+    paused_total: Duration,
+    /// Start of the current pause
+    paused_since: Option<Instant>,
+}
+
+impl PhaseTimer {
+    /// Start timing a phase of length `target`, of which `already` has run
+    /// (non-zero when a saved session is resumed)
+    pub fn start(target: Duration, already: Duration, now: Instant) -> Self {
+        PhaseTimer {
+            target,
+            counted: already,
+            running_since: Some(now),
+            paused_total: Duration::ZERO,
+            paused_since: None,
+        }
+    }
+
+    /// Running time so far, excluding pauses
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        let running = self
+            .running_since
+            .map(|since| now.saturating_duration_since(since))
+            .unwrap_or_default();
+        self.counted + running
+    }
+
+    /// Time left until the phase is over
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.target.saturating_sub(self.elapsed(now))
+    }
+
+    /// Time spent paused since the timer was started
+    pub fn paused(&self, now: Instant) -> Duration {
+        let current = self
+            .paused_since
+            .map(|since| now.saturating_duration_since(since))
+            .unwrap_or_default();
+        self.paused_total + current
+    }
+
+    /// Change the length of the phase (`pomo extend`); the phase is over at
+    /// once if it has already run for longer
+    pub fn set_target(&mut self, target: Duration) {
+        self.target = target;
+    }
+
+    pub fn is_finished(&self, now: Instant) -> bool {
+        self.elapsed(now) >= self.target
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.running_since.is_none()
+    }
+
+    /// Stop the clock; does nothing if already paused
+    pub fn pause(&mut self, now: Instant) {
+        if !self.is_paused() {
+            self.counted = self.elapsed(now);
+            self.running_since = None;
+            self.paused_since = Some(now);
+        }
+    }
+
+    /// Pause as of an earlier instant, taking back the running time since
+    /// then (as when the user turns out to have been idle). Never reaches
+    /// back past the start of the current stretch.
+    pub fn pause_since(&mut self, since: Instant, now: Instant) {
+        if let Some(running_since) = self.running_since {
+            let since = since.clamp(running_since, now);
+            self.counted += since.saturating_duration_since(running_since);
+            self.running_since = None;
+            self.paused_since = Some(since);
+        }
+    }
+
+    /// Leave out the last `gap` of time, running or paused (the system was
+    /// asleep but the monotonic clock kept counting)
+    pub fn leave_out(&mut self, gap: Duration, now: Instant) {
+        for since in [&mut self.running_since, &mut self.paused_since]
+            .into_iter()
+            .flatten()
+        {
+            *since = (*since + gap).min(now);
+        }
+    }
+
+    /// Restart the clock; does nothing if already running
+    pub fn resume(&mut self, now: Instant) {
+        if self.is_paused() {
+            self.paused_total = self.paused(now);
+            self.paused_since = None;
+            self.running_since = Some(now);
+        }
+    }
+}
+
+/// `time` today, or `None` if it has already passed. A time the clocks skip
+/// when summer time starts (02:30 on a day that goes from 02:00 to 03:00)
+/// is taken as the same time on the other side of the gap.
+pub fn deadline_today(time: NaiveTime, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let local = now.date_naive().and_time(time);
+    local
+        .and_local_timezone(Local)
+        .earliest()
+        .or_else(|| {
+            let before = (local - TimeDelta::hours(1))
+                .and_local_timezone(Local)
+                .earliest()?;
+            Some(before + TimeDelta::hours(1))
+        })
+        .filter(|deadline| *deadline > now)
+}
+
+/// Length a phase that has run for `elapsed` needs to end at `deadline`
+pub fn length_until(
+    deadline: DateTime<Local>,
+    now: DateTime<Local>,
+    elapsed: Duration,
+) -> Duration {
+    let left = (deadline - now).to_std().unwrap_or_default();
+    elapsed + Duration::from_secs(left.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn counts_time_since_start() {
+        let t0 = Instant::now();
+        let timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+        assert_eq!(timer.elapsed(t0 + secs(10)), secs(10));
+        assert_eq!(timer.remaining(t0 + secs(10)), secs(50));
+        assert!(!timer.is_finished(t0 + secs(59)));
+        assert!(timer.is_finished(t0 + secs(60)));
+    }
+
+    #[test]
+    fn left_out_time_is_not_counted() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+        timer.leave_out(secs(30), t0 + secs(40));
+        assert_eq!(timer.elapsed(t0 + secs(40)), secs(10));
+        // Never more than there is
+        timer.leave_out(secs(30), t0 + secs(50));
+        assert_eq!(timer.elapsed(t0 + secs(50)), Duration::ZERO);
+    }
+
+    #[test]
+    fn target_can_change_mid_phase() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+        timer.set_target(secs(120));
+        assert!(!timer.is_finished(t0 + secs(90)));
+        assert_eq!(timer.remaining(t0 + secs(90)), secs(30));
+        timer.set_target(secs(30));
+        assert!(timer.is_finished(t0 + secs(40)));
+        assert_eq!(timer.remaining(t0 + secs(40)), Duration::ZERO);
+    }
+
+    #[test]
+    fn deadline_gives_the_length() {
+        let now = Local.with_ymd_and_hms(2024, 3, 5, 14, 0, 0).unwrap();
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let deadline = deadline_today(time(14, 30), now).unwrap();
+        assert_eq!(length_until(deadline, now, Duration::ZERO), secs(1800));
+        assert_eq!(deadline_today(time(13, 30), now), None);
+        assert_eq!(deadline_today(time(14, 0), now), None);
+
+        // The clock jumped 10 minutes ahead 5 minutes in
+        let later = now + chrono::Duration::minutes(15);
+        assert_eq!(length_until(deadline, later, secs(300)), secs(300 + 900));
+        // Past the deadline, the phase is over
+        let past = deadline + chrono::Duration::minutes(1);
+        assert_eq!(length_until(deadline, past, secs(300)), secs(300));
+    }
+
+    #[test]
+    fn resumed_phase_starts_from_saved_elapsed_time() {
+        let t0 = Instant::now();
+        let timer = PhaseTimer::start(secs(60), secs(45), t0);
+        assert_eq!(timer.elapsed(t0), secs(45));
+        assert_eq!(timer.remaining(t0 + secs(5)), secs(10));
+    }
+
+    #[test]
+    fn paused_time_is_not_counted() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+
+        timer.pause(t0 + secs(10));
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed(t0 + secs(100)), secs(10));
+
+        timer.resume(t0 + secs(100));
+        assert_eq!(timer.elapsed(t0 + secs(120)), secs(30));
+        assert_eq!(timer.remaining(t0 + secs(120)), secs(30));
+    }
+
+    #[test]
+    fn pause_since_takes_back_running_time() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+
+        timer.pause_since(t0 + secs(10), t0 + secs(40));
+        assert_eq!(timer.elapsed(t0 + secs(40)), secs(10));
+        assert_eq!(timer.paused(t0 + secs(40)), secs(30));
+
+        // Not back past the last resume
+        timer.resume(t0 + secs(50));
+        timer.pause_since(t0, t0 + secs(55));
+        assert_eq!(timer.elapsed(t0 + secs(55)), secs(10));
+        assert_eq!(timer.paused(t0 + secs(55)), secs(45));
+    }
+
+    #[test]
+    fn repeated_pause_and_resume_are_ignored() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+
+        timer.pause(t0 + secs(10));
+        timer.pause(t0 + secs(20));
+        assert_eq!(timer.elapsed(t0 + secs(30)), secs(10));
+
+        timer.resume(t0 + secs(30));
+        timer.resume(t0 + secs(40));
+        assert_eq!(timer.elapsed(t0 + secs(50)), secs(30));
+    }
+
+    #[test]
+    fn several_pauses_add_up() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+        for i in 0..3 {
+            // 10s running, then 5s paused, three times over
+            let base = t0 + secs(15 * i);
+            timer.pause(base + secs(10));
+            timer.resume(base + secs(15));
+        }
+        assert_eq!(timer.elapsed(t0 + secs(45)), secs(30));
+    }
+
+    #[test]
+    fn pause_time_is_accumulated() {
+        let t0 = Instant::now();
+        let mut timer = PhaseTimer::start(secs(60), Duration::ZERO, t0);
+        assert_eq!(timer.paused(t0 + secs(10)), Duration::ZERO);
+
+        timer.pause(t0 + secs(10));
+        assert_eq!(timer.paused(t0 + secs(15)), secs(5));
+        timer.resume(t0 + secs(20));
+        assert_eq!(timer.paused(t0 + secs(30)), secs(10));
+
+        timer.pause(t0 + secs(40));
+        assert_eq!(timer.paused(t0 + secs(43)), secs(13));
+    }
+
+    #[test]
+    fn remaining_never_goes_negative() {
+        let t0 = Instant::now();
+        let timer = PhaseTimer::start(secs(5), Duration::ZERO, t0);
+        assert_eq!(timer.remaining(t0 + secs(10)), Duration::ZERO);
+    }
+}
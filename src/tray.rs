@@ -0,0 +1,442 @@
+// `--tray`: an icon in the system tray (the menu bar on macOS) for those who
+// don't keep a terminal in view. pomo has to be built with the `tray` feature:
+//
+//   cargo install rust-pomo --features tray
+//   pomo --tray                 (or `pomo start --tray`)
+//   pomo tray                   (for a session already running)
+//
+//   ● 12:34   the time left, next to a dot in the phase's colour: red for
+//             focus, green for breaks, orange in overtime, grey while paused
+//             or waiting for the next phase
+//   menu      Pause / Resume (Start next phase between phases), Skip,
+//             Snooze 5m (between phases), Stop, Close icon
+//
+// The icon is a companion process (`pomo tray`) that --tray starts next to the
+// session. Once a second it asks the session for its state over the control
+// socket, like `pomo status --json` does, and it sends the menu's commands the
+// same way, so the timer never waits on a GUI event loop (which macOS wants on
+// the main thread). It goes away when the session ends. Windows shows no text
+// next to tray icons; the time is in the tooltip there.
+//
+// Linux needs a tray that speaks StatusNotifierItem (KDE, most panels, GNOME
+// with the AppIndicator extension).
+
+use crate::{
+    daemon,
+    error::{Error, Result},
+    session::{Phase, cycle_of},
+    state,
+    statusline::clock,
+};
+use serde::Deserialize;
+use std::{
+    env, io,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// How often the icon asks the session for its state
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+const POLL: Duration = Duration::from_secs(1);
+
+/// Side of the icon in pixels
+const ICON_SIZE: u32 = 32;
+
+const RED: [u8; 3] = [0xe5, 0x48, 0x3a];
+const GREEN: [u8; 3] = [0x3a, 0xa6, 0x5a];
+const ORANGE: [u8; 3] = [0xf0, 0x96, 0x1e];
+const GREY: [u8; 3] = [0x9e, 0x9e, 0x9e];
+
+/// The session's reply to `status-json`, as far as the icon needs it
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Reply {
+    state: Option<String>,
+    phase: Option<Phase>,
+    cycle: u32,
+    cycles: u32,
+    remaining: u64,
+    overtime: u64,
+    task: Option<String>,
+}
+
+/// What the icon and its menu show
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct View {
+    /// Text next to the icon
+    title: String,
+    tooltip: String,
+    color: [u8; 3],
+    /// Label and command of the first menu item
+    toggle: (&'static str, &'static str),
+    /// A phase is waiting to start (and can be snoozed)
+    between_phases: bool,
+}
+
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+impl View {
+    fn of(reply: &Reply) -> View {
+        let state = reply.state.as_deref().unwrap_or("starting");
+        let Some(phase) = reply.phase else {
+            return View {
+                title: String::new(),
+                tooltip: "pomo: starting".to_string(),
+                color: GREY,
+                toggle: ("Pause", "pause"),
+                between_phases: false,
+            };
+        };
+        let between_phases = matches!(state, "waiting" | "snoozed");
+        let time = if reply.overtime > 0 {
+            format!("+{}", clock(reply.overtime))
+        } else {
+            clock(reply.remaining)
+        };
+        let mut tooltip = format!(
+            "{} {}: {}",
+            match phase {
+                Phase::Focus => "Focus",
+                Phase::Break => "Break",
+            },
+            cycle_of(reply.cycle, reply.cycles),
+            time
+        );
+        match state {
+            "paused" | "overtime" | "snoozed" => tooltip.push_str(&format!(" ({})", state)),
+            "waiting" => tooltip.push_str(" (waiting to start)"),
+            _ => {}
+        }
+        if let Some(task) = &reply.task {
+            tooltip.push_str(&format!(" · {}", task));
+        }
+        View {
+            title: match state {
+                "waiting" => "ready".to_string(),
+                "snoozed" => "snoozed".to_string(),
+                _ => time,
+            },
+            tooltip,
+            color: match (state, phase) {
+                ("paused" | "waiting" | "snoozed", _) => GREY,
+                ("overtime", _) => ORANGE,
+                (_, Phase::Focus) => RED,
+                (_, Phase::Break) => GREEN,
+            },
+            toggle: match state {
+                "paused" => ("Resume", "resume"),
+                _ if between_phases => ("Start next phase", "resume"),
+                _ => ("Pause", "pause"),
+            },
+            between_phases,
+        }
+    }
+}
+
+// The icon: a dot in `color` on a transparent square, as RGBA
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn icon_rgba(color: [u8; 3]) -> Vec<u8> {
+    let center = ICON_SIZE as f32 / 2.0;
+    let radius = center - 2.0;
+    let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+            // Soften the edge over one pixel
+            let alpha = (radius + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0);
+            rgba.extend_from_slice(&color);
+            rgba.push((alpha * 255.0).round() as u8);
+        }
+    }
+    rgba
+}
+
+// Fail early (before a session starts) if this build has no tray support
+pub fn supported() -> io::Result<()> {
+    if cfg!(feature = "tray") {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "this build has no tray icon; \
+             reinstall with `cargo install rust-pomo --features tray`",
+        ))
+    }
+}
+
+// Start the icon for this session (`pomo tray`); it ends with the session
+pub fn spawn() -> io::Result<()> {
+    supported()?;
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .arg("tray")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    if let Some(name) = state::name() {
+        command.args(["--name", &name]);
+    }
+    let mut child = command.spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// `pomo tray`: show the running session in the tray until it ends
+pub fn run() -> Result<()> {
+    supported()?;
+    if !daemon::is_running() {
+        return Err(Error::NotRunning(io::Error::new(
+            io::ErrorKind::NotFound,
+            "nothing to show in the tray",
+        )));
+    }
+    #[cfg(feature = "tray")]
+    gui::run().map_err(|e| io::Error::new(e.kind(), format!("tray icon: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(feature = "tray")]
+mod gui {
+    use super::*;
+    use tray_icon::{
+        Icon, TrayIcon, TrayIconBuilder,
+        menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+    };
+
+    /// The icon and the menu items that change
+    struct Tray {
+        icon: TrayIcon,
+        status: MenuItem,
+        toggle: MenuItem,
+        skip: MenuItem,
+        snooze: MenuItem,
+        stop: MenuItem,
+        close: MenuItem,
+        shown: Option<View>,
+    }
+
+    impl Tray {
+        fn new() -> io::Result<Tray> {
+            let status = MenuItem::new("pomo", false, None);
+            let toggle = MenuItem::new("Pause", true, None);
+            let skip = MenuItem::new("Skip", true, None);
+            let snooze = MenuItem::new("Snooze 5m", false, None);
+            let stop = MenuItem::new("Stop", true, None);
+            let close = MenuItem::new("Close icon", true, None);
+            let menu = Menu::with_items(&[
+                &status,
+                &PredefinedMenuItem::separator(),
+                &toggle,
+                &skip,
+                &snooze,
+                &stop,
+                &PredefinedMenuItem::separator(),
+                &close,
+            ])
+            .map_err(io::Error::other)?;
+            let icon = TrayIconBuilder::new()
+                .with_menu(Box::new(menu))
+                .with_tooltip("pomo")
+                .with_icon(icon(GREY)?)
+                .build()
+                .map_err(io::Error::other)?;
+            Ok(Tray {
+                icon,
+                status,
+                toggle,
+                skip,
+                snooze,
+                stop,
+                close,
+                shown: None,
+            })
+        }
+
+        // Ask the session where it is and redraw what changed; false once
+        // the session is gone
+        fn refresh(&mut self) -> bool {
+            let Ok(reply) = daemon::send("status-json") else {
+                return false;
+            };
+            let view = View::of(&serde_json::from_str(&reply).unwrap_or_default());
+            if self.shown.as_ref() == Some(&view) {
+                return true;
+            }
+            if self
+                .shown
+                .as_ref()
+                .is_none_or(|shown| shown.color != view.color)
+                && let Err(e) = icon(view.color)
+                    .and_then(|icon| self.icon.set_icon(Some(icon)).map_err(io::Error::other))
+            {
+                log::warn!("Failed to update the tray icon: {}", e);
+            }
+            self.icon.set_title(Some(&view.title));
+            if let Err(e) = self.icon.set_tooltip(Some(&view.tooltip)) {
+                log::debug!("tray tooltip: {}", e);
+            }
+            self.status.set_text(&view.tooltip);
+            self.toggle.set_text(view.toggle.0);
+            self.skip.set_enabled(!view.between_phases);
+            self.snooze.set_enabled(view.between_phases);
+            self.shown = Some(view);
+            true
+        }
+
+        // Carry out a click on the menu; false for "Close icon"
+        fn handle(&self, event: &MenuEvent) -> bool {
+            let command = if event.id == self.toggle.id() {
+                self.shown.as_ref().map_or("pause", |view| view.toggle.1)
+            } else if event.id == self.skip.id() {
+                "skip"
+            } else if event.id == self.snooze.id() {
+                "snooze"
+            } else if event.id == self.stop.id() {
+                "stop"
+            } else {
+                return event.id != self.close.id();
+            };
+            match daemon::send(command) {
+                Ok(reply) if reply.starts_with("error: ") => log::warn!("{}", &reply[7..]),
+                Ok(_) => {}
+                Err(e) => log::warn!("{}", e),
+            }
+            true
+        }
+    }
+
+    fn icon(color: [u8; 3]) -> io::Result<Icon> {
+        Icon::from_rgba(icon_rgba(color), ICON_SIZE, ICON_SIZE).map_err(io::Error::other)
+    }
+
+    // The tray runs its D-Bus service on a thread of its own; this thread
+    // only polls and waits for clicks
+    #[cfg(not(any(target_os = "macos", windows)))]
+    pub fn run() -> io::Result<()> {
+        let mut tray = Tray::new()?;
+        while tray.refresh() {
+            match MenuEvent::receiver().recv_timeout(POLL) {
+                Ok(event) if !tray.handle(&event) => break,
+                Err(e) if e.is_disconnected() => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // macOS and Windows want an event loop on this (the main) thread; menu
+    // clicks are forwarded to it so they are handled straight away
+    #[cfg(any(target_os = "macos", windows))]
+    pub fn run() -> io::Result<()> {
+        use std::time::Instant;
+        use tao::{
+            event::{Event, StartCause},
+            event_loop::{ControlFlow, EventLoopBuilder},
+        };
+
+        #[allow(unused_mut)]
+        let mut event_loop = EventLoopBuilder::<MenuEvent>::with_user_event().build();
+        // No Dock icon
+        #[cfg(target_os = "macos")]
+        {
+            use tao::platform::macos::{ActivationPolicy, EventLoopExtMacOS};
+            event_loop.set_activation_policy(ActivationPolicy::Accessory);
+        }
+        let proxy = event_loop.create_proxy();
+        MenuEvent::set_event_handler(Some(move |event| {
+            let _ = proxy.send_event(event);
+        }));
+
+        let mut tray = None;
+        let mut next = Instant::now();
+        event_loop.run(move |event, _, control_flow| {
+            match event {
+                // The icon may only be made once the loop is running
+                Event::NewEvents(StartCause::Init) => match Tray::new() {
+                    Ok(new) => tray = Some(new),
+                    Err(e) => {
+                        eprintln!("pomo: tray icon: {}", e);
+                        *control_flow = ControlFlow::ExitWithCode(1);
+                        return;
+                    }
+                },
+                Event::UserEvent(event) => {
+                    if let Some(tray) = &tray
+                        && !tray.handle(&event)
+                    {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    next = Instant::now();
+                }
+                _ => {}
+            }
+            let Some(tray) = tray.as_mut() else {
+                return;
+            };
+            if Instant::now() >= next {
+                if !tray.refresh() {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                next = Instant::now() + POLL;
+            }
+            *control_flow = ControlFlow::WaitUntil(next);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_follows_the_session_state() {
+        let reply: Reply = serde_json::from_str(
+            r#"{"running":true,"state":"running","phase":"focus","cycle":2,"cycles":4,
+                "elapsed":746,"remaining":754,"duration":1500,"paused":0,"overtime":0,
+                "task":"write report","tags":[]}"#,
+        )
+        .unwrap();
+        let view = View::of(&reply);
+        assert_eq!(view.title, "12:34");
+        assert_eq!(view.tooltip, "Focus 2/4: 12:34 · write report");
+        assert_eq!(view.color, RED);
+        assert_eq!(view.toggle, ("Pause", "pause"));
+
+        let paused = Reply {
+            state: Some("paused".to_string()),
+            phase: Some(Phase::Break),
+            remaining: 60,
+            ..Reply::default()
+        };
+        let view = View::of(&paused);
+        assert_eq!((view.color, view.toggle.1), (GREY, "resume"));
+        assert!(view.tooltip.ends_with("01:00 (paused)"));
+
+        let waiting = Reply {
+            state: Some("waiting".to_string()),
+            ..paused
+        };
+        let view = View::of(&waiting);
+        assert_eq!(view.title, "ready");
+        assert_eq!(view.toggle, ("Start next phase", "resume"));
+        assert!(view.between_phases);
+
+        let overtime = Reply {
+            state: Some("overtime".to_string()),
+            phase: Some(Phase::Focus),
+            overtime: 250,
+            ..Reply::default()
+        };
+        assert_eq!(View::of(&overtime).title, "+04:10");
+        assert_eq!(View::of(&overtime).color, ORANGE);
+        assert_eq!(View::of(&Reply::default()).title, "");
+
+        let rgba = icon_rgba(GREEN);
+        assert_eq!(rgba.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+        // Opaque in the middle, transparent in the corner
+        let middle = ((ICON_SIZE / 2 * ICON_SIZE + ICON_SIZE / 2) * 4) as usize;
+        assert_eq!(rgba[middle..middle + 4], [0x3a, 0xa6, 0x5a, 255]);
+        assert_eq!(rgba[3], 0);
+    }
+}
@@ -0,0 +1,637 @@
+// `--tui`: a full-screen dashboard in place of the single progress bar.
+//
+//   rust-pomo                                                  14:02
+//
+//   FOCUS · cycle 2/4 · write report #work
+//
+//        ███ ███   ███ ███      (big countdown)
+//
+//   [##########--------------------]  38%  ends 14:25
+//
+//   Cycles ●◐○○    Today 3/8 🍅 · 4-day streak   (with a daily goal)
+//
+//   <latest messages>
+//
+//   p pause/resume · s skip · +/- 5m · i note · enter start next phase · q stop
+//
+// The screen is redrawn a few times a second from the shared AppState, so it
+// shows the same thing `pomo status` does. Keys are read one at a time (the
+// terminal is put in raw mode with crossterm, on Windows too; Ctrl+C comes in
+// as a key and interrupts the session like the signal would), and `say!`
+// messages are kept for the dashboard instead of being printed over it. The
+// terminal is put back when the dashboard is dropped; the last messages are
+// printed then.
+// `i` opens a line for a note to the inbox (inbox.rs): keys go to the note
+// until ENTER saves it or Esc drops it, and the timer keeps running.
+//
+// The same screen handling serves the break overlay (--enforce-break overlay,
+// see enforce.rs): a full-screen break countdown that swallows every key
+// (Ctrl+C included) except three presses of Esc in a row.
+
+use crate::{
+    cleanup,
+    goal::Progress,
+    history::{self, Outcome, PhaseRecord},
+    runner::{self, AppState},
+    session::{Phase, PomodoroSession, cycle_of},
+    statusline::clock,
+};
+use chrono::{DateTime, Local, NaiveDate};
+use console::{Term, measure_text_width, style};
+use crossterm::{
+    cursor::{Hide, Show},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::{
+    io::{self, Read},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU8, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often the screen is redrawn
+const REDRAW: Duration = Duration::from_millis(250);
+
+/// Messages kept for the dashboard
+const MESSAGES: usize = 3;
+
+/// Width of the progress bar in characters
+const BAR_WIDTH: usize = 40;
+
+/// Esc presses in a row that end an enforced break early
+const ESCAPE_PRESSES: u8 = 3;
+
+/// Set while the break overlay is up
+static OVERLAY: AtomicBool = AtomicBool::new(false);
+
+/// Esc presses in a row while the overlay is up
+static ESCAPES: AtomicU8 = AtomicU8::new(0);
+
+/// The note being typed after `i`, as bytes so far
+static NOTE: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// `Some` while the dashboard is up: the latest messages. Drawing holds the
+/// lock, so nothing is drawn after restoring.
+static SCREEN: Mutex<Option<Screen>> = Mutex::new(None);
+
+struct Screen {
+    messages: Vec<String>,
+}
+
+/// The running dashboard; the terminal is restored when it is dropped
+pub struct Dashboard;
+
+impl Dashboard {
+    // Take over the terminal and start drawing and reading keys
+    pub fn start(app_state: Arc<AppState>) -> io::Result<Self> {
+        take_screen().map_err(|e| io::Error::new(e.kind(), format!("--tui: {}", e)))?;
+
+        let drawing = Arc::clone(&app_state);
+        thread::spawn(move || draw_loop(&drawing));
+        thread::spawn(move || read_keys(&app_state));
+        Ok(Dashboard)
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        for message in restore() {
+            println!("{}", message);
+        }
+    }
+}
+
+/// The break overlay; taken down when dropped
+pub struct Overlay {
+    /// The overlay took over the terminal itself (rather than covering the
+    /// dashboard), so it puts it back
+    took_screen: bool,
+}
+
+impl Overlay {
+    pub fn start() -> io::Result<Self> {
+        let took_screen = !is_active();
+        // Raw mode (the dashboard's too) passes Ctrl+C and Ctrl+Z on as
+        // plain keys
+        if took_screen {
+            take_screen()?;
+            // Ask the terminal window to go full screen (xterm and others)
+            let _ = Term::stdout().write_str("\x1b[10;1t");
+        }
+        ESCAPES.store(0, Ordering::SeqCst);
+        OVERLAY.store(true, Ordering::SeqCst);
+        Ok(Overlay { took_screen })
+    }
+
+    // Draw the break countdown over the whole screen
+    pub fn draw(&self, app_state: &AppState) {
+        let session = app_state.session.lock().unwrap().clone();
+        let guard = SCREEN.lock().unwrap();
+        if guard.is_none() {
+            return;
+        }
+        let term = Term::stdout();
+        let (height, width) = term.size();
+        let lines = overlay_frame(session.as_ref(), width as usize);
+        let top = (height as usize).saturating_sub(lines.len()) / 2;
+        let mut text = String::from("\x1b[H\x1b[J");
+        text.push_str(&"\r\n".repeat(top));
+        text.push_str(&lines.join("\r\n"));
+        let _ = term.write_str(&text);
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        OVERLAY.store(false, Ordering::SeqCst);
+        if self.took_screen {
+            let _ = Term::stdout().write_str("\x1b[10;0t");
+            for message in restore() {
+                println!("{}", message);
+            }
+        }
+    }
+}
+
+/// Handle a key while the overlay is up; returns false (the key is not
+/// the overlay's) when it isn't
+pub fn overlay_key(app_state: &AppState, byte: u8) -> bool {
+    if !OVERLAY.load(Ordering::SeqCst) {
+        return false;
+    }
+    if byte != 0x1b {
+        ESCAPES.store(0, Ordering::SeqCst);
+    } else if ESCAPES.fetch_add(1, Ordering::SeqCst) + 1 >= ESCAPE_PRESSES {
+        ESCAPES.store(0, Ordering::SeqCst);
+        crate::say!("Ending the break early.");
+        app_state.skip.store(true, Ordering::SeqCst);
+    }
+    true
+}
+
+/// Whether `say!` messages go to the dashboard
+pub fn is_active() -> bool {
+    SCREEN.lock().unwrap().is_some()
+}
+
+/// Keep a message for the dashboard
+pub fn log(message: String) {
+    if let Some(screen) = SCREEN.lock().unwrap().as_mut() {
+        screen.messages.push(message);
+        let excess = screen.messages.len().saturating_sub(MESSAGES);
+        screen.messages.drain(..excess);
+    }
+}
+
+// Put the terminal back the way it was. Returns the messages still on the
+// dashboard; does nothing (and returns none) if it isn't up.
+pub fn restore() -> Vec<String> {
+    let Some(screen) = SCREEN.lock().unwrap().take() else {
+        return Vec::new();
+    };
+    let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    screen.messages
+}
+
+// Switch the terminal to the alternate screen in raw mode, keeping `say!`
+// messages from then on
+fn take_screen() -> io::Result<()> {
+    if !Term::stdout().is_term() || !Term::stderr().is_term() {
+        return Err(io::Error::other("this needs a terminal"));
+    }
+    terminal::enable_raw_mode()?;
+    *SCREEN.lock().unwrap() = Some(Screen {
+        messages: Vec::new(),
+    });
+    // Alternate screen, so the shell's scrollback is left alone
+    if let Err(e) = execute!(io::stdout(), EnterAlternateScreen, Hide) {
+        restore();
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Redraw until the dashboard is restored
+fn draw_loop(app_state: &AppState) {
+    let term = Term::stdout();
+    // Today's pomodoros come from the session log, reread when a phase ends
+    let mut position = None;
+    let mut today = 0;
+    let mut goal = None;
+    loop {
+        let session = app_state.session.lock().unwrap().clone();
+        let now_at = session.as_ref().map(|s| (s.cycle, s.phase));
+        if position != Some(now_at) {
+            position = Some(now_at);
+            let records = history::load().unwrap_or_default();
+            let date = Local::now().date_naive();
+            today = completed_on(&records, date);
+            goal = session
+                .as_ref()
+                .and_then(|s| s.daily_goal)
+                .map(|daily_goal| Progress::of(&records, daily_goal, date));
+        }
+
+        let guard = SCREEN.lock().unwrap();
+        let Some(screen) = guard.as_ref() else {
+            return;
+        };
+        // The break overlay is drawn over the dashboard
+        if OVERLAY.load(Ordering::SeqCst) {
+            drop(guard);
+            thread::sleep(REDRAW);
+            continue;
+        }
+        let view = View {
+            session: session.as_ref(),
+            state: app_state.run_state(),
+            today,
+            goal,
+            messages: &screen.messages,
+            note: NOTE
+                .lock()
+                .unwrap()
+                .as_deref()
+                .map(|note| String::from_utf8_lossy(note).into_owned()),
+            now: Local::now(),
+        };
+        let (_, width) = term.size();
+        let mut text = String::from("\x1b[H");
+        for line in frame(&view, width as usize) {
+            // Clear the rest of each line, then everything below
+            text.push_str(&line);
+            text.push_str("\x1b[K\r\n");
+        }
+        text.push_str("\x1b[J");
+        let _ = term.write_str(&text);
+        drop(guard);
+
+        thread::sleep(REDRAW);
+    }
+}
+
+// Handle single-key commands; these do what the matching `pomo` subcommands do
+fn read_keys(app_state: &AppState) {
+    let mut byte = [0u8];
+    while io::stdin().read(&mut byte).is_ok_and(|n| n == 1) {
+        if overlay_key(app_state, byte[0]) || note_key(app_state, byte[0]) {
+            continue;
+        }
+        let waiting = app_state.waiting.load(Ordering::SeqCst);
+        match byte[0] {
+            // Ctrl+C
+            0x03 => cleanup::interrupt(app_state),
+            b'\n' | b'\r' | b' ' | b's' if waiting => {
+                app_state.waiting.store(false, Ordering::SeqCst);
+            }
+            b'z' => {
+                app_state.snooze(runner::SNOOZE_STEP);
+            }
+            // Nothing to pause while the next phase waits to start
+            b'p' if waiting => {}
+            b'p' => {
+                app_state.paused.fetch_xor(true, Ordering::SeqCst);
+            }
+            b's' => app_state.skip.store(true, Ordering::SeqCst),
+            b'+' | b'=' => {
+                app_state.extend(runner::EXTEND_STEP);
+            }
+            b'-' => {
+                app_state.extend(-runner::EXTEND_STEP);
+            }
+            b'i' => *NOTE.lock().unwrap() = Some(Vec::new()),
+            b'q' => app_state.stop.store(true, Ordering::SeqCst),
+            _ => {}
+        }
+    }
+}
+
+// Handle a key while a note is being typed; returns false when there is no
+// note open
+fn note_key(app_state: &AppState, byte: u8) -> bool {
+    let mut note = NOTE.lock().unwrap();
+    let Some(text) = note.as_mut() else {
+        return false;
+    };
+    match byte {
+        b'\n' | b'\r' => {
+            let text = String::from_utf8_lossy(&note.take().unwrap_or_default()).into_owned();
+            drop(note);
+            if !text.trim().is_empty() {
+                match app_state.note(&text) {
+                    Ok(()) => crate::say!("Noted: {}", text.trim()),
+                    Err(e) => crate::say!("Failed to write to the inbox: {}", e),
+                }
+            }
+        }
+        // Esc
+        0x1b => *note = None,
+        // Backspace (DEL or ^H): drop the last character
+        0x7f | 0x08 => {
+            let mut chars = String::from_utf8_lossy(text).into_owned();
+            chars.pop();
+            *text = chars.into_bytes();
+        }
+        _ => text.push(byte),
+    }
+    true
+}
+
+// Focus phases completed on the day, according to the session log
+pub fn completed_on(records: &[PhaseRecord], date: NaiveDate) -> usize {
+    records
+        .iter()
+        .filter(|r| r.phase == Phase::Focus && r.outcome == Outcome::Completed)
+        .filter(|r| r.ended_at.date_naive() == date)
+        .count()
+}
+
+/// Everything one frame shows
+struct View<'a> {
+    session: Option<&'a PomodoroSession>,
+    /// As in `AppState::run_state`
+    state: &'static str,
+    /// Pomodoros completed today
+    today: usize,
+    /// Progress toward the daily goal, if one is set
+    goal: Option<Progress>,
+    messages: &'a [String],
+    /// The note being typed, if one is open
+    note: Option<String>,
+    now: DateTime<Local>,
+}
+
+// Lay out one frame, `width` columns wide
+fn frame(view: &View, width: usize) -> Vec<String> {
+    let mut lines = vec![spread(
+        "rust-pomo",
+        &view.now.format("%H:%M").to_string(),
+        width,
+    )];
+    lines.push(String::new());
+
+    let Some(session) = view.session else {
+        lines.push(center("Starting…", width));
+        return lines;
+    };
+
+    // Phase, cycle and what the session is doing if not just counting down
+    let name = match session.phase {
+        Phase::Focus => style("FOCUS").red().bold(),
+        Phase::Break => style("BREAK").green().bold(),
+    };
+    let mut header = format!(
+        "{} · cycle {}",
+        name,
+        cycle_of(session.cycle, session.cycles)
+    );
+    if let Some(label) = session.label() {
+        header.push_str(&format!(" · {}", label));
+    }
+    if let Some(planned) = session.planned.filter(|_| session.phase == Phase::Focus) {
+        header.push_str(&format!(" · {}", planned));
+    }
+    match view.state {
+        "paused" => header.push_str(&format!(" {}", style("(paused)").yellow())),
+        "waiting" => header.push_str(&format!(" {}", style("(ready)").yellow())),
+        "snoozed" => header.push_str(&format!(" {}", style("(snoozed)").blue())),
+        _ => {}
+    }
+    lines.push(center(&header, width));
+    lines.push(String::new());
+
+    // Big countdown, or the overtime counting up
+    let (time, overtime) = if view.state == "overtime" || session.overtime_elapsed > 0 {
+        (format!("+{}", clock(session.overtime_elapsed)), true)
+    } else {
+        (clock(session.remaining()), false)
+    };
+    for row in big_digits(&time) {
+        let row = if overtime {
+            style(row).yellow().to_string()
+        } else {
+            row
+        };
+        lines.push(center(&row, width));
+    }
+    lines.push(String::new());
+
+    // Progress through the phase and when it ends
+    let duration = session.phase_len();
+    let done = (session.elapsed.min(duration) * BAR_WIDTH as u64)
+        .checked_div(duration)
+        .map_or(BAR_WIDTH, |done| done as usize);
+    let percent = (session.elapsed * 100)
+        .checked_div(duration)
+        .unwrap_or(100)
+        .min(100);
+    let ends = view.now + chrono::Duration::seconds(session.remaining() as i64);
+    lines.push(center(
+        &format!(
+            "[{}{}] {:>3}%  ends {}",
+            style("#".repeat(done)).cyan(),
+            style("-".repeat(BAR_WIDTH - done)).blue(),
+            percent,
+            ends.format("%H:%M")
+        ),
+        width,
+    ));
+    lines.push(String::new());
+
+    // Cycles done, the current one and those to come, and today's count
+    // Running until stopped, only the cycles so far
+    let last = match session.cycles {
+        0 => session.cycle,
+        cycles => cycles,
+    };
+    let cycles: String = (1..=last)
+        .map(|cycle| match cycle.cmp(&session.cycle) {
+            std::cmp::Ordering::Less => '●',
+            std::cmp::Ordering::Equal => '◐',
+            std::cmp::Ordering::Greater => '○',
+        })
+        .collect();
+    let today = match view.goal {
+        Some(goal) if goal.streak > 0 => {
+            format!(
+                "{}/{} 🍅 · {}-day streak",
+                goal.done, goal.goal, goal.streak
+            )
+        }
+        Some(goal) => format!("{}/{} 🍅", goal.done, goal.goal),
+        None => format!("{} 🍅", view.today),
+    };
+    lines.push(center(
+        &format!("Cycles {}    Today {}", cycles, today),
+        width,
+    ));
+    lines.push(String::new());
+
+    for message in view.messages {
+        lines.push(center(&style(message).dim().to_string(), width));
+    }
+    lines.push(String::new());
+
+    if let Some(note) = &view.note {
+        lines.push(center(
+            &format!("Note: {}▏ {}", note, style("enter save · esc cancel").dim()),
+            width,
+        ));
+        return lines;
+    }
+    let keys = if view.state == "waiting" || view.state == "snoozed" {
+        "enter start next phase · z snooze 5m · q stop"
+    } else if view.state == "overtime" {
+        "enter start next phase · q stop"
+    } else {
+        "p pause/resume · s skip · +/- 5m · i note · q stop"
+    };
+    lines.push(center(&style(keys).dim().to_string(), width));
+    lines
+}
+
+// `left` and `right` at either end of a line
+fn spread(left: &str, right: &str, width: usize) -> String {
+    let gap = width.saturating_sub(measure_text_width(left) + measure_text_width(right));
+    format!("{}{}{}", left, " ".repeat(gap.max(1)), right)
+}
+
+fn center(text: &str, width: usize) -> String {
+    let pad = width.saturating_sub(measure_text_width(text)) / 2;
+    format!("{}{}", " ".repeat(pad), text)
+}
+
+// The break overlay: what to do, the big countdown and the way out
+fn overlay_frame(session: Option<&PomodoroSession>, width: usize) -> Vec<String> {
+    let mut lines = vec![
+        center(&style("BREAK").green().bold().to_string(), width),
+        String::new(),
+    ];
+    let remaining = session.map_or(0, |s| s.remaining());
+    for row in big_digits(&clock(remaining)) {
+        lines.push(center(&style(row).green().to_string(), width));
+    }
+    lines.push(String::new());
+    lines.push(center(
+        "Step away: stand up, stretch, look into the distance.",
+        width,
+    ));
+    lines.push(String::new());
+    lines.push(center(
+        &style("Esc Esc Esc ends the break early").dim().to_string(),
+        width,
+    ));
+    lines
+}
+
+// Render a clock ("12:34", "+1:02:03") in large block characters, five rows tall
+fn big_digits(text: &str) -> [String; 5] {
+    let mut rows: [String; 5] = Default::default();
+    for (i, c) in text.chars().enumerate() {
+        let glyph = glyph(c);
+        for (row, part) in rows.iter_mut().zip(glyph) {
+            if i > 0 {
+                row.push_str("  ");
+            }
+            // Each cell is two characters wide so digits come out about square
+            for cell in part.chars() {
+                row.push_str(if cell == '#' { "██" } else { "  " });
+            }
+        }
+    }
+    rows
+}
+
+fn glyph(c: char) -> [&'static str; 5] {
+    match c {
+        '0' => ["###", "# #", "# #", "# #", "###"],
+        '1' => ["  #", "  #", "  #", "  #", "  #"],
+        '2' => ["###", "  #", "###", "#  ", "###"],
+        '3' => ["###", "  #", "###", "  #", "###"],
+        '4' => ["# #", "# #", "###", "  #", "  #"],
+        '5' => ["###", "#  ", "###", "  #", "###"],
+        '6' => ["###", "#  ", "###", "# #", "###"],
+        '7' => ["###", "  #", "  #", "  #", "  #"],
+        '8' => ["###", "# #", "###", "# #", "###"],
+        '9' => ["###", "# #", "###", "  #", "###"],
+        ':' => [" ", "#", " ", "#", " "],
+        '+' => ["   ", " # ", "###", " # ", "   "],
+        _ => ["   ", "   ", "   ", "   ", "   "],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_digits_are_five_rows_of_blocks() {
+        let rows = big_digits("1:0");
+        assert_eq!(rows[0], "    ██      ██████");
+        assert_eq!(rows[1], "    ██  ██  ██  ██");
+        assert_eq!(rows[4], "    ██      ██████");
+    }
+
+    #[test]
+    fn overlay_shows_break_countdown_and_escape() {
+        console::set_colors_enabled(false);
+        let mut session = PomodoroSession::new(25 * 60, 5 * 60, 4);
+        session.phase = Phase::Break;
+        session.elapsed = 60;
+        let text = overlay_frame(Some(&session), 80).join("\n");
+        assert!(text.contains("BREAK"));
+        assert!(text.contains(&big_digits("04:00")[0]));
+        assert!(text.contains("Esc Esc Esc ends the break early"));
+    }
+
+    #[test]
+    fn frame_shows_phase_countdown_and_cycles() {
+        console::set_colors_enabled(false);
+        let mut session = PomodoroSession::new(25 * 60, 5 * 60, 4);
+        session.cycle = 2;
+        session.elapsed = 10 * 60;
+        session.task = Some("write report".to_string());
+        let now = Local::now();
+        let view = View {
+            session: Some(&session),
+            state: "paused",
+            today: 3,
+            goal: None,
+            messages: &["Pause toggled".to_string()],
+            note: None,
+            now,
+        };
+
+        let text = frame(&view, 80).join("\n");
+        assert!(text.contains("FOCUS · cycle 2/4 · write report (paused)"));
+        assert!(text.contains(&big_digits("15:00")[0]));
+        assert!(text.contains(" 40%  ends "));
+        assert!(text.contains("Cycles ●◐○○    Today 3 🍅"));
+        assert!(text.contains("Pause toggled"));
+
+        let goal = Progress {
+            done: 3,
+            goal: 8,
+            streak: 4,
+        };
+        let view = View {
+            goal: Some(goal),
+            ..view
+        };
+        let text = frame(&view, 80).join("\n");
+        assert!(text.contains("Today 3/8 🍅 · 4-day streak"));
+        assert!(text.contains("i note · q stop"));
+
+        let view = View {
+            note: Some("call the bank".to_string()),
+            ..view
+        };
+        let text = frame(&view, 80).join("\n");
+        assert!(text.contains("Note: call the bank▏ enter save · esc cancel"));
+    }
+}
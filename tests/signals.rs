@@ -0,0 +1,234 @@
+// Killing a session mid-focus must not leave anything blocked, SIGUSR1 /
+// SIGUSR2 control a running session, `pomo stop` ends it for good and named
+// sessions running side by side share the blockers. The hosts blocker is
+// pointed at a scratch file (POMO_HOSTS_FILE) so its state can be checked from
+// outside; state and data go to a scratch HOME as well.
+#![cfg(unix)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const HOSTS: &str = "127.0.0.1 localhost\n";
+const MARKER: &str = "# BEGIN rust-pomo";
+
+// A fresh directory for one test run
+fn scratch_dir(name: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("pomo-test-{}-{}", name, nanos));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// `pomo` with everything pointed into `dir`
+fn pomo(dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_pomo"));
+    command
+        .env("HOME", dir)
+        .env("XDG_STATE_HOME", dir.join("state"))
+        .env("XDG_DATA_HOME", dir.join("data"))
+        .env("XDG_CONFIG_HOME", dir.join("config"))
+        .env("POMO_HOSTS_FILE", dir.join("hosts"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    command
+}
+
+fn start_session(dir: &Path) -> Child {
+    pomo(dir)
+        .args(["--focus", "10m", "--block", "hosts", "--no-notify"])
+        .spawn()
+        .unwrap()
+}
+
+// `pomo status --json` of the session running in `dir`
+fn status(dir: &Path) -> String {
+    let output = pomo(dir)
+        .args(["status", "--json"])
+        .stdout(Stdio::piped())
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn send_signal(child: &Child, signal: &str) {
+    let status = Command::new("kill")
+        .args([&format!("-{}", signal), &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+// Poll until `check` passes, failing the test after a few seconds
+fn wait_for(what: &str, mut check: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !check() {
+        assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn kill_mid_focus(signal: &str) {
+    let dir = scratch_dir(signal);
+    let hosts = dir.join("hosts");
+    fs::write(&hosts, HOSTS).unwrap();
+
+    let mut child = start_session(&dir);
+    wait_for("sites to be blocked", || {
+        fs::read_to_string(&hosts).unwrap().contains(MARKER)
+    });
+
+    send_signal(&child, signal);
+    wait_for("the session to exit", || {
+        child.try_wait().unwrap().is_some()
+    });
+
+    assert_eq!(fs::read_to_string(&hosts).unwrap(), HOSTS);
+    // The position is kept for `pomo resume`
+    assert!(dir.join("state/rust-pomo/session.json").is_file());
+    // and the control socket is gone
+    assert!(!dir.join("state/rust-pomo/pomo.sock").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sigterm_mid_focus_releases_blockers() {
+    kill_mid_focus("TERM");
+}
+
+#[test]
+fn sighup_mid_focus_releases_blockers() {
+    kill_mid_focus("HUP");
+}
+
+#[test]
+fn sigint_mid_focus_releases_blockers() {
+    kill_mid_focus("INT");
+}
+
+#[test]
+fn stop_command_ends_the_session_gracefully() {
+    let dir = scratch_dir("stop");
+    let hosts = dir.join("hosts");
+    fs::write(&hosts, HOSTS).unwrap();
+
+    let mut child = start_session(&dir);
+    wait_for("sites to be blocked", || {
+        fs::read_to_string(&hosts).unwrap().contains(MARKER)
+    });
+
+    let stop = pomo(&dir).arg("stop").status().unwrap();
+    assert!(stop.success());
+    let mut exit = None;
+    wait_for("the session to exit", || {
+        exit = child.try_wait().unwrap();
+        exit.is_some()
+    });
+
+    assert_eq!(exit.unwrap().code(), Some(3));
+    assert_eq!(fs::read_to_string(&hosts).unwrap(), HOSTS);
+    // Nothing is left to resume, and the phase is in the log
+    assert!(!dir.join("state/rust-pomo/session.json").exists());
+    let log = fs::read_to_string(dir.join("data/rust-pomo/history.jsonl")).unwrap();
+    assert!(log.contains(r#""outcome":"stopped""#));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn sigusr1_toggles_pause_and_sigusr2_skips() {
+    let dir = scratch_dir("USR");
+    let hosts = dir.join("hosts");
+    fs::write(&hosts, HOSTS).unwrap();
+
+    let mut child = start_session(&dir);
+    wait_for("the session to start", || {
+        status(&dir).contains(r#""state":"running""#)
+    });
+
+    send_signal(&child, "USR1");
+    wait_for("the session to pause", || {
+        status(&dir).contains(r#""state":"paused""#)
+    });
+    send_signal(&child, "USR1");
+    wait_for("the session to resume", || {
+        status(&dir).contains(r#""state":"running""#)
+    });
+
+    // Skipping focus leaves the break waiting to start
+    send_signal(&child, "USR2");
+    wait_for("focus to be skipped", || {
+        let status = status(&dir);
+        status.contains(r#""state":"waiting""#) && status.contains(r#""phase":"break""#)
+    });
+
+    send_signal(&child, "TERM");
+    wait_for("the session to exit", || {
+        child.try_wait().unwrap().is_some()
+    });
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn named_sessions_share_blockers() {
+    let dir = scratch_dir("named");
+    let hosts = dir.join("hosts");
+    fs::write(&hosts, HOSTS).unwrap();
+
+    let mut first = start_session(&dir);
+    wait_for("sites to be blocked", || {
+        fs::read_to_string(&hosts).unwrap().contains(MARKER)
+    });
+    let mut second = pomo(&dir)
+        .args([
+            "--name",
+            "meeting",
+            "--focus",
+            "10m",
+            "--block",
+            "hosts",
+            "--no-notify",
+        ])
+        .spawn()
+        .unwrap();
+    wait_for("the named session to start", || {
+        let output = pomo(&dir)
+            .arg("list")
+            .stdout(Stdio::piped())
+            .output()
+            .unwrap();
+        let list = String::from_utf8(output.stdout).unwrap();
+        list.contains("default") && list.contains("meeting")
+    });
+    assert!(dir.join("state/rust-pomo/pomo.meeting.sock").exists());
+
+    // The sites stay blocked while the other session still needs them
+    assert!(
+        pomo(&dir)
+            .args(["stop", "--name", "meeting"])
+            .status()
+            .unwrap()
+            .success()
+    );
+    wait_for("the named session to exit", || {
+        second.try_wait().unwrap().is_some()
+    });
+    assert!(fs::read_to_string(&hosts).unwrap().contains(MARKER));
+
+    assert!(pomo(&dir).arg("stop").status().unwrap().success());
+    wait_for("the session to exit", || {
+        first.try_wait().unwrap().is_some()
+    });
+    assert_eq!(fs::read_to_string(&hosts).unwrap(), HOSTS);
+
+    let _ = fs::remove_dir_all(&dir);
+}